@@ -0,0 +1,314 @@
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
+
+use crate::default_fn::LazyDefault;
+use crate::DefaultFn;
+
+/// A map with a maximum number of entries that returns a default for keys that are not present.
+///
+/// When a new key is inserted while the map is full, the least recently used entry is evicted.
+/// Evicted keys return the default again, so this can be used as a memoization cache. Inserting,
+/// `get` and `get_mut` count as a use of the key, while `peek` and `map[key]` don't.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut cache: BoundedDefaultMap<&str, u32> = BoundedDefaultMap::new(2);
+/// cache["a"] += 1;
+/// cache["b"] += 2;
+/// cache.get("a");
+/// cache["c"] += 3;
+///
+/// assert_eq!(cache.len(), 2);
+/// assert_eq!(cache["a"], 1);
+/// assert_eq!(cache["b"], 0);
+/// assert_eq!(cache["c"], 3);
+/// ```
+#[derive(Clone)]
+pub struct BoundedDefaultMap<K: Eq + Hash, V> {
+    map: HashMap<K, (V, u64)>,
+    order: BTreeMap<u64, K>,
+    last_use: u64,
+    capacity: usize,
+    default: LazyDefault<V>,
+}
+
+impl<K: Eq + Hash, V: Default> BoundedDefaultMap<K, V> {
+    /// Creates an empty map that holds at most `capacity` entries, with the default of `V` as the
+    /// default for missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> BoundedDefaultMap<K, V> {
+        BoundedDefaultMap::with_lazy_default(capacity, LazyDefault::std_default())
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> BoundedDefaultMap<K, V> {
+    /// Creates an empty map that holds at most `capacity` entries, with `default` as the default
+    /// for missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_default(capacity: usize, default: V) -> BoundedDefaultMap<K, V> {
+        BoundedDefaultMap::with_lazy_default(capacity, LazyDefault::value(default))
+    }
+}
+
+impl<K: Eq + Hash, V> BoundedDefaultMap<K, V> {
+    /// Creates an empty map that holds at most `capacity` entries, with `default_fn` as the
+    /// default value generation function for missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_fn(
+        capacity: usize,
+        default_fn: impl DefaultFn<V> + 'static,
+    ) -> BoundedDefaultMap<K, V> {
+        BoundedDefaultMap::with_lazy_default(capacity, LazyDefault::with_fn(default_fn))
+    }
+
+    fn with_lazy_default(capacity: usize, default: LazyDefault<V>) -> BoundedDefaultMap<K, V> {
+        assert!(
+            capacity > 0,
+            "BoundedDefaultMap needs a capacity of at least one"
+        );
+        BoundedDefaultMap {
+            map: HashMap::new(),
+            order: BTreeMap::new(),
+            last_use: 0,
+            capacity,
+            default,
+        }
+    }
+
+    /// Returns the maximum number of entries the map holds.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns `true` if a value is stored for the key, i.e. it was inserted and not evicted yet.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Returns a reference to the default value of the map.
+    pub fn default_value(&self) -> &V {
+        self.default.get()
+    }
+
+    /// Returns the value stored for the key or the default, without counting this as a use of
+    /// the key.
+    pub fn peek<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        match self.map.get(key) {
+            Some((value, _)) => value,
+            None => self.default.get(),
+        }
+    }
+
+    /// Returns the value stored for the key or the default. If a value is stored, the key is
+    /// marked as the most recently used one.
+    pub fn get<Q>(&mut self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        if self.touch(key) {
+            &self.map[key].0
+        } else {
+            self.default.get()
+        }
+    }
+
+    /// Removes the key from the map and returns its value, if it was stored.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let (value, last_use) = self.map.remove(key)?;
+        self.order.remove(&last_use);
+        Some(value)
+    }
+
+    /// Removes the least recently used entry and returns it.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let (_, key) = self.order.pop_first()?;
+        let (value, _) = self
+            .map
+            .remove(&key)
+            .expect("every key in the order is in the map");
+        Some((key, value))
+    }
+
+    /// Returns an iterator over the entries, from the least recently used to the most recently
+    /// used one.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.order.values().map(move |key| (key, &self.map[key].0))
+    }
+
+    /// Removes all entries from the map.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.order.clear();
+    }
+
+    /// Marks the key as the most recently used one, if it's stored. Returns whether it was.
+    fn touch<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        match self.map.get_mut(key) {
+            Some((_, last_use)) => {
+                let key = self
+                    .order
+                    .remove(last_use)
+                    .expect("every key in the map is in the order");
+                self.last_use += 1;
+                *last_use = self.last_use;
+                self.order.insert(self.last_use, key);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> BoundedDefaultMap<K, V> {
+    /// Returns a mutable reference to the value stored for the key, inserting the default first
+    /// if there is none. The key is marked as the most recently used one, and inserting it
+    /// evicts the least recently used entry if the map is full.
+    pub fn get_mut(&mut self, key: K) -> &mut V {
+        if !self.touch(&key) {
+            let value = self.default.create();
+            self.insert_new(key.clone(), value);
+        }
+        &mut self.map.get_mut(&key).expect("the key was just used").0
+    }
+
+    /// Inserts a value for the key and returns the previous value, if there was one. The key is
+    /// marked as the most recently used one, and inserting it evicts the least recently used
+    /// entry if the map is full.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.touch(&key) {
+            let stored = &mut self.map.get_mut(&key).expect("the key was just used").0;
+            return Some(std::mem::replace(stored, value));
+        }
+        self.insert_new(key, value);
+        None
+    }
+
+    fn insert_new(&mut self, key: K, value: V) {
+        if self.map.len() == self.capacity {
+            self.pop_lru();
+        }
+        self.last_use += 1;
+        self.order.insert(self.last_use, key.clone());
+        self.map.insert(key, (value, self.last_use));
+    }
+}
+
+/// The `Debug` output lists the entries from the least recently used to the most recently used
+/// one.
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug> fmt::Debug for BoundedDefaultMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<_> = self.iter().collect();
+        f.debug_struct("BoundedDefaultMap")
+            .field("entries", &entries)
+            .field("capacity", &self.capacity)
+            .field("default", self.default.get())
+            .finish()
+    }
+}
+
+/// Implements the `Index` trait so you can do `map[key]`. Like `peek`, this doesn't count as a
+/// use of the key.
+impl<K: Eq + Hash, KB: Borrow<K>, V> Index<KB> for BoundedDefaultMap<K, V> {
+    type Output = V;
+
+    fn index(&self, index: KB) -> &V {
+        self.peek(index.borrow())
+    }
+}
+
+/// Implements the `IndexMut` trait so you can do `map[key] = val`, see `get_mut`.
+impl<K: Eq + Hash + Clone, V> IndexMut<K> for BoundedDefaultMap<K, V> {
+    fn index_mut(&mut self, index: K) -> &mut V {
+        self.get_mut(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoundedDefaultMap;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut map: BoundedDefaultMap<i32, i32> = BoundedDefaultMap::with_default(3, -1);
+        map.insert(1, 10);
+        map.insert(2, 20);
+        map.insert(3, 30);
+        assert_eq!(*map.get(&1), 10);
+        assert_eq!(map.peek(&2), &20);
+        assert_eq!(map.insert(4, 40), None);
+        assert!(!map.contains_key(&2));
+        assert_eq!(map[2], -1);
+
+        map[3] += 1;
+        map.insert(5, 50);
+        assert!(!map.contains_key(&1));
+        let keys: Vec<i32> = map.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, [4, 3, 5]);
+        assert_eq!(map.insert(5, 51), Some(50));
+        assert_eq!(map.pop_lru(), Some((4, 40)));
+        assert_eq!(map.remove(&3), Some(31));
+        assert_eq!(map.len(), 1);
+        assert_eq!(
+            format!("{:?}", map),
+            "BoundedDefaultMap { entries: [(5, 51)], capacity: 3, default: -1 }"
+        );
+    }
+
+    #[test]
+    fn get_of_missing_key_does_not_insert() {
+        let mut map: BoundedDefaultMap<&str, Vec<i32>> = BoundedDefaultMap::with_fn(1, Vec::new);
+        assert!(map.get("a").is_empty());
+        assert!(map.is_empty());
+        map["a"].push(1);
+        map["b"].push(2);
+        assert!(map["a"].is_empty());
+        assert_eq!(map["b"], [2]);
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one")]
+    fn zero_capacity() {
+        let _: BoundedDefaultMap<i32, i32> = BoundedDefaultMap::new(0);
+    }
+}
@@ -1,32 +1,66 @@
-use derive_more::Debug;
+//! A `BTreeMap` that returns a default for missing keys, and the types used by its methods.
+
 use std::borrow::Borrow;
 use std::collections::btree_map::*;
 use std::collections::BTreeMap;
-use std::iter::{FromIterator, IntoIterator};
+use std::fmt;
+use std::hash::Hash;
+use std::iter::{Cloned, FromIterator, IntoIterator, Rev, Sum, Take};
+use std::ops::Bound::{Excluded, Included, Unbounded};
 use std::ops::RangeBounds;
-use std::ops::{Index, IndexMut};
+use std::ops::{
+    Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, Deref, DerefMut, Index, IndexMut,
+    Sub, SubAssign,
+};
+#[cfg(target_has_atomic = "64")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
-use crate::DefaultFn;
+use crate::default_fn::{LazyDefault, NamedFn};
+use crate::missing::Missing;
+use crate::redacted::MaskedEntries;
+use crate::{
+    AsF64, CompositeKey, DefaultFn, DefaultHashMap, DefaultMap, MapDiff, OccupiedError, One,
+    RangeByFirst, Redacted, TryDefaultFn, ValueStats,
+};
 
 /// A `BTreeMap` that returns a default when keys are accessed that are not present.
-#[derive(Clone, Debug)]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "with-serde",
+    serde(bound(serialize = "K: serde::Serialize, V: serde::Serialize"))
+)]
 pub struct DefaultBTreeMap<K: Eq + Ord, V> {
     map: BTreeMap<K, V>,
-    default: V,
-    #[debug(skip)]
+    default: LazyDefault<V>,
     #[cfg_attr(feature = "with-serde", serde(skip))]
-    default_fn: Box<dyn DefaultFn<V>>,
+    missing: Missing<K>,
 }
 
 impl<K: Eq + Ord, V: PartialEq> PartialEq for DefaultBTreeMap<K, V> {
     fn eq(&self, other: &Self) -> bool {
-        self.map == other.map && self.default == other.default
+        self.map == other.map && self.default.get() == other.default.get()
     }
 }
 
 impl<K: Eq + Ord, V: Eq> Eq for DefaultBTreeMap<K, V> {}
 
+/// The `Debug` output contains the stored entries and the default. When the map was created with
+/// a named default value generation function, its name is included too.
+impl<K: Eq + Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for DefaultBTreeMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("DefaultBTreeMap");
+        debug
+            .field("map", &self.map)
+            .field("default", self.default.get());
+        if let Some(name) = self.default.name() {
+            debug.field("default_fn", &name);
+        }
+        debug.finish()
+    }
+}
+
 impl<K: Eq + Ord, V: Default> DefaultBTreeMap<K, V> {
     /// The `new()` constructor creates an empty DefaultBTreeMap with the default of `V`
     /// as the default for missing keys.
@@ -35,8 +69,8 @@ impl<K: Eq + Ord, V: Default> DefaultBTreeMap<K, V> {
     pub fn new() -> DefaultBTreeMap<K, V> {
         DefaultBTreeMap {
             map: BTreeMap::default(),
-            default_fn: Box::new(|| V::default()),
-            default: V::default(),
+            default: LazyDefault::std_default(),
+            missing: Missing::default(),
         }
     }
 }
@@ -57,12 +91,28 @@ impl<K: Eq + Ord, V: Default> From<BTreeMap<K, V>> for DefaultBTreeMap<K, V> {
     fn from(map: BTreeMap<K, V>) -> DefaultBTreeMap<K, V> {
         DefaultBTreeMap {
             map,
-            default_fn: Box::new(|| V::default()),
-            default: V::default(),
+            default: LazyDefault::std_default(),
+            missing: Missing::default(),
         }
     }
 }
 
+impl<K: Eq + Ord, V: Default, const N: usize> From<[(K, V); N]> for DefaultBTreeMap<K, V> {
+    /// Creates a `DefaultBTreeMap` from an array of key-value pairs, like `BTreeMap::from` does.
+    /// The default value for missing keys will be `V::default()`,
+    /// if this is not desired `DefaultBTreeMap::from_array_with_default()` should be used.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let map = DefaultBTreeMap::from([("a", 1), ("b", 2)]);
+    /// assert_eq!(map["b"], 2);
+    /// assert_eq!(map["c"], 0);
+    /// ```
+    fn from(entries: [(K, V); N]) -> DefaultBTreeMap<K, V> {
+        DefaultBTreeMap::from(BTreeMap::from(entries))
+    }
+}
+
 impl<K: Eq + Ord, V> From<DefaultBTreeMap<K, V>> for BTreeMap<K, V> {
     /// The into method can be used to convert a `DefaultBTreeMap` back into a
     /// `BTreeMap`.
@@ -71,15 +121,45 @@ impl<K: Eq + Ord, V> From<DefaultBTreeMap<K, V>> for BTreeMap<K, V> {
     }
 }
 
-impl<K: Eq + Ord, V: Clone + 'static> DefaultBTreeMap<K, V> {
+impl<K: Eq + Hash + Ord, V, S> From<DefaultHashMap<K, V, S>> for DefaultBTreeMap<K, V> {
+    /// Converts a `DefaultHashMap` into a `DefaultBTreeMap` with the same entries and the same
+    /// default for missing keys. This is useful to get sorted output after aggregating into a
+    /// `DefaultHashMap`.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::with_default(1);
+    /// counts["b"] += 1;
+    /// counts["a"] += 1;
+    /// let sorted = DefaultBTreeMap::from(counts);
+    /// assert_eq!(sorted.keys().collect::<Vec<_>>(), [&"a", &"b"]);
+    /// assert_eq!(sorted["c"], 1);
+    /// ```
+    fn from(default_map: DefaultHashMap<K, V, S>) -> DefaultBTreeMap<K, V> {
+        let (map, default, missing) = default_map.into_parts();
+        DefaultBTreeMap {
+            map: map.into_iter().collect(),
+            default,
+            missing,
+        }
+    }
+}
+
+impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
+    pub(crate) fn into_parts(self) -> (BTreeMap<K, V>, LazyDefault<V>, Missing<K>) {
+        (self.map, self.default, self.missing)
+    }
+}
+
+impl<K: Eq + Ord, V: Clone> DefaultBTreeMap<K, V> {
     /// Creates an empty `DefaultBTreeMap` with `default` as the default for missing keys.
     /// When the provided `default` is equivalent to `V::default()` it is preferred to use
     /// `DefaultBTreeMap::default()` instead.
     pub fn with_default(default: V) -> DefaultBTreeMap<K, V> {
         DefaultBTreeMap {
             map: BTreeMap::new(),
-            default: default.clone(),
-            default_fn: Box::new(move || default.clone()),
+            default: LazyDefault::value(default),
+            missing: Missing::default(),
         }
     }
 
@@ -89,15 +169,92 @@ impl<K: Eq + Ord, V: Clone + 'static> DefaultBTreeMap<K, V> {
     pub fn from_map_with_default(map: BTreeMap<K, V>, default: V) -> DefaultBTreeMap<K, V> {
         DefaultBTreeMap {
             map,
-            default: default.clone(),
-            default_fn: Box::new(move || default.clone()),
+            default: LazyDefault::value(default),
+            missing: Missing::default(),
         }
     }
 
+    /// Creates a `DefaultBTreeMap` from an array of key-value pairs, with `default` as the default
+    /// for missing keys.
+    pub fn from_array_with_default<const N: usize>(
+        entries: [(K, V); N],
+        default: V,
+    ) -> DefaultBTreeMap<K, V> {
+        DefaultBTreeMap::from_map_with_default(BTreeMap::from(entries), default)
+    }
+
+    /// Creates a `DefaultBTreeMap` that contains a clone of `default` for every key, with `default` as
+    /// the default for missing keys too. Like Python's `dict.fromkeys`, this makes sure that the
+    /// keys are present when iterating over the map.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let totals: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::from_keys(["food", "rent"], 0);
+    /// assert_eq!(totals.len(), 2);
+    /// assert_eq!(totals["food"], 0);
+    /// ```
+    pub fn from_keys(keys: impl IntoIterator<Item = K>, default: V) -> DefaultBTreeMap<K, V> {
+        let map = keys.into_iter().map(|key| (key, default.clone())).collect();
+        DefaultBTreeMap::from_map_with_default(map, default)
+    }
+
     /// Changes the default value permanently or until `set_default()` is called again.
     pub fn set_default(&mut self, new_default: V) {
-        self.default = new_default.clone();
-        self.default_fn = Box::new(move || new_default.clone());
+        self.default = LazyDefault::value(new_default);
+    }
+
+    /// Creates a clone of the stored entries that uses `new_default` as the default for missing
+    /// keys.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, i32> = DefaultBTreeMap::new();
+    /// counts["a"] += 1;
+    /// let marked = counts.clone_with_default(-1);
+    /// assert_eq!(marked["a"], 1);
+    /// assert_eq!(marked["b"], -1);
+    /// assert_eq!(counts["b"], 0);
+    /// ```
+    pub fn clone_with_default(&self, new_default: V) -> DefaultBTreeMap<K, V>
+    where
+        K: Clone,
+    {
+        DefaultBTreeMap::from_map_with_default(self.map.clone(), new_default)
+    }
+
+    /// Calls `f` with the map while `temp_default` is used as its default, and restores the
+    /// previous default afterwards. Values that were inserted by `f` keep the temporary default.
+    /// The previous default is also restored if `f` panics.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut limits: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::with_default(10);
+    /// limits.with_scoped_default(100, |limits| {
+    ///     limits.get_mut("upload");
+    ///     assert_eq!(limits["download"], 100);
+    /// });
+    /// assert_eq!(limits["upload"], 100);
+    /// assert_eq!(limits["download"], 10);
+    /// ```
+    pub fn with_scoped_default<R>(&mut self, temp_default: V, f: impl FnOnce(&mut Self) -> R) -> R {
+        // Swaps the previous default back in when dropped, which also happens during unwinding.
+        struct Restore<'a, K: Eq + Ord, V> {
+            map: &'a mut DefaultBTreeMap<K, V>,
+            old_default: LazyDefault<V>,
+        }
+
+        impl<K: Eq + Ord, V> Drop for Restore<'_, K, V> {
+            fn drop(&mut self) {
+                std::mem::swap(&mut self.map.default, &mut self.old_default);
+            }
+        }
+
+        let old_default = std::mem::replace(&mut self.default, LazyDefault::value(temp_default));
+        let guard = Restore {
+            map: self,
+            old_default,
+        };
+        f(guard.map)
     }
 }
 
@@ -111,7 +268,57 @@ impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
         K: Borrow<Q>,
         Q: ?Sized + Ord + Eq,
     {
-        self.map.get(key.borrow()).unwrap_or(&self.default)
+        match self.map.get(key.borrow()) {
+            Some(value) => value,
+            None => {
+                self.missing.record_miss(None);
+                self.default.get()
+            }
+        }
+    }
+
+    /// Returns a reference to the value stored for the provided key, or `fallback` if the key is
+    /// not in the map. This is for a single lookup that needs a different value for missing keys
+    /// than the default of the map, which is left alone.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut limits: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::with_default(10);
+    /// limits["upload"] = 5;
+    /// assert_eq!(limits.get_or("upload", &0), &5);
+    /// assert_eq!(limits.get_or("download", &0), &0);
+    /// assert_eq!(limits["download"], 10);
+    /// ```
+    pub fn get_or<'a, Q>(&'a self, key: &Q, fallback: &'a V) -> &'a V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord + Eq,
+    {
+        self.map.get(key).unwrap_or(fallback)
+    }
+
+    /// Returns an iterator over the provided keys together with their values. Keys that are not
+    /// in the map get a reference to the default value, without inserting anything. This is
+    /// useful when a fixed set of keys should always be shown, even if there's no data for them.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// counts["error"] += 2;
+    /// let rows: Vec<_> = counts.iter_with_defaults(["info", "error"]).collect();
+    /// assert_eq!(rows, vec![("info", &0), ("error", &2)]);
+    /// assert_eq!(counts.len(), 1);
+    /// ```
+    pub fn iter_with_defaults<'a, I>(&'a self, keys: I) -> impl Iterator<Item = (I::Item, &'a V)>
+    where
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: Borrow<K>,
+    {
+        keys.into_iter().map(move |key| {
+            let value = self.map.get(key.borrow()).unwrap_or(self.default.get());
+            (key, value)
+        })
     }
 
     /// Returns the an owned version of the default value
@@ -120,17 +327,152 @@ impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
     /// assert_eq!(DefaultBTreeMap::<String, i32>::new().get_default(), 0);
     /// ```
     pub fn get_default(&self) -> V {
-        self.default_fn.call()
+        self.default.create()
+    }
+
+    /// Returns a reference to the default value, without creating a new one like `get_default`
+    /// does.
+    /// ```
+    /// use defaultmap::DefaultBTreeMap;
+    /// assert_eq!(DefaultBTreeMap::<String, i32>::with_default(3).default_value(), &3);
+    /// ```
+    pub fn default_value(&self) -> &V {
+        self.default.get()
+    }
+
+    /// Returns how often `get` or indexing returned the default because the key was missing.
+    /// This is only available with the `stats` feature.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut map: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// map["a"] += 1;
+    /// let _ = map["a"] + map["b"] + map["c"];
+    /// assert_eq!(map.miss_count(), 2);
+    /// assert_eq!(map.materialized_keys(), 1);
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn miss_count(&self) -> usize {
+        self.missing.stats.misses()
+    }
+
+    /// Returns how often the default was inserted for a missing key by `get_mut`, mutable
+    /// indexing or one of the methods that build on them. This is only available with the
+    /// `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn materialized_keys(&self) -> usize {
+        self.missing.stats.materialized()
+    }
+
+    /// Resets the counters of `miss_count` and `materialized_keys` to zero. This is only
+    /// available with the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.missing.stats.reset();
+    }
+
+    /// Changes the default value generation function permanently or until `set_default()` or
+    /// `set_default_fn()` is called again. Unlike `set_default` this doesn't require the default
+    /// to implement `Clone`. Values that are already stored are not changed.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut map: DefaultBTreeMap<i32, Vec<i32>> = DefaultBTreeMap::new();
+    /// map.set_default_fn(|| Vec::with_capacity(16));
+    /// assert!(map.get_mut(1).capacity() >= 16);
+    /// ```
+    pub fn set_default_fn(&mut self, default_fn: impl DefaultFn<V> + 'static) {
+        self.default = LazyDefault::with_fn(default_fn);
+    }
+
+    /// Sets a function that is called with the key whenever the default is used for a missing
+    /// key, e.g. to log lookups of unknown keys. It's called when indexing returns the default,
+    /// and when the default is inserted by `get_mut`, mutable indexing or one of the methods that
+    /// build on them. It's not called by `get`, because that only gets a borrowed form of the
+    /// key.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// let unknown = Rc::new(RefCell::new(Vec::new()));
+    /// let mut config: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::with_default(30);
+    /// config["timeout"] = 10;
+    /// let log = unknown.clone();
+    /// config.set_missing_hook(move |key| log.borrow_mut().push(*key));
+    ///
+    /// assert_eq!(config["timeout"], 10);
+    /// assert_eq!(config["timeuot"], 30);
+    /// assert_eq!(*unknown.borrow(), vec!["timeuot"]);
+    /// ```
+    pub fn set_missing_hook(&mut self, hook: impl Fn(&K) + 'static) {
+        self.missing.set_hook(Some(Arc::new(hook)));
+    }
+
+    /// Removes the function that was set with `set_missing_hook`.
+    pub fn clear_missing_hook(&mut self) {
+        self.missing.set_hook(None);
+    }
+
+    /// Creates an empty `DefaultBTreeMap` with the same default as this one.
+    pub fn clone_empty(&self) -> DefaultBTreeMap<K, V> {
+        DefaultBTreeMap {
+            map: BTreeMap::new(),
+            default: self.default.fresh(),
+            missing: Missing::default(),
+        }
     }
 
     /// Creates an empty `DefaultBTreeMap` with `default_fn` as the default value generation
     /// function for missing keys. When the provided `default_fn` only calls clone on a value,
-    /// using `DefaultBTreeMap::new` is preferred.
+    /// using `DefaultBTreeMap::new` is preferred. The function isn't called until the default is first
+    /// needed.
     pub fn with_fn(default_fn: impl DefaultFn<V> + 'static) -> DefaultBTreeMap<K, V> {
         DefaultBTreeMap {
             map: BTreeMap::new(),
-            default: default_fn.call(),
-            default_fn: Box::new(default_fn),
+            default: LazyDefault::with_fn(default_fn),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Creates an empty `DefaultBTreeMap` like `with_fn` does, but gives the function a name. The
+    /// name is shown in the `Debug` output of the map, which otherwise doesn't show anything
+    /// about the default value generation function.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let map: DefaultBTreeMap<i32, Vec<i32>> = DefaultBTreeMap::with_named_fn("empty vec", Vec::new);
+    /// assert_eq!(
+    ///     format!("{:?}", map),
+    ///     r#"DefaultBTreeMap { map: {}, default: [], default_fn: "empty vec" }"#
+    /// );
+    /// ```
+    pub fn with_named_fn(
+        name: &'static str,
+        default_fn: impl DefaultFn<V> + Clone + 'static,
+    ) -> DefaultBTreeMap<K, V> {
+        DefaultBTreeMap::with_fn(NamedFn {
+            name,
+            f: default_fn,
+        })
+    }
+
+    /// Creates a `DefaultBTreeMap` that contains a value created by `default_fn` for every key, with
+    /// `default_fn` as the default value generation function for missing keys too, see
+    /// `from_keys`.
+    pub fn from_keys_with_fn(
+        keys: impl IntoIterator<Item = K>,
+        default_fn: impl DefaultFn<V> + 'static,
+    ) -> DefaultBTreeMap<K, V> {
+        let default = LazyDefault::with_fn(default_fn);
+        DefaultBTreeMap {
+            map: keys
+                .into_iter()
+                .map(|key| (key, default.create()))
+                .collect(),
+            default,
+            missing: Missing::default(),
         }
     }
 
@@ -143,9 +485,143 @@ impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
     ) -> DefaultBTreeMap<K, V> {
         DefaultBTreeMap {
             map,
-            default: default_fn.call(),
-            default_fn: Box::new(default_fn),
+            default: LazyDefault::with_fn(default_fn),
+            missing: Missing::default(),
+        }
+    }
+}
+
+/// Helpers for maps of atomic counters, whose stored counters can be changed through a shared
+/// reference, e.g. while other code holds references into the map. The map isn't `Sync`, because
+/// the default value generation function isn't required to be, so these are single threaded
+/// interior mutability helpers and not a way to share counters between threads. `new()` creates
+/// such a map with an atomic zero as the default. All operations use `Ordering::Relaxed`.
+///
+/// ```
+/// # use defaultmap::*;
+/// use std::sync::atomic::AtomicU64;
+///
+/// let mut hits: DefaultBTreeMap<&str, AtomicU64> = DefaultBTreeMap::new();
+/// hits.fetch_add_or_insert("/", 1);
+/// let shared = &hits;
+/// assert_eq!(shared.fetch_add("/", 2), Some(1));
+/// assert_eq!(shared.fetch_add("/about", 1), None);
+/// assert_eq!(shared.load("/"), 3);
+/// assert_eq!(shared.load("/about"), 0);
+/// ```
+#[cfg(target_has_atomic = "64")]
+impl<K: Eq + Ord> DefaultBTreeMap<K, AtomicU64> {
+    /// Adds `n` to the counter of the key and returns its previous value. Inserting a counter
+    /// needs a mutable reference, so keys that have no counter stored yet are left alone and
+    /// `None` is returned. Use `fetch_add_or_insert` for those.
+    pub fn fetch_add<Q>(&self, key: &Q, n: u64) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        Some(self.map.get(key)?.fetch_add(n, Ordering::Relaxed))
+    }
+
+    /// Adds `n` to the counter of the key, inserting the default counter first if there is none,
+    /// and returns its previous value.
+    pub fn fetch_add_or_insert(&mut self, key: K, n: u64) -> u64 {
+        self.get_mut(key).fetch_add(n, Ordering::Relaxed)
+    }
+
+    /// Returns the value of the counter of the key, or of the default counter if there is none.
+    pub fn load<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.get(key).load(Ordering::Relaxed)
+    }
+}
+
+impl<K: Eq + Ord> DefaultBTreeMap<K, f64> {
+    /// Divides every value by the sum of all values in place, see `normalized`. The default
+    /// isn't changed. When the sum is zero the values become NaN.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut weights: DefaultBTreeMap<&str, f64> = DefaultBTreeMap::new();
+    /// weights["a"] = 1.0;
+    /// weights["b"] = 3.0;
+    /// weights.normalize();
+    /// assert_eq!(weights["a"], 0.25);
+    /// assert_eq!(weights.total(), 1.0);
+    /// ```
+    pub fn normalize(&mut self) {
+        let total: f64 = self.map.values().sum();
+        for value in self.map.values_mut() {
+            *value /= total;
+        }
+    }
+}
+
+impl<K: Eq + Ord> DefaultBTreeMap<K, usize> {
+    /// Creates a map that counts how often every key occurs in `iter`.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let counts = DefaultBTreeMap::count_occurrences([1, 4, 3, 3, 4, 2, 4]);
+    /// assert_eq!(counts[4], 3);
+    /// assert_eq!(counts[3], 2);
+    /// assert_eq!(counts[5], 0);
+    /// ```
+    pub fn count_occurrences<I>(iter: I) -> DefaultBTreeMap<K, usize>
+    where
+        I: IntoIterator<Item = K>,
+    {
+        let mut map: DefaultBTreeMap<K, usize> = DefaultBTreeMap::new();
+        for key in iter {
+            map[key] += 1;
+        }
+        map
+    }
+
+    /// Creates a histogram of `values`, which counts how many values `bucket_fn` puts into every
+    /// bucket. Iterating over the map returns the buckets in order, and buckets without values
+    /// have a count of zero.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let latencies_ms = [3, 12, 18, 7, 95, 14];
+    /// let histogram = DefaultBTreeMap::histogram(latencies_ms, |ms| ms / 10 * 10);
+    /// let buckets: Vec<_> = histogram.iter().collect();
+    /// assert_eq!(buckets, [(&0, &2), (&10, &3), (&90, &1)]);
+    /// ```
+    pub fn histogram<I, F>(values: I, bucket_fn: F) -> DefaultBTreeMap<K, usize>
+    where
+        I: IntoIterator,
+        F: FnMut(I::Item) -> K,
+    {
+        DefaultBTreeMap::count_occurrences(values.into_iter().map(bucket_fn))
+    }
+}
+
+impl<K: Eq + Ord, T> DefaultBTreeMap<K, Vec<T>> {
+    /// Creates a map that groups the items of `iter` by the key that `f` returns for them. The
+    /// items of every group keep the order in which they were returned by `iter`.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let words = ["apple", "bob", "avocado", "cat"];
+    /// let by_letter = DefaultBTreeMap::group_by(words, |word| word.chars().next().unwrap());
+    /// assert_eq!(by_letter['a'], vec!["apple", "avocado"]);
+    /// assert_eq!(by_letter['b'], vec!["bob"]);
+    /// assert!(by_letter['z'].is_empty());
+    /// ```
+    pub fn group_by<I, F>(iter: I, mut f: F) -> DefaultBTreeMap<K, Vec<T>>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(&T) -> K,
+    {
+        let mut map: DefaultBTreeMap<K, Vec<T>> = DefaultBTreeMap::new();
+        for item in iter {
+            map[f(&item)].push(item);
         }
+        map
     }
 }
 
@@ -159,404 +635,2969 @@ impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
         let entry = self.map.entry(key);
         match entry {
             Entry::Occupied(occupied) => occupied.into_mut(),
-            Entry::Vacant(vacant) => vacant.insert(self.default_fn.call()),
+            Entry::Vacant(vacant) => {
+                self.missing.record_materialized(vacant.key());
+                vacant.insert(self.default.create())
+            }
         }
     }
-}
 
-/// Implements the `Index` trait so you can do `map[key]`.
-/// Nonmutable indexing can be done both by passing a reference or an owned value as the key.
-impl<K: Eq + Ord, KB: Borrow<K>, V> Index<KB> for DefaultBTreeMap<K, V> {
-    type Output = V;
+    /// Returns a mutable reference to the value stored for the provided key, like `get_mut`.
+    /// If there is no value stored for the key, `default_fn` is used to create it instead of the
+    /// default of the map. When `default_fn` fails its error is returned and nothing is inserted.
+    /// [`TryDefaultMap`](crate::TryDefaultMap) stores such a function, so it doesn't have to be
+    /// passed on every call.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut templates: DefaultBTreeMap<&str, String> = DefaultBTreeMap::new();
+    /// fn load(name: &'static str) -> impl Fn() -> Result<String, String> {
+    ///     move || match name {
+    ///         "greeting" => Ok("Hello {name}!".to_string()),
+    ///         _ => Err(format!("no template named {name}")),
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(templates.try_get_mut("greeting", load("greeting")).unwrap(), "Hello {name}!");
+    /// assert!(templates.try_get_mut("farewell", load("farewell")).is_err());
+    /// assert_eq!(templates.len(), 1);
+    /// ```
+    pub fn try_get_mut<E>(
+        &mut self,
+        key: K,
+        default_fn: impl TryDefaultFn<V, E>,
+    ) -> Result<&mut V, E> {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => Ok(occupied.into_mut()),
+            Entry::Vacant(vacant) => {
+                let value = default_fn.try_call()?;
+                self.missing.record_materialized(vacant.key());
+                Ok(vacant.insert(value))
+            }
+        }
+    }
 
-    fn index(&self, index: KB) -> &V {
-        self.get(index)
+    /// Returns a mutable reference to the value stored for the provided key, like `get_mut`.
+    /// If there is no value stored for the key, it's created by `f` instead of using the default
+    /// of the map. Later lookups of other missing keys still get the default.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut sessions: DefaultBTreeMap<u32, Vec<&str>> = DefaultBTreeMap::new();
+    /// sessions.get_or_insert_with(1, || vec!["login"]).push("view");
+    /// sessions.get_or_insert_with(1, || vec!["login"]).push("logout");
+    /// assert_eq!(sessions[1], ["login", "view", "logout"]);
+    /// assert!(sessions[2].is_empty());
+    /// ```
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &mut V {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => occupied.into_mut(),
+            Entry::Vacant(vacant) => {
+                self.missing.record_materialized(vacant.key());
+                vacant.insert(f())
+            }
+        }
     }
-}
 
-/// Implements the `IndexMut` trait so you can do `map[key] = val`.
-/// Mutably indexing can only be done when passing an owned value as the key.
-impl<K: Eq + Ord, V> IndexMut<K> for DefaultBTreeMap<K, V> {
-    #[inline]
-    fn index_mut(&mut self, index: K) -> &mut V {
-        self.get_mut(index)
+    /// Calls `f` with a mutable reference to the value of the key, inserting the default first
+    /// if there is no value stored yet, like `get_mut`. The map is returned so that calls can be
+    /// chained.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut stock: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// stock
+    ///     .modify("apples", |n| *n += 5)
+    ///     .modify("pears", |n| *n += 2)
+    ///     .modify("apples", |n| *n *= 2);
+    /// assert_eq!(stock["apples"], 10);
+    /// assert_eq!(stock["pears"], 2);
+    /// ```
+    pub fn modify(&mut self, key: K, f: impl FnOnce(&mut V)) -> &mut Self {
+        f(self.get_mut(key));
+        self
     }
-}
 
-// grcov-excl-start
-/// These methods simply forward to the underlying `BTreeMap`, see that
-/// [documentation](https://doc.rust-lang.org/std/collections/struct.BTreeMap.html) for
-/// the usage of these methods.
-impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
-    #[inline]
-    pub fn clear(&mut self) {
-        self.map.clear()
+    /// Calls `f` with the value of the key, or the default if there is no value stored, and
+    /// stores what `f` returns. When `f` returns `None` the key is removed instead. This is meant
+    /// for maps of reference counts, where a key should be freed when its count drops to zero.
+    /// While `f` runs the key holds a fresh default, so if `f` panics the key is kept with the
+    /// default as its value.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut refs: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// refs.update_or_remove("a", |n| Some(n + 1));
+    /// refs.update_or_remove("a", |n| n.checked_sub(1).filter(|&n| n > 0));
+    /// assert!(!refs.contains_key("a"));
+    /// ```
+    pub fn update_or_remove(&mut self, key: K, f: impl FnOnce(V) -> Option<V>) {
+        match self.map.entry(key) {
+            Entry::Occupied(mut occupied) => {
+                let value = std::mem::replace(occupied.get_mut(), self.default.create());
+                match f(value) {
+                    Some(value) => *occupied.get_mut() = value,
+                    None => {
+                        occupied.remove_entry();
+                    }
+                }
+            }
+            Entry::Vacant(vacant) => {
+                if let Some(value) = f(self.default.create()) {
+                    self.missing.record_materialized(vacant.key());
+                    vacant.insert(value);
+                }
+            }
+        }
     }
-    #[inline]
-    pub fn first_key_value(&self) -> Option<(&K, &V)>
+
+    /// Returns a mutable reference to the value stored for the provided key. If there is no value
+    /// stored for the key, the future returned by `f` is awaited to create it instead of using
+    /// the default of the map. This allows using the map as a read-through cache, where missing
+    /// values are fetched over the network. Values that are already stored are returned without
+    /// waiting for anything. This is only available with the `async` feature.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// async fn fetch_user_name(id: u32) -> String {
+    ///     // Fetch the name over the network
+    ///     # format!("user {id}")
+    /// }
+    ///
+    /// async fn user_name(cache: &mut DefaultBTreeMap<u32, String>, id: u32) -> &str {
+    ///     cache.get_or_insert_async(id, |id| fetch_user_name(*id)).await
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn get_or_insert_async<F, Fut>(&mut self, key: K, f: F) -> &mut V
     where
-        K: Ord,
+        F: FnOnce(&K) -> Fut,
+        Fut: std::future::Future<Output = V>,
     {
-        self.map.first_key_value()
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => occupied.into_mut(),
+            Entry::Vacant(vacant) => {
+                let value = f(vacant.key()).await;
+                self.missing.record_materialized(vacant.key());
+                vacant.insert(value)
+            }
+        }
     }
-    #[inline]
-    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>>
+
+    /// Returns mutable references to the values stored for several keys at once.
+    /// Keys that have no value stored get the default value inserted first, just like `get_mut`
+    /// does. Missing keys are converted to owned keys using `ToOwned`. The values are collected in
+    /// a single pass over the entries between the smallest and the largest key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any two of the keys refer to the same entry.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut balances: DefaultBTreeMap<&str, i32> = DefaultBTreeMap::new();
+    /// balances.insert("alice", 10);
+    /// let [alice, bob] = balances.get_disjoint_mut([&"alice", &"bob"]);
+    /// *alice -= 3;
+    /// *bob += 3;
+    /// assert_eq!(balances["alice"], 7);
+    /// assert_eq!(balances["bob"], 3);
+    /// ```
+    pub fn get_disjoint_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> [&mut V; N]
     where
-        K: Ord,
+        K: Borrow<Q>,
+        Q: ?Sized + Ord + ToOwned<Owned = K>,
     {
-        self.map.first_entry()
+        for key in keys {
+            if !self.map.contains_key(key) {
+                self.map.insert(key.to_owned(), self.default.create());
+            }
+        }
+        // All values are taken from a single pass over the entries between the smallest and the
+        // largest key, so no reference is held across a later lookup.
+        let min = keys.iter().copied().min().map_or(Unbounded, Included);
+        let max = keys.iter().copied().max().map_or(Unbounded, Included);
+        let entries = self.map.range_mut::<Q, _>((min, max));
+        let mut values: [Option<&mut V>; N] = std::array::from_fn(|_| None);
+        for (k, value) in entries {
+            let mut matches = keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| k.borrow().cmp(*key).is_eq());
+            if let Some((i, _)) = matches.next() {
+                assert!(
+                    matches.next().is_none(),
+                    "duplicate keys passed to get_disjoint_mut"
+                );
+                values[i].get_or_insert(value);
+            }
+        }
+        values.map(|value| value.expect("key was just inserted"))
     }
-    #[inline]
-    pub fn pop_first(&mut self) -> Option<(K, V)>
+
+    /// Removes all entries for which `pred` returns `true` and returns them as an iterator, in
+    /// key order. The predicate also gets a mutable reference to the value, so entries that are
+    /// kept can be updated in the same pass.
+    ///
+    /// Unlike `BTreeMap::extract_if`, which needs a newer Rust version than this crate supports,
+    /// the entries are removed right away and not when the iterator is consumed. The keys of
+    /// matching entries are cloned and the entries are only removed after `pred` was called for
+    /// every entry, so if `pred` panics no entries are removed.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut deadlines: DefaultBTreeMap<u64, Vec<&str>> = DefaultBTreeMap::new();
+    /// deadlines[10].push("flush");
+    /// deadlines[20].push("compact");
+    /// deadlines[30].push("rotate");
+    ///
+    /// let now = 25;
+    /// let expired: Vec<_> = deadlines.extract_if(|&at, _| at <= now).collect();
+    /// assert_eq!(expired, vec![(10, vec!["flush"]), (20, vec!["compact"])]);
+    /// assert_eq!(deadlines.len(), 1);
+    /// ```
+    pub fn extract_if<F>(&mut self, mut pred: F) -> std::vec::IntoIter<(K, V)>
     where
-        K: Ord,
+        K: Clone,
+        F: FnMut(&K, &mut V) -> bool,
     {
-        self.map.pop_first()
+        let keys: Vec<K> = self
+            .map
+            .iter_mut()
+            .filter_map(|(key, value)| pred(key, value).then(|| key.clone()))
+            .collect();
+        let extracted: Vec<_> = keys
+            .iter()
+            .map(|key| self.map.remove_entry(key).expect("key was just found"))
+            .collect();
+        extracted.into_iter()
     }
-    #[inline]
-    pub fn last_key_value(&self) -> Option<(&K, &V)>
+
+    /// Splits the map into the entries for which `pred` returns `true` and the ones for which it
+    /// returns `false`. Both maps create their defaults in the same way as this map.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// counts["error"] = 120;
+    /// counts["warning"] = 3;
+    /// let (frequent, rare) = counts.partition(|_, &count| count >= 100);
+    /// assert_eq!(frequent.len(), 1);
+    /// assert_eq!(rare["warning"], 3);
+    /// assert_eq!(rare["error"], 0);
+    /// ```
+    pub fn partition<F>(self, mut pred: F) -> (Self, Self)
     where
-        K: Ord,
+        F: FnMut(&K, &V) -> bool,
     {
-        self.map.last_key_value()
+        let (map, default, _) = self.into_parts();
+        let mut matching = DefaultBTreeMap {
+            map: BTreeMap::new(),
+            default: default.fresh(),
+            missing: Missing::default(),
+        };
+        let mut rest = DefaultBTreeMap {
+            map: BTreeMap::new(),
+            default,
+            missing: Missing::default(),
+        };
+        for (key, value) in map {
+            if pred(&key, &value) {
+                matching.map.insert(key, value);
+            } else {
+                rest.map.insert(key, value);
+            }
+        }
+        (matching, rest)
     }
-    #[inline]
-    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>>
+
+    /// Retains only the entries for which `pred` returns `true` for their key. This is the same
+    /// as `retain`, but it doesn't borrow the values mutably when the predicate doesn't need
+    /// them.
+    pub fn retain_keys<F>(&mut self, mut pred: F)
     where
-        K: Ord,
+        F: FnMut(&K) -> bool,
     {
-        self.map.last_entry()
+        self.map.retain(|key, _| pred(key))
     }
-    #[inline]
-    pub fn pop_last(&mut self) -> Option<(K, V)>
+
+    /// Sets the value of the key back to a freshly generated default and returns the previous
+    /// value. Keys that have no value stored are left alone, because they already resolve to the
+    /// default. Use `remove` to get rid of the entry instead.
+    pub fn reset<Q>(&mut self, key: &Q) -> Option<V>
     where
-        K: Ord,
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
     {
-        self.map.pop_last()
+        let value = self.map.get_mut(key)?;
+        Some(std::mem::replace(value, self.default.create()))
     }
-    #[inline]
-    pub fn contains_key<Q>(&self, k: &Q) -> bool
+
+    /// Sets the values of all keys back to a freshly generated default, while keeping the keys
+    /// in the map. This is useful to zero counters between measurement windows.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// counts["a"] += 3;
+    /// counts.reset_all();
+    /// assert_eq!(counts["a"], 0);
+    /// assert!(counts.contains_key("a"));
+    /// ```
+    pub fn reset_all(&mut self) {
+        for value in self.map.values_mut() {
+            *value = self.default.create();
+        }
+    }
+}
+
+impl<K: Eq + Ord, V: PartialEq> DefaultBTreeMap<K, V> {
+    /// Checks the internal consistency of the map and panics if it's violated. This is only
+    /// available in debug builds, and is meant for wrappers and fuzzers that want to check the map
+    /// after unusual sequences of operations.
+    ///
+    /// Currently this checks that the stored default matches the values that are created for
+    /// missing keys, unless those are created by a default value generation function.
+    #[cfg(debug_assertions)]
+    pub fn assert_invariants(&self) {
+        assert!(
+            self.default.agrees_with_source(),
+            "the default value disagrees with the values created for missing keys"
+        );
+    }
+
+    /// Returns `true` if a value is stored for the key and that value is not equal to the default.
+    /// Unlike `contains_key` this ignores entries that only hold the default, e.g. because they
+    /// were created by mutably indexing the map without changing the value.
+    pub fn contains_non_default<Q>(&self, key: &Q) -> bool
     where
         K: Borrow<Q>,
         Q: ?Sized + Ord,
     {
-        self.map.contains_key(k)
+        self.map
+            .get(key)
+            .is_some_and(|value| *value != *self.default.get())
     }
-    #[inline]
-    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
-        self.map.insert(k, v)
+
+    /// Removes all entries whose value is equal to the default. Mutably indexing the map inserts
+    /// the default for missing keys, so this can be used to shrink a map again after many such
+    /// reads. Lookups behave exactly the same after pruning.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, i32> = DefaultBTreeMap::new();
+    /// counts["a"] += 1;
+    /// counts["b"] += 0;
+    /// assert_eq!(counts.len(), 2);
+    /// assert_eq!(counts.pruned_len(), 1);
+    /// counts.prune();
+    /// assert_eq!(counts.len(), 1);
+    /// assert_eq!(counts["b"], 0);
+    /// ```
+    pub fn prune(&mut self) {
+        let default = self.default.get();
+        self.map.retain(|_, value| value != default);
     }
-    #[inline]
-    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+
+    /// Returns the number of entries the map would have after calling `prune()`, i.e. the number
+    /// of entries whose value is not equal to the default.
+    pub fn pruned_len(&self) -> usize {
+        self.map
+            .values()
+            .filter(|value| **value != *self.default.get())
+            .count()
+    }
+
+    /// Returns `true` if looking up the key returns the default, either because no value is
+    /// stored for it or because the stored value is equal to the default.
+    pub fn is_default<Q>(&self, key: &Q) -> bool
     where
         K: Borrow<Q>,
         Q: ?Sized + Ord,
     {
-        self.map.remove(k)
+        !self.contains_non_default(key)
     }
-    #[inline]
-    pub fn retain<RF>(&mut self, f: RF)
+
+    /// Returns `true` if both maps return the same value for every possible key. Unlike `==`,
+    /// this treats a key that is missing the same as a key that has the default stored.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut a: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+    /// let b: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+    /// a[1] += 0;
+    /// assert_ne!(a, b);
+    /// assert!(a.semantic_eq(&b));
+    /// ```
+    pub fn semantic_eq(&self, other: &DefaultBTreeMap<K, V>) -> bool {
+        self.default.get() == other.default.get()
+            && self.map.iter().all(|(key, value)| value == other.get(key))
+            && other.map.iter().all(|(key, value)| value == self.get(key))
+    }
+
+    /// Returns the changes that turn this map into `other`, see [`MapDiff`].
+    pub fn diff(&self, other: &DefaultBTreeMap<K, V>) -> MapDiff<K, V>
     where
-        RF: FnMut(&K, &mut V) -> bool,
+        K: Clone,
+        V: Clone,
     {
-        self.map.retain(f)
+        let mut diff = MapDiff::default();
+        for key in self.union_keys(other) {
+            let old = self.get(key);
+            let new = other.get(key);
+            if old == new {
+                continue;
+            }
+            if *old == *self.default.get() {
+                diff.added.push((key.clone(), new.clone()));
+            } else if *new == *other.default.get() {
+                diff.removed.push((key.clone(), old.clone()));
+            } else {
+                diff.changed.push((key.clone(), old.clone(), new.clone()));
+            }
+        }
+        diff
     }
-    #[inline]
-    pub fn append(&mut self, other: &mut Self) {
-        self.map.append(&mut other.map)
+
+    /// Applies the changes of `diff`, as returned by `diff`, to this map. Keys whose new value is
+    /// equal to the default of this map are removed instead of being stored. The old values in
+    /// the diff are not checked against the current values.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut primary: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// let mut replica = primary.clone();
+    /// let before = primary.clone();
+    /// primary["a"] = 1;
+    /// primary["b"] = 2;
+    ///
+    /// replica.apply_diff(before.diff(&primary));
+    /// assert_eq!(replica, primary);
+    /// ```
+    pub fn apply_diff(&mut self, diff: MapDiff<K, V>) {
+        for (key, _) in diff.removed {
+            self.map.remove(&key);
+        }
+        let new_values = diff
+            .added
+            .into_iter()
+            .chain(diff.changed.into_iter().map(|(key, _, new)| (key, new)));
+        for (key, value) in new_values {
+            if value == *self.default.get() {
+                self.map.remove(&key);
+            } else {
+                self.map.insert(key, value);
+            }
+        }
     }
-    #[inline]
-    pub fn range<T: ?Sized, R>(&self, range: R) -> Range<'_, K, V>
+
+    /// Returns a guard that gives mutable access to the value of the key, like `get_mut`.
+    /// When the guard is dropped and the value is equal to the default, the entry is removed
+    /// from the map again, or never inserted if the key was missing. This keeps the map sparse
+    /// in code that accesses many keys mutably without changing most of them.
+    ///
+    /// `IndexMut` has to return a plain reference, so `map[key]` can't prune automatically.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, i32> = DefaultBTreeMap::new();
+    /// *counts.get_mut_pruned("a") += 1;
+    /// *counts.get_mut_pruned("b") += 0;
+    /// *counts.get_mut_pruned("a") -= 1;
+    /// assert!(counts.is_empty());
+    /// ```
+    pub fn get_mut_pruned(&mut self, key: K) -> PruneGuard<'_, K, V> {
+        let state = match self.map.entry(key) {
+            Entry::Occupied(occupied) => GuardState::Occupied(occupied),
+            Entry::Vacant(vacant) => GuardState::Vacant(vacant, self.default.create()),
+        };
+        PruneGuard {
+            state: Some(state),
+            default: self.default.get(),
+        }
+    }
+}
+
+impl<K: Eq + Ord, T: ?Sized + ToOwned> DefaultBTreeMap<K, &T>
+where
+    T::Owned: Clone + 'static,
+{
+    /// Converts a map that borrows its values into a map that owns them, by calling `to_owned()`
+    /// on every value and on the default.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let text = String::from("a b a");
+    /// let mut last_word: DefaultBTreeMap<usize, &str> = DefaultBTreeMap::new();
+    /// for (i, word) in text.split(' ').enumerate() {
+    ///     last_word[i % 2] = word;
+    /// }
+    /// let owned: DefaultBTreeMap<usize, String> = last_word.to_owned_values();
+    /// drop(text);
+    /// assert_eq!(owned[0], "a");
+    /// assert_eq!(owned[2], "");
+    /// ```
+    pub fn to_owned_values(self) -> DefaultBTreeMap<K, T::Owned> {
+        let map = self
+            .map
+            .into_iter()
+            .map(|(key, value)| (key, value.to_owned()))
+            .collect();
+        DefaultBTreeMap::from_map_with_default(map, (*self.default.get()).to_owned())
+    }
+}
+
+impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
+    /// Returns an iterator over the entries with the `n` smallest keys, in ascending order.
+    pub fn first_n(&self, n: usize) -> Take<Iter<'_, K, V>> {
+        self.map.iter().take(n)
+    }
+
+    /// Returns an iterator over the entries with the `n` largest keys, in descending order.
+    pub fn last_n(&self, n: usize) -> Take<Rev<Iter<'_, K, V>>> {
+        self.map.iter().rev().take(n)
+    }
+
+    /// Returns an entry for the key that can also look at the entries right before and after
+    /// it, which is useful when an insertion depends on its neighbors.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// // Maps the start of each interval to its end
+    /// let mut intervals: DefaultBTreeMap<u32, u32> = DefaultBTreeMap::new();
+    /// intervals.insert(0, 5);
+    ///
+    /// let entry = intervals.entry_with_neighbors(5);
+    /// assert_eq!(entry.peek_prev(), Some((&0, &5)));
+    /// ```
+    pub fn entry_with_neighbors(&mut self, key: K) -> NeighborEntry<'_, K, V> {
+        NeighborEntry {
+            map: &mut self.map,
+            default: &self.default,
+            missing: &self.missing,
+            key,
+        }
+    }
+}
+
+impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
+    /// Adds one to the value of the key, starting from the default if the key is missing.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u64> = DefaultBTreeMap::new();
+    /// for word in "the cat saw the dog".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts["the"], 2);
+    /// ```
+    pub fn incr(&mut self, key: K) -> &mut V
     where
-        T: Ord,
-        K: Borrow<T> + Ord,
-        R: RangeBounds<T>,
+        V: AddAssign + One,
     {
-        self.map.range(range)
+        self.incr_by(key, V::one())
     }
-    #[inline]
-    pub fn range_mut<T: ?Sized, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+
+    /// Adds `n` to the value of the key, starting from the default if the key is missing.
+    pub fn incr_by(&mut self, key: K, n: V) -> &mut V
     where
-        T: Ord,
-        K: Borrow<T> + Ord,
+        V: AddAssign,
+    {
+        let value = self.get_mut(key);
+        *value += n;
+        value
+    }
+
+    /// Subtracts one from the value of the key, starting from the default if the key is missing.
+    pub fn decr(&mut self, key: K) -> &mut V
+    where
+        V: SubAssign + One,
+    {
+        self.decr_by(key, V::one())
+    }
+
+    /// Subtracts `n` from the value of the key, starting from the default if the key is missing.
+    pub fn decr_by(&mut self, key: K, n: V) -> &mut V
+    where
+        V: SubAssign,
+    {
+        let value = self.get_mut(key);
+        *value -= n;
+        value
+    }
+
+    /// Updates the value of the key with the result of `f`, which gets the current value or the
+    /// default if the key is missing. When `f` returns an error, the map is left unchanged. This
+    /// can be used with checked arithmetic, so counters of bounded types can't silently overflow.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u8> = DefaultBTreeMap::with_default(250);
+    /// assert_eq!(counts.try_update("a", |v| v.checked_add(5).ok_or("overflow")), Ok(&mut 255));
+    /// assert_eq!(counts.try_update("a", |v| v.checked_add(1).ok_or("overflow")), Err("overflow"));
+    /// assert_eq!(counts.try_update("b", |v| v.checked_add(10).ok_or("overflow")), Err("overflow"));
+    /// assert_eq!(counts["a"], 255);
+    /// assert!(!counts.contains_key("b"));
+    /// ```
+    pub fn try_update<E, F>(&mut self, key: K, f: F) -> Result<&mut V, E>
+    where
+        F: FnOnce(&V) -> Result<V, E>,
+    {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => {
+                let value = occupied.into_mut();
+                *value = f(value)?;
+                Ok(value)
+            }
+            Entry::Vacant(vacant) => Ok(vacant.insert(f(self.default.get())?)),
+        }
+    }
+
+    /// Moves all entries of `other` into this map. For keys that are stored in both maps `f` is
+    /// called with the key, the value in this map and the value from `other`, so it can combine
+    /// them. Unlike `extend`, this never silently overwrites values.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut latest: DefaultBTreeMap<&str, (u32, &str)> = DefaultBTreeMap::new();
+    /// latest["a"] = (1, "old");
+    /// let mut update: DefaultBTreeMap<&str, (u32, &str)> = DefaultBTreeMap::new();
+    /// update["a"] = (2, "new");
+    /// update["b"] = (1, "added");
+    ///
+    /// latest.merge(update, |_, mine, theirs| {
+    ///     if theirs.0 > mine.0 {
+    ///         *mine = theirs;
+    ///     }
+    /// });
+    /// assert_eq!(latest["a"], (2, "new"));
+    /// assert_eq!(latest["b"], (1, "added"));
+    /// ```
+    pub fn merge<F>(&mut self, other: Self, mut f: F)
+    where
+        F: FnMut(&K, &mut V, V),
+    {
+        for (key, value) in other.map {
+            if let Some(mine) = self.map.get_mut(&key) {
+                f(&key, mine, value);
+            } else {
+                self.map.insert(key, value);
+            }
+        }
+    }
+
+    /// Inserts all entries of `iter`, like Python's `dict.update`. Values of keys that are
+    /// already present are replaced, use `merge` to combine them instead. The default of the map
+    /// isn't changed.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut config: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::with_default(1);
+    /// config["retries"] = 3;
+    /// config.update_from([("retries", 5), ("workers", 8)]);
+    /// assert_eq!(config["retries"], 5);
+    /// assert_eq!(config["workers"], 8);
+    /// ```
+    pub fn update_from(&mut self, iter: impl IntoIterator<Item = (K, V)>) {
+        self.map.extend(iter);
+    }
+
+    /// Inserts clones of all entries that are stored in `other`, replacing the values of keys
+    /// that are already present, see `update_from`. The default of `other` is ignored.
+    pub fn update_from_map(&mut self, other: &Self)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.update_from(other.map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+/// Helpers for using a map with counts as values as a sorted multiset, where each key occurs as
+/// many times as its count. These only look at the stored counts, so they are meant for maps
+/// with a default of zero.
+impl<K: Eq + Ord> DefaultBTreeMap<K, usize> {
+    /// Adds `n` occurrences of the key to the multiset.
+    pub fn insert_count(&mut self, key: K, n: usize) -> &mut usize {
+        self.incr_by(key, n)
+    }
+
+    /// Returns the `n`th smallest element of the multiset, counting from zero and taking the
+    /// count of every key into account. Returns `None` if the multiset has `n` or fewer elements.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut latencies: DefaultBTreeMap<u32, usize> = DefaultBTreeMap::new();
+    /// latencies.insert_count(10, 3);
+    /// latencies.insert_count(20, 1);
+    /// latencies.insert_count(50, 1);
+    ///
+    /// let median = latencies.total_in_range(..) / 2;
+    /// assert_eq!(latencies.nth_smallest(median), Some(&10));
+    /// assert_eq!(latencies.nth_smallest(3), Some(&20));
+    /// assert_eq!(latencies.nth_smallest(5), None);
+    /// ```
+    pub fn nth_smallest(&self, mut n: usize) -> Option<&K> {
+        for (key, count) in self.map.iter() {
+            if n < *count {
+                return Some(key);
+            }
+            n -= count;
+        }
+        None
+    }
+
+    /// Returns the number of elements of the multiset whose key is in the range.
+    pub fn total_in_range<T, R>(&self, range: R) -> usize
+    where
+        T: ?Sized + Ord,
+        K: Borrow<T>,
         R: RangeBounds<T>,
     {
-        self.map.range_mut(range)
+        self.map.range(range).map(|(_, count)| count).sum()
     }
-    #[inline]
-    pub fn entry(&mut self, key: K) -> Entry<K, V> {
-        self.map.entry(key)
+}
+
+impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
+    /// Creates a new map by applying `f` to every stored value. The default and the default value
+    /// generation function are transformed by `f` too, so missing keys of the new map behave like
+    /// missing keys of this map after transforming them.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::with_default(1);
+    /// counts["a"] = 4;
+    /// let halves = counts.map_values(|v| f64::from(v) / 2.0);
+    /// assert_eq!(halves["a"], 2.0);
+    /// assert_eq!(halves["b"], 0.5);
+    /// ```
+    pub fn map_values<W, F>(self, f: F) -> DefaultBTreeMap<K, W>
+    where
+        V: 'static,
+        F: Fn(V) -> W + Clone + 'static,
+    {
+        let mut map = BTreeMap::new();
+        map.extend(self.map.into_iter().map(|(key, value)| (key, f(value))));
+        DefaultBTreeMap {
+            map,
+            default: self.default.map(f),
+            missing: Missing::default(),
+        }
     }
-    #[inline]
-    pub fn into_keys(self) -> IntoKeys<K, V> {
-        self.map.into_keys()
+
+    /// Converts the map into one with different keys, keeping the default for missing keys.
+    /// The new key of every entry is computed by `f`. When multiple entries end up with the same
+    /// new key, `merge` is called to fold the value of the later entry into the earlier one.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut hits: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// hits["example.com/a"] += 2;
+    /// hits["example.com/b"] += 3;
+    /// hits["example.org/"] += 1;
+    ///
+    /// let per_domain = hits.reindex_by(|url, _| url.split('/').next().unwrap(), |a, b| *a += b);
+    /// assert_eq!(per_domain["example.com"], 5);
+    /// assert_eq!(per_domain["example.org"], 1);
+    /// assert_eq!(per_domain["example.net"], 0);
+    /// ```
+    pub fn reindex_by<K2, F, M>(self, mut f: F, mut merge: M) -> DefaultBTreeMap<K2, V>
+    where
+        K2: Eq + Ord,
+        F: FnMut(&K, &V) -> K2,
+        M: FnMut(&mut V, V),
+    {
+        let mut map = BTreeMap::new();
+        for (key, value) in self.map {
+            match map.entry(f(&key, &value)) {
+                Entry::Occupied(occupied) => merge(occupied.into_mut(), value),
+                Entry::Vacant(vacant) => {
+                    vacant.insert(value);
+                }
+            }
+        }
+        DefaultBTreeMap {
+            map,
+            default: self.default,
+            missing: Missing::default(),
+        }
     }
-    #[inline]
-    pub fn into_values(self) -> IntoValues<K, V> {
-        self.map.into_values()
+
+    /// Creates a new map that only contains the entries for which `pred` returns `true`, keeping
+    /// the default for missing keys.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// counts["rare"] += 1;
+    /// counts["common"] += 10;
+    /// let frequent = counts.filter(|_, count| *count >= 5);
+    /// assert_eq!(frequent["common"], 10);
+    /// assert!(!frequent.contains_key("rare"));
+    /// ```
+    pub fn filter<F>(mut self, mut pred: F) -> DefaultBTreeMap<K, V>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.map.retain(|key, value| pred(key, value));
+        self
+    }
+
+    /// Creates a new map from the entries for which `f` returns `Some`, using the returned value
+    /// as the new value. The default for missing keys is kept.
+    pub fn filter_map<F>(self, mut f: F) -> DefaultBTreeMap<K, V>
+    where
+        F: FnMut(&K, V) -> Option<V>,
+    {
+        let mut map = BTreeMap::new();
+        map.extend(
+            self.map
+                .into_iter()
+                .filter_map(|(key, value)| f(&key, value).map(|value| (key, value))),
+        );
+        DefaultBTreeMap {
+            map,
+            default: self.default,
+            missing: Missing::default(),
+        }
+    }
+}
+
+impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
+    /// Returns an iterator over clones of the stored values.
+    pub fn values_cloned(&self) -> Cloned<Values<'_, K, V>>
+    where
+        V: Clone,
+    {
+        self.map.values().cloned()
+    }
+
+    /// Returns the sum of all values stored in the map, e.g. the total number of events seen by
+    /// a counter map.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u64> = DefaultBTreeMap::new();
+    /// for word in "the cat saw the dog".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts.total(), 5);
+    /// ```
+    pub fn total(&self) -> V
+    where
+        V: for<'a> Sum<&'a V>,
+    {
+        self.map.values().sum()
+    }
+
+    /// Returns the count, minimum, maximum, mean and variance of the values stored in the map,
+    /// or `None` if the map is empty. See [`ValueStats`] for an example.
+    pub fn value_stats(&self) -> Option<ValueStats>
+    where
+        V: AsF64,
+    {
+        ValueStats::from_values(self.map.values())
+    }
+
+    /// Returns the statistics of the values of the provided keys, like `value_stats`. Keys that
+    /// are not in the map count as the default, so the statistics include keys that were never
+    /// seen. Keys that are stored but not provided are left out.
+    pub fn value_stats_over<I>(&self, keys: I) -> Option<ValueStats>
+    where
+        V: AsF64,
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        ValueStats::from_values(self.iter_with_defaults(keys).map(|(_, value)| value))
+    }
+
+    /// Returns a map with every value divided by the sum of all values, which turns a map of
+    /// counts into a probability distribution. The default is converted to an `f64`, but isn't
+    /// divided. When the sum is zero the values become NaN.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// counts["the"] = 3;
+    /// counts["cat"] = 1;
+    /// let probabilities = counts.normalized();
+    /// assert_eq!(probabilities["the"], 0.75);
+    /// assert_eq!(probabilities["dog"], 0.0);
+    /// ```
+    pub fn normalized(&self) -> DefaultBTreeMap<K, f64>
+    where
+        K: Clone,
+        V: AsF64,
+    {
+        let total: f64 = self.map.values().map(AsF64::as_f64).sum();
+        let map = self
+            .map
+            .iter()
+            .map(|(key, value)| (key.clone(), value.as_f64() / total))
+            .collect();
+        DefaultBTreeMap {
+            map,
+            default: LazyDefault::value(self.default.get().as_f64()),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Returns the entry with the largest value, or `None` if the map is empty. If several
+    /// entries have the largest value, it's unspecified which of them is returned.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u64> = DefaultBTreeMap::new();
+    /// for word in "the cat saw the dog".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts.max_by_value(), Some((&"the", &2)));
+    /// assert_eq!(counts.argmax(), Some(&"the"));
+    /// ```
+    pub fn max_by_value(&self) -> Option<(&K, &V)>
+    where
+        V: Ord,
+    {
+        self.map.iter().max_by(|a, b| a.1.cmp(b.1))
+    }
+
+    /// Returns the entry with the smallest value, or `None` if the map is empty. If several
+    /// entries have the smallest value, it's unspecified which of them is returned.
+    pub fn min_by_value(&self) -> Option<(&K, &V)>
+    where
+        V: Ord,
+    {
+        self.map.iter().min_by(|a, b| a.1.cmp(b.1))
+    }
+
+    /// Returns the key with the largest value, see `max_by_value`.
+    pub fn argmax(&self) -> Option<&K>
+    where
+        V: Ord,
+    {
+        self.max_by_value().map(|(key, _)| key)
+    }
+
+    /// Returns the key with the smallest value, see `min_by_value`.
+    pub fn argmin(&self) -> Option<&K>
+    where
+        V: Ord,
+    {
+        self.min_by_value().map(|(key, _)| key)
+    }
+
+    /// Returns the `n` entries with the largest values, ordered from largest to smallest value.
+    /// When the map has fewer than `n` entries all of them are returned. Only the returned
+    /// entries are sorted, so this is faster than sorting the whole map when `n` is small. The
+    /// order of entries with equal values is unspecified.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u64> = DefaultBTreeMap::new();
+    /// for word in "the cat saw the dog and the cat".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts.most_common(2), vec![(&"the", &3), (&"cat", &2)]);
+    /// ```
+    pub fn most_common(&self, n: usize) -> Vec<(&K, &V)>
+    where
+        V: Ord,
+    {
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut entries: Vec<_> = self.map.iter().collect();
+        if n < entries.len() {
+            entries.select_nth_unstable_by(n - 1, |a, b| b.1.cmp(a.1));
+            entries.truncate(n);
+        }
+        entries.sort_unstable_by(|a, b| b.1.cmp(a.1));
+        entries
+    }
+
+    /// Returns an iterator over all stored entries, sorted from the smallest to the largest value.
+    /// Entries with equal values are returned in key order. The entries are collected and sorted
+    /// when this is called.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultBTreeMap<&str, u64> = DefaultBTreeMap::new();
+    /// for word in "the cat saw the dog and the cat".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// let top: Vec<_> = counts.iter_sorted_by_value_desc().take(2).collect();
+    /// assert_eq!(top, [(&"the", &3), (&"cat", &2)]);
+    /// assert_eq!(counts.keys_sorted_by_value().last(), Some(&"the"));
+    /// ```
+    pub fn iter_sorted_by_value(&self) -> std::vec::IntoIter<(&K, &V)>
+    where
+        V: Ord,
+    {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by(|a, b| a.1.cmp(b.1));
+        entries.into_iter()
+    }
+
+    /// Returns an iterator over all stored entries, sorted from the largest to the smallest
+    /// value, see `iter_sorted_by_value`.
+    pub fn iter_sorted_by_value_desc(&self) -> std::vec::IntoIter<(&K, &V)>
+    where
+        V: Ord,
+    {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by(|a, b| b.1.cmp(a.1));
+        entries.into_iter()
+    }
+
+    /// Returns an iterator over the keys, sorted from the smallest to the largest value, see
+    /// `iter_sorted_by_value`. Use `rev()` to get the keys with the largest values first.
+    pub fn keys_sorted_by_value(&self) -> impl DoubleEndedIterator<Item = &K>
+    where
+        V: Ord,
+    {
+        self.iter_sorted_by_value().map(|(key, _)| key)
+    }
+}
+
+impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
+    /// Returns an iterator over the keys that are stored in both this map and `other`, in
+    /// ascending order. The values of the maps don't need to have the same type.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut yesterday: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// yesterday["a"] = 1;
+    /// yesterday["b"] = 2;
+    /// let mut today: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// today["b"] = 3;
+    /// today["c"] = 4;
+    ///
+    /// assert_eq!(yesterday.intersect_keys(&today).collect::<Vec<_>>(), vec![&"b"]);
+    /// assert_eq!(yesterday.difference_keys(&today).collect::<Vec<_>>(), vec![&"a"]);
+    /// assert_eq!(yesterday.union_keys(&today).count(), 3);
+    /// ```
+    pub fn intersect_keys<'a, V2>(
+        &'a self,
+        other: &'a DefaultBTreeMap<K, V2>,
+    ) -> impl Iterator<Item = &'a K> + 'a {
+        self.map
+            .keys()
+            .filter(move |key| other.map.contains_key(*key))
+    }
+
+    /// Returns an iterator over the keys that are stored in this map but not in `other`, in
+    /// ascending order.
+    pub fn difference_keys<'a, V2>(
+        &'a self,
+        other: &'a DefaultBTreeMap<K, V2>,
+    ) -> impl Iterator<Item = &'a K> + 'a {
+        self.map
+            .keys()
+            .filter(move |key| !other.map.contains_key(*key))
+    }
+
+    /// Returns an iterator over the keys that are stored in this map or in `other`, without
+    /// duplicates, in ascending order. Both maps are walked side by side, so no keys are looked up.
+    pub fn union_keys<'a, V2>(
+        &'a self,
+        other: &'a DefaultBTreeMap<K, V2>,
+    ) -> impl Iterator<Item = &'a K> + 'a {
+        let mut left = self.map.keys().peekable();
+        let mut right = other.map.keys().peekable();
+        std::iter::from_fn(move || match (left.peek(), right.peek()) {
+            (Some(l), Some(r)) => match l.cmp(r) {
+                std::cmp::Ordering::Less => left.next(),
+                std::cmp::Ordering::Greater => right.next(),
+                std::cmp::Ordering::Equal => {
+                    right.next();
+                    left.next()
+                }
+            },
+            (Some(_), None) => left.next(),
+            (None, _) => right.next(),
+        })
+    }
+
+    /// Returns an iterator over the keys that are stored in this map or in `other`, together with
+    /// the values of both maps for these keys. Missing values are filled in with the default of
+    /// the map they are missing from. The keys are returned in ascending order, like by
+    /// `union_keys`.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut before: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// before["a"] = 1;
+    /// let mut after: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+    /// after["b"] = 2;
+    ///
+    /// let joined: Vec<_> = before.outer_join(&after).collect();
+    /// assert_eq!(joined, vec![(&"a", &1, &0), (&"b", &0, &2)]);
+    /// ```
+    pub fn outer_join<'a, V2>(
+        &'a self,
+        other: &'a DefaultBTreeMap<K, V2>,
+    ) -> impl Iterator<Item = (&'a K, &'a V, &'a V2)> + 'a {
+        let mut left = self.map.iter().peekable();
+        let mut right = other.map.iter().peekable();
+        std::iter::from_fn(move || {
+            let order = match (left.peek(), right.peek()) {
+                (Some((l, _)), Some((r, _))) => l.cmp(r),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => return None,
+            };
+            Some(match order {
+                std::cmp::Ordering::Less => {
+                    let (key, value) = left.next()?;
+                    (key, value, other.default.get())
+                }
+                std::cmp::Ordering::Greater => {
+                    let (key, value) = right.next()?;
+                    (key, self.default.get(), value)
+                }
+                std::cmp::Ordering::Equal => {
+                    let (key, value) = left.next()?;
+                    (key, value, right.next()?.1)
+                }
+            })
+        })
+    }
+}
+
+impl<K: Eq + Ord + Hash, V> DefaultBTreeMap<K, V> {
+    /// Returns a wrapper whose `Debug` output has all keys masked, see [`Redacted`].
+    pub fn redacted(&self) -> Redacted<'_, Self> {
+        Redacted { map: self }
+    }
+}
+
+impl<K: Eq + Ord + Hash, V: fmt::Debug> fmt::Debug for Redacted<'_, DefaultBTreeMap<K, V>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("DefaultBTreeMap");
+        debug
+            .field("map", &MaskedEntries(self.map.map.iter()))
+            .field("default", self.map.default.get());
+        if let Some(name) = self.map.default.name() {
+            debug.field("default_fn", &name);
+        }
+        debug.finish()
+    }
+}
+
+impl<K: Eq + Ord + CompositeKey, V> DefaultBTreeMap<K, V> {
+    /// Returns an iterator over all entries whose key has `first` as its first component.
+    /// Keys that are missing from the map are not included, so no defaults are returned.
+    /// The matching entries are found with a range lookup that starts at the smallest key with
+    /// `first` as its first component, see [`RangeByFirst`], and are returned in key order.
+    pub fn get_by_first<'a, Q>(&'a self, first: &'a Q) -> impl Iterator<Item = (&'a K, &'a V)>
+    where
+        K: RangeByFirst<Q>,
+        K::First: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map
+            .range(K::first_of_range(first)..)
+            .take_while(move |(key, _)| key.first().borrow() == first)
+    }
+}
+
+/// A guard that gives mutable access to a value of a `DefaultBTreeMap`, and removes the entry again
+/// when it's dropped while the value is equal to the default. It's returned by
+/// [`DefaultBTreeMap::get_mut_pruned`].
+pub struct PruneGuard<'a, K: Ord, V: PartialEq> {
+    state: Option<GuardState<'a, K, V>>,
+    default: &'a V,
+}
+
+enum GuardState<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>, V),
+}
+
+impl<K: Ord, V: PartialEq> Deref for PruneGuard<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        match self.state.as_ref().expect("state is only taken on drop") {
+            GuardState::Occupied(occupied) => occupied.get(),
+            GuardState::Vacant(_, value) => value,
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> DerefMut for PruneGuard<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut V {
+        match self.state.as_mut().expect("state is only taken on drop") {
+            GuardState::Occupied(occupied) => occupied.get_mut(),
+            GuardState::Vacant(_, value) => value,
+        }
+    }
+}
+
+impl<K: Ord, V: PartialEq> Drop for PruneGuard<'_, K, V> {
+    fn drop(&mut self) {
+        match self.state.take() {
+            Some(GuardState::Occupied(occupied)) if occupied.get() == self.default => {
+                occupied.remove();
+            }
+            Some(GuardState::Vacant(vacant, value)) if value != *self.default => {
+                vacant.insert(value);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// An entry of a `DefaultBTreeMap` that gives access to the entries with the neighboring keys.
+/// It's returned by [`DefaultBTreeMap::entry_with_neighbors`].
+pub struct NeighborEntry<'a, K: Ord, V> {
+    map: &'a mut BTreeMap<K, V>,
+    default: &'a LazyDefault<V>,
+    missing: &'a Missing<K>,
+    key: K,
+}
+
+impl<'a, K: Ord, V> NeighborEntry<'a, K, V> {
+    /// Returns the key of this entry.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the value stored for the key of this entry, if there is one.
+    pub fn get(&self) -> Option<&V> {
+        self.map.get(&self.key)
+    }
+
+    /// Returns the entry with the largest key that is smaller than the key of this entry.
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        self.map.range((Unbounded, Excluded(&self.key))).next_back()
+    }
+
+    /// Returns the entry with the smallest key that is larger than the key of this entry.
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        self.map.range((Excluded(&self.key), Unbounded)).next()
+    }
+
+    /// Returns a mutable reference to the value of this entry, inserting the default first if
+    /// there is no value stored for the key yet, like `get_mut` of the map.
+    pub fn into_mut(self) -> &'a mut V {
+        match self.map.entry(self.key) {
+            Entry::Occupied(occupied) => occupied.into_mut(),
+            Entry::Vacant(vacant) => {
+                self.missing.record_materialized(vacant.key());
+                vacant.insert(self.default.create())
+            }
+        }
+    }
+
+    /// Stores `value` for the key of this entry and returns a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        match self.map.entry(self.key) {
+            Entry::Occupied(occupied) => {
+                let slot = occupied.into_mut();
+                *slot = value;
+                slot
+            }
+            Entry::Vacant(vacant) => vacant.insert(value),
+        }
+    }
+
+    /// Removes the value stored for the key of this entry and returns it.
+    pub fn remove(self) -> Option<V> {
+        self.map.remove(&self.key)
+    }
+}
+
+impl<K: Eq + Ord, V> DefaultMap<K, V> for DefaultBTreeMap<K, V> {
+    type Iter<'a>
+        = Iter<'a, K, V>
+    where
+        K: 'a,
+        V: 'a;
+
+    fn get(&self, key: &K) -> &V {
+        DefaultBTreeMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: K) -> &mut V {
+        DefaultBTreeMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.map.remove(key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.map.iter()
+    }
+
+    fn get_default(&self) -> V {
+        self.default.create()
+    }
+
+    fn default_value(&self) -> &V {
+        self.default.get()
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+/// Shows the entries as `key: value` pairs, followed by the default. The alternate form `{:#}`
+/// puts every entry on its own line, which is useful for printing counters in command line tools.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut counts: DefaultBTreeMap<&str, u32> = DefaultBTreeMap::new();
+/// counts["b"] += 1;
+/// counts["a"] += 3;
+/// assert_eq!(format!("{}", counts), "{a: 3, b: 1} (default: 0)");
+/// assert_eq!(format!("{:#}", counts), "a: 3\nb: 1\n(default: 0)");
+/// ```
+impl<K: Eq + Ord + fmt::Display, V: fmt::Display> fmt::Display for DefaultBTreeMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            for (key, value) in &self.map {
+                writeln!(f, "{}: {}", key, value)?;
+            }
+        } else {
+            f.write_str("{")?;
+            for (i, (key, value)) in self.map.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}: {}", key, value)?;
+            }
+            f.write_str("} ")?;
+        }
+        write!(f, "(default: {})", self.default.get())
+    }
+}
+
+/// Implements the `Deref` trait, so all read-only methods of `BTreeMap` can be used on the map,
+/// like `get_key_value`. Methods of `DefaultBTreeMap` with the same name, such as `get`, take
+/// precedence and return the default for missing keys. There is no `DerefMut`, because that
+/// would allow changing the map without going through its methods.
+///
+/// ```
+/// # use defaultmap::*;
+/// let map: DefaultBTreeMap<&str, u32> = defaultbtreemap! {"a" => 1};
+/// assert_eq!(map.get_key_value("a"), Some((&"a", &1)));
+/// ```
+impl<K: Eq + Ord, V> Deref for DefaultBTreeMap<K, V> {
+    type Target = BTreeMap<K, V>;
+
+    fn deref(&self) -> &BTreeMap<K, V> {
+        &self.map
+    }
+}
+
+/// Deserializes the format the map is serialized in, a struct with a `map` and a `default` field,
+/// or a plain map of entries. A plain map gets the default of `V` as its default for missing
+/// keys. The input is only read as the struct when its `map` key holds a map, see
+/// [`serde_helpers`](crate::serde_helpers) to only accept plain maps.
+#[cfg(feature = "with-serde")]
+impl<'de, K, V> serde::Deserialize<'de> for DefaultBTreeMap<K, V>
+where
+    K: Eq + Ord + serde::Deserialize<'de>,
+    V: serde::Deserialize<'de> + Default,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (map, default) =
+            crate::serde_helpers::deserialize_entries(deserializer, "DefaultBTreeMap")?;
+        Ok(DefaultBTreeMap {
+            map,
+            default,
+            missing: Missing::default(),
+        })
+    }
+}
+
+/// Implements the `Index` trait so you can do `map[key]`.
+/// Nonmutable indexing can be done both by passing a reference or an owned value as the key.
+impl<K: Eq + Ord, KB: Borrow<K>, V> Index<KB> for DefaultBTreeMap<K, V> {
+    type Output = V;
+
+    fn index(&self, index: KB) -> &V {
+        let key = index.borrow();
+        match self.map.get(key) {
+            Some(value) => value,
+            None => {
+                self.missing.record_miss(Some(key));
+                self.default.get()
+            }
+        }
+    }
+}
+
+/// Implements the `IndexMut` trait so you can do `map[key] = val`.
+/// Mutably indexing can only be done when passing an owned value as the key.
+impl<K: Eq + Ord, V> IndexMut<K> for DefaultBTreeMap<K, V> {
+    #[inline]
+    fn index_mut(&mut self, index: K) -> &mut V {
+        self.get_mut(index)
+    }
+}
+
+/// Implements the `AddAssign` trait so you can do `map += other` to add the values of two maps
+/// together key-wise, like for Python's `Counter`. Keys that are missing from `map` start out at
+/// its default.
+impl<K: Eq + Ord, V: AddAssign> AddAssign for DefaultBTreeMap<K, V> {
+    fn add_assign(&mut self, other: Self) {
+        for (key, value) in other.map {
+            *self.get_mut(key) += value;
+        }
+    }
+}
+
+/// Implements the `Add` trait so you can do `map + other`, see the `AddAssign` implementation.
+/// The result keeps the default of `map`.
+impl<K: Eq + Ord, V: AddAssign> Add for DefaultBTreeMap<K, V> {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+/// Implements the `SubAssign` trait so you can do `map -= other` to subtract the values of
+/// `other` from `map` key-wise, like for Python's `Counter`. The result is clamped at the default:
+/// keys whose value ends up less than or equal to the default are removed.
+impl<K: Eq + Ord, V: SubAssign + PartialOrd> SubAssign for DefaultBTreeMap<K, V> {
+    fn sub_assign(&mut self, other: Self) {
+        for (key, value) in other.map {
+            match self.map.entry(key) {
+                Entry::Occupied(mut occupied) => {
+                    *occupied.get_mut() -= value;
+                    if *occupied.get() <= *self.default.get() {
+                        occupied.remove();
+                    }
+                }
+                Entry::Vacant(vacant) => {
+                    let mut new = self.default.create();
+                    new -= value;
+                    if new > *self.default.get() {
+                        vacant.insert(new);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Implements the `Sub` trait so you can do `map - other`, see the `SubAssign` implementation.
+impl<K: Eq + Ord, V: SubAssign + PartialOrd> Sub for DefaultBTreeMap<K, V> {
+    type Output = Self;
+
+    fn sub(mut self, other: Self) -> Self {
+        self -= other;
+        self
+    }
+}
+
+/// Implements the `BitOrAssign` trait so you can do `map |= other` to keep the maximum of the
+/// values of both maps for every key, like for Python's `Counter`. Keys that are missing from
+/// `map` are compared against its default.
+impl<K: Eq + Ord, V: PartialOrd> BitOrAssign for DefaultBTreeMap<K, V> {
+    fn bitor_assign(&mut self, other: Self) {
+        for (key, value) in other.map {
+            match self.map.entry(key) {
+                Entry::Occupied(mut occupied) => {
+                    if value > *occupied.get() {
+                        occupied.insert(value);
+                    }
+                }
+                Entry::Vacant(vacant) => {
+                    if value > *self.default.get() {
+                        vacant.insert(value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Implements the `BitOr` trait so you can do `map | other`, see the `BitOrAssign`
+/// implementation.
+impl<K: Eq + Ord, V: PartialOrd> BitOr for DefaultBTreeMap<K, V> {
+    type Output = Self;
+
+    fn bitor(mut self, other: Self) -> Self {
+        self |= other;
+        self
+    }
+}
+
+/// Implements the `BitAndAssign` trait so you can do `map &= other` to keep the minimum of the
+/// values of both maps for every key, like for Python's `Counter`. Only the keys that are stored
+/// in both maps are kept.
+impl<K: Eq + Ord, V: PartialOrd> BitAndAssign for DefaultBTreeMap<K, V> {
+    fn bitand_assign(&mut self, mut other: Self) {
+        self.map.retain(|key, value| match other.map.remove(key) {
+            Some(other_value) => {
+                if other_value < *value {
+                    *value = other_value;
+                }
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+/// Implements the `BitAnd` trait so you can do `map & other`, see the `BitAndAssign`
+/// implementation.
+impl<K: Eq + Ord, V: PartialOrd> BitAnd for DefaultBTreeMap<K, V> {
+    type Output = Self;
+
+    fn bitand(mut self, other: Self) -> Self {
+        self &= other;
+        self
+    }
+}
+
+// grcov-excl-start
+/// These methods simply forward to the underlying `BTreeMap`, see that
+/// [documentation](https://doc.rust-lang.org/std/collections/struct.BTreeMap.html) for
+/// the usage of these methods.
+impl<K: Eq + Ord, V> DefaultBTreeMap<K, V> {
+    #[inline]
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+    #[inline]
+    pub fn first_key_value(&self) -> Option<(&K, &V)>
+    where
+        K: Ord,
+    {
+        self.map.first_key_value()
+    }
+    #[inline]
+    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>>
+    where
+        K: Ord,
+    {
+        self.map.first_entry()
+    }
+    #[inline]
+    pub fn pop_first(&mut self) -> Option<(K, V)>
+    where
+        K: Ord,
+    {
+        self.map.pop_first()
+    }
+    #[inline]
+    pub fn last_key_value(&self) -> Option<(&K, &V)>
+    where
+        K: Ord,
+    {
+        self.map.last_key_value()
+    }
+    #[inline]
+    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V>>
+    where
+        K: Ord,
+    {
+        self.map.last_entry()
+    }
+    #[inline]
+    pub fn pop_last(&mut self) -> Option<(K, V)>
+    where
+        K: Ord,
+    {
+        self.map.pop_last()
+    }
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map.contains_key(k)
+    }
+    #[inline]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        self.map.insert(k, v)
+    }
+    /// A stable version of the unstable `try_insert` method of the underlying map.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<&mut V, OccupiedError<OccupiedEntry<'_, K, V>, V>> {
+        match self.map.entry(key) {
+            Entry::Occupied(entry) => Err(OccupiedError { entry, value }),
+            Entry::Vacant(entry) => Ok(entry.insert(value)),
+        }
+    }
+    #[inline]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map.remove(k)
+    }
+    #[inline]
+    pub fn retain<RF>(&mut self, f: RF)
+    where
+        RF: FnMut(&K, &mut V) -> bool,
+    {
+        self.map.retain(f)
+    }
+    #[inline]
+    pub fn append(&mut self, other: &mut Self) {
+        self.map.append(&mut other.map)
+    }
+    #[inline]
+    pub fn range<T, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        T: ?Sized + Ord,
+        K: Borrow<T> + Ord,
+        R: RangeBounds<T>,
+    {
+        self.map.range(range)
+    }
+    #[inline]
+    pub fn range_mut<T, R>(&mut self, range: R) -> RangeMut<'_, K, V>
+    where
+        T: ?Sized + Ord,
+        K: Borrow<T> + Ord,
+        R: RangeBounds<T>,
+    {
+        self.map.range_mut(range)
+    }
+    #[inline]
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        self.map.entry(key)
+    }
+    #[inline]
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        self.map.into_keys()
+    }
+    #[inline]
+    pub fn into_values(self) -> IntoValues<K, V> {
+        self.map.into_values()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.map.iter()
+    }
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.map.iter_mut()
+    }
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        self.map.keys()
+    }
+    #[inline]
+    pub fn values(&self) -> Values<'_, K, V> {
+        self.map.values()
+    }
+    #[inline]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        self.map.values_mut()
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+// grcov-excl-stop
+
+impl<K: Eq + Ord, V: Default> FromIterator<(K, V)> for DefaultBTreeMap<K, V> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        Self {
+            map: BTreeMap::from_iter(iter),
+            default: LazyDefault::std_default(),
+            missing: Missing::default(),
+        }
+    }
+}
+
+/// The `defaultbtreemap!` macro can be used to easily initialize a `DefaultBTreeMap` in the
+/// following ways:
+///
+/// ```
+/// # #[macro_use] extern crate defaultmap;
+/// # use defaultmap::*;
+/// // An empty map with the default as default
+/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{};
+///
+/// // An empty map with a specified default
+/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{5};
+///
+/// // A prefilled map with the default as the default
+/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{
+///     1 => 10,
+///     5 => 20,
+///     6 => 30,
+/// };
+///
+/// // A prefilled map with a custom default
+/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{
+///     5,
+///     1 => 10,
+///     5 => 20,
+///     6 => 30,
+/// };
+///
+/// // A prefilled map with a function that creates the default
+/// let _: DefaultBTreeMap<&str, Vec<i32>> = defaultbtreemap!{
+///     @fn || Vec::with_capacity(8),
+///     "a" => vec![1],
+/// };
+/// ```
+#[macro_export]
+macro_rules! defaultbtreemap {
+    (@single $($x:tt)*) => (());
+    (@count $($rest:expr),*) => (<[()]>::len(&[$(defaultbtreemap!(@single $rest)),*]));
+    // Copied almost verbatim from maplit
+    (@btreemap $($key:expr => $value:expr),*) => {
+        {
+            let mut _map = ::std::collections::BTreeMap::new();
+            $(
+                _map.insert($key, $value);
+            )*
+            _map
+        }
+    };
+
+    (@fn $default_fn:expr$(, $key:expr => $value:expr)+ ,) => { defaultbtreemap!(@fn $default_fn, $($key => $value),+) };
+    (@fn $default_fn:expr$(, $key:expr => $value:expr)*) => {
+        {
+            let _map = defaultbtreemap!(@btreemap $($key => $value),*);
+            $crate::DefaultBTreeMap::from_map_with_fn(_map, $default_fn)
+        }
+    };
+
+    ($($key:expr => $value:expr,)+) => { defaultbtreemap!($($key => $value),+) };
+    ($($key:expr => $value:expr),*) => {
+        {
+            let _map = defaultbtreemap!(@btreemap $($key => $value),*);
+            $crate::DefaultBTreeMap::from(_map)
+        }
+    };
+
+    ($default:expr$(, $key:expr => $value:expr)+ ,) => { defaultbtreemap!($default, $($key => $value),+) };
+    ($default:expr$(, $key:expr => $value:expr)*) => {
+        {
+            let _map = defaultbtreemap!(@btreemap $($key => $value),*);
+            $crate::DefaultBTreeMap::from_map_with_default(_map, $default)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DefaultBTreeMap;
+    use crate::MapDiff;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn macro_test() {
+        // empty default
+        let macro_map: DefaultBTreeMap<i32, i32> = defaultbtreemap! {};
+        let normal_map = DefaultBTreeMap::<i32, i32>::default();
+        assert_eq!(macro_map, normal_map);
+
+        // with content
+        let macro_map: DefaultBTreeMap<_, _> = defaultbtreemap! {
+            1 => 2,
+            2 => 3,
+        };
+        let mut normal_map = DefaultBTreeMap::<_, _>::default();
+        normal_map[1] = 2;
+        normal_map[2] = 3;
+        assert_eq!(macro_map, normal_map);
+
+        // empty with custom default
+        let macro_map: DefaultBTreeMap<i32, i32> = defaultbtreemap! {5};
+        let normal_map = DefaultBTreeMap::<i32, i32>::with_default(5);
+        assert_eq!(macro_map, normal_map);
+
+        // filled btreemap with custom default
+        let macro_map: DefaultBTreeMap<_, _> = defaultbtreemap! {
+            5,
+            1 => 2,
+            2 => 3,
+        };
+        let mut normal_map = DefaultBTreeMap::<_, _>::with_default(5);
+        normal_map[1] = 2;
+        normal_map[2] = 3;
+        assert_eq!(macro_map, normal_map);
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
+    fn add() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::default();
+        *map.get_mut(0) += 1;
+        map[1] += 4;
+        map[2] = map[0] + map.get(&1);
+        assert_eq!(*map.get(0), 1);
+        assert_eq!(*map.get(&0), 1);
+        assert_eq!(map[0], 1);
+        assert_eq!(map[&0], 1);
+        assert_eq!(*map.get(&1), 4);
+        assert_eq!(*map.get(&2), 5);
+        assert_eq!(*map.get(999), 0);
+        assert_eq!(*map.get(&999), 0);
+        assert_eq!(map[999], 0);
+        assert_eq!(map[&999], 0);
+    }
+
+    #[test]
+    fn counter() {
+        let nums = [1, 4, 3, 3, 4, 2, 4];
+        let mut counts: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::default();
+        for num in nums.iter() {
+            counts[*num] += 1;
+        }
+
+        assert_eq!(1, counts[1]);
+        assert_eq!(1, counts[2]);
+        assert_eq!(2, counts[3]);
+        assert_eq!(3, counts[4]);
+        assert_eq!(0, counts[5]);
+    }
+
+    #[test]
+    fn change_default() {
+        let mut numbers: DefaultBTreeMap<i32, String> =
+            DefaultBTreeMap::with_default("Mexico".to_string());
+
+        assert_eq!("Mexico", numbers.get_mut(1));
+        assert_eq!("Mexico", numbers.get_mut(2));
+        assert_eq!("Mexico", numbers[3]);
+
+        numbers.set_default("Cybernetics".to_string());
+        assert_eq!("Mexico", numbers[1]);
+        assert_eq!("Mexico", numbers[2]);
+        assert_eq!("Cybernetics", numbers[3]);
+        assert_eq!("Cybernetics", numbers[4]);
+        assert_eq!("Cybernetics", numbers[5]);
+    }
+
+    #[test]
+    fn synonyms() {
+        let synonym_tuples = [
+            ("nice", "sweet"),
+            ("sweet", "candy"),
+            ("nice", "entertaining"),
+            ("nice", "good"),
+            ("entertaining", "absorbing"),
+        ];
+
+        let mut synonym_map: DefaultBTreeMap<&str, Vec<&str>> = DefaultBTreeMap::default();
+
+        for &(l, r) in synonym_tuples.iter() {
+            synonym_map[l].push(r);
+            synonym_map[r].push(l);
+        }
+
+        println!("{:#?}", synonym_map);
+        assert_eq!(synonym_map["good"], vec!["nice"]);
+        assert_eq!(synonym_map["nice"], vec!["sweet", "entertaining", "good"]);
+        assert_eq!(synonym_map["evil"], Vec::<&str>::new());
+    }
+
+    #[derive(Clone)]
+    struct Clonable;
+
+    #[derive(Default, Clone)]
+    struct DefaultableValue;
+
+    #[derive(Ord, PartialOrd, Eq, PartialEq)]
+    struct Orderable(i32);
+
+    #[test]
+    fn minimal_derives() {
+        let _: DefaultBTreeMap<Orderable, Clonable> = DefaultBTreeMap::with_default(Clonable);
+        let _: DefaultBTreeMap<Orderable, DefaultableValue> = DefaultBTreeMap::default();
+    }
+
+    #[test]
+    fn from() {
+        let normal: BTreeMap<i32, i32> = vec![(0, 1), (2, 3)].into_iter().collect();
+        let mut default: DefaultBTreeMap<_, _> = normal.into();
+        default.get_mut(4);
+        assert_eq!(default[0], 1);
+        assert_eq!(default[2], 3);
+        assert_eq!(default[1], 0);
+        assert_eq!(default[4], 0);
+        let expected: BTreeMap<i32, i32> = vec![(0, 1), (2, 3), (4, 0)].into_iter().collect();
+        assert_eq!(expected, default.into());
+    }
+
+    #[test]
+    fn with_fn() {
+        let i: i32 = 20;
+        let mut map = DefaultBTreeMap::with_fn(move || i);
+        map[0] += 1;
+        assert_eq!(21, map[0]);
+        assert_eq!(20, map[1]);
+    }
+
+    #[test]
+    fn from_map_with_fn() {
+        let i: i32 = 20;
+        let normal: BTreeMap<i32, i32> = vec![(0, 1), (2, 3)].into_iter().collect();
+        let mut map = DefaultBTreeMap::from_map_with_fn(normal, move || i);
+        map[0] += 1;
+        assert_eq!(map[0], 2);
+        assert_eq!(map[1], 20);
+        assert_eq!(map[2], 3);
+    }
+
+    #[test]
+    fn first_n_last_n() {
+        let map: DefaultBTreeMap<i32, i32> = defaultbtreemap! {1 => 10, 2 => 20, 3 => 30, 4 => 40};
+        let first: Vec<_> = map.first_n(2).collect();
+        assert_eq!(first, vec![(&1, &10), (&2, &20)]);
+        let last: Vec<_> = map.last_n(3).collect();
+        assert_eq!(last, vec![(&4, &40), (&3, &30), (&2, &20)]);
+        assert_eq!(map.first_n(10).count(), 4);
+        assert_eq!(map.last_n(0).count(), 0);
+    }
+
+    #[test]
+    fn get_disjoint_mut() {
+        let mut map: DefaultBTreeMap<String, i32> = DefaultBTreeMap::with_default(5);
+        map.insert("a".to_string(), 1);
+        let [a, b, c] = map.get_disjoint_mut(["a", "b", "c"]);
+        *a += 10;
+        std::mem::swap(b, c);
+        *c += 1;
+        assert_eq!(map["a".to_string()], 11);
+        assert_eq!(map["b".to_string()], 5);
+        assert_eq!(map["c".to_string()], 6);
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate keys")]
+    fn get_disjoint_mut_duplicate() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map.get_disjoint_mut([&1, &2, &1]);
+    }
+
+    /// Equal to every key that differs by at most one, so `0` and `2` aren't equal to each other
+    /// but both find the entry for `1`.
+    #[derive(Clone, Copy, Debug)]
+    struct Close(i32);
+
+    impl PartialEq for Close {
+        fn eq(&self, other: &Close) -> bool {
+            self.cmp(other).is_eq()
+        }
+    }
+
+    impl Eq for Close {}
+
+    impl PartialOrd for Close {
+        fn partial_cmp(&self, other: &Close) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Close {
+        fn cmp(&self, other: &Close) -> std::cmp::Ordering {
+            if (self.0 - other.0).abs() <= 1 {
+                std::cmp::Ordering::Equal
+            } else {
+                self.0.cmp(&other.0)
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate keys")]
+    fn get_disjoint_mut_inconsistent_eq() {
+        let mut map: DefaultBTreeMap<Close, i32> = DefaultBTreeMap::new();
+        map.insert(Close(1), 1);
+        map.get_disjoint_mut([&Close(0), &Close(2)]);
+    }
+
+    #[test]
+    fn try_insert() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        *map.try_insert(1, 10).unwrap() += 1;
+        let err = map.try_insert(1, 20).unwrap_err();
+        assert_eq!(err.value, 20);
+        assert_eq!(err.entry.get(), &11);
+        assert_eq!(map[1], 11);
+    }
+
+    #[test]
+    fn contains_non_default() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map[1] += 1;
+        map[2] += 0;
+        assert!(map.contains_non_default(&1));
+        assert!(!map.contains_non_default(&2));
+        assert!(map.contains_key(&2));
+        assert!(!map.contains_non_default(&3));
+    }
+
+    #[test]
+    fn to_owned_values() {
+        let words = vec!["x".to_string(), "y".to_string()];
+        let mut map: DefaultBTreeMap<i32, &str> = DefaultBTreeMap::new();
+        map[1] = &words[0];
+        map[2] = &words[1];
+        let owned = map.to_owned_values();
+        drop(words);
+        assert_eq!(owned[1], "x");
+        assert_eq!(owned[2], "y");
+        assert_eq!(owned[3], "");
+        assert_eq!(owned.get_default(), "");
+    }
+
+    #[test]
+    fn get_by_first() {
+        let mut map: DefaultBTreeMap<(&str, &str), i32> = DefaultBTreeMap::new();
+        map[("a", "x")] += 1;
+        map[("a", "y")] += 2;
+        map[("b", "x")] += 4;
+
+        map[("", "z")] += 8;
+        map[("c", "")] += 16;
+
+        let a: Vec<_> = map.get_by_first(&"a").collect();
+        assert_eq!(a, vec![(&("a", "x"), &1), (&("a", "y"), &2)]);
+        assert_eq!(map.get_by_first(&"b").count(), 1);
+        assert_eq!(map.get_by_first(&"").count(), 1);
+        assert_eq!(map.get_by_first(&"c").count(), 1);
+        assert_eq!(map.get_by_first(&"d").count(), 0);
+
+        let mut map: DefaultBTreeMap<(i32, i32, i32), i32> = DefaultBTreeMap::new();
+        map[(1, i32::MIN, 0)] = 1;
+        map[(1, 5, 5)] = 2;
+        map[(0, i32::MAX, 0)] = 3;
+        map[(2, i32::MIN, i32::MIN)] = 4;
+        let values: Vec<_> = map.get_by_first(&1).map(|(_, v)| *v).collect();
+        assert_eq!(values, [1, 2]);
+    }
+
+    #[test]
+    fn prune() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(3);
+        map[1] += 1;
+        map[2] += 0;
+        map[3] = 3;
+        map[4] = 0;
+        assert_eq!(map.pruned_len(), 2);
+        let before = map.clone();
+        map.prune();
+        assert_eq!(map.len(), 2);
+        for key in 0..5 {
+            assert_eq!(map[key], before[key]);
+        }
+    }
+
+    #[test]
+    fn get_mut_pruned() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        map[1] = 5;
+        *map.get_mut_pruned(1) -= 4;
+        *map.get_mut_pruned(2) += 0;
+        *map.get_mut_pruned(3) += 1;
+        {
+            let mut guard = map.get_mut_pruned(4);
+            *guard += 1;
+            assert_eq!(*guard, 2);
+            *guard -= 1;
+        }
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[1], 1);
+        assert_eq!(map[3], 2);
+    }
+
+    #[test]
+    fn entry_with_neighbors() {
+        // Coalesce touching intervals, stored as start => end
+        let mut map: DefaultBTreeMap<u32, u32> = defaultbtreemap! {0 => 5, 10 => 15};
+        for (start, end) in [(5, 7), (7, 10), (20, 25)] {
+            let entry = map.entry_with_neighbors(start);
+            assert_eq!(entry.key(), &start);
+            assert_eq!(entry.get(), None);
+            let prev = entry.peek_prev().map(|(s, e)| (*s, *e));
+            let next = entry.peek_next().map(|(s, e)| (*s, *e));
+            let merged_end = match next {
+                Some((next_start, next_end)) if next_start == end => {
+                    map.remove(&next_start);
+                    next_end
+                }
+                _ => end,
+            };
+            match prev {
+                Some((prev_start, prev_end)) if prev_end == start => {
+                    map[prev_start] = merged_end;
+                }
+                _ => {
+                    *map.entry_with_neighbors(start).into_mut() = merged_end;
+                }
+            }
+        }
+        let intervals: Vec<_> = map.iter().map(|(s, e)| (*s, *e)).collect();
+        assert_eq!(intervals, vec![(0, 15), (20, 25)]);
+
+        assert_eq!(*map.entry_with_neighbors(30).insert(31), 31);
+        assert_eq!(map.entry_with_neighbors(30).remove(), Some(31));
+        assert_eq!(map.entry_with_neighbors(40).peek_next(), None);
+        assert_eq!(*map.entry_with_neighbors(40).into_mut(), 0);
+    }
+
+    #[test]
+    fn clone_empty() {
+        let mut map: DefaultBTreeMap<i32, Vec<i32>> = DefaultBTreeMap::with_fn(|| vec![1]);
+        map[1].push(2);
+        let mut empty = map.clone_empty();
+        assert!(empty.is_empty());
+        empty[2].push(3);
+        assert_eq!(empty[2], vec![1, 3]);
+        assert_eq!(map[1], vec![1, 2]);
+    }
+
+    #[test]
+    fn semantic_eq() {
+        let mut a: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        let mut b: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        assert!(a.semantic_eq(&b));
+        a[1] += 1;
+        b[2] += 0;
+        assert!(!a.semantic_eq(&b));
+        b[1] = 2;
+        assert!(a.semantic_eq(&b));
+        assert!(b.semantic_eq(&a));
+        b.set_default(2);
+        assert!(!a.semantic_eq(&b));
+    }
+
+    #[test]
+    fn retain_keys() {
+        let mut map: DefaultBTreeMap<i32, i32> = (0..6).map(|i| (i, i * 10)).collect();
+        map.retain_keys(|k| k % 3 == 0);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[3], 30);
+        assert_eq!(map[4], 0);
+    }
+
+    #[test]
+    fn is_default() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(2);
+        map[1] += 1;
+        map[2] += 0;
+        assert!(!map.is_default(&1));
+        assert!(map.is_default(&2));
+        assert!(map.is_default(&3));
+        assert_eq!(map.pruned_len(), 1);
+    }
+
+    #[test]
+    fn reset() {
+        let mut map: DefaultBTreeMap<i32, Vec<i32>> = DefaultBTreeMap::with_fn(|| vec![0]);
+        map[1].push(1);
+        map[2].push(2);
+        assert_eq!(map.reset(&1), Some(vec![0, 1]));
+        assert_eq!(map.reset(&3), None);
+        assert_eq!(map[1], vec![0]);
+        assert_eq!(map.len(), 2);
+        map.reset_all();
+        assert_eq!(map[2], vec![0]);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn incr_decr() {
+        let mut map: DefaultBTreeMap<&str, i64> = DefaultBTreeMap::with_default(10);
+        assert_eq!(*map.incr("a"), 11);
+        assert_eq!(*map.incr_by("a", 5), 16);
+        assert_eq!(*map.decr("b"), 9);
+        assert_eq!(*map.decr_by("a", 20), -4);
+        let mut floats: DefaultBTreeMap<&str, f64> = DefaultBTreeMap::new();
+        floats.incr_by("x", 0.5);
+        floats.incr("x");
+        assert_eq!(floats["x"], 1.5);
+        let mut small: DefaultBTreeMap<&str, i8> = DefaultBTreeMap::new();
+        small.decr("x");
+        assert_eq!(small["x"], -1);
+    }
+
+    #[test]
+    fn try_update() {
+        let mut map: DefaultBTreeMap<i32, i8> = DefaultBTreeMap::new();
+        assert_eq!(
+            map.try_update(1, |v| v.checked_sub(100).ok_or(())),
+            Ok(&mut -100)
+        );
+        assert_eq!(map.try_update(1, |v| v.checked_sub(100).ok_or(())), Err(()));
+        assert_eq!(map[1], -100);
+        assert_eq!(map.try_update(2, |_| Err::<i8, _>(())), Err(()));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn multiset() {
+        let mut map: DefaultBTreeMap<char, usize> = DefaultBTreeMap::new();
+        map.insert_count('b', 2);
+        map.insert_count('a', 1);
+        map.insert_count('d', 3);
+        map.insert_count('b', 1);
+        let sorted: Vec<_> = (0..7).map(|n| *map.nth_smallest(n).unwrap()).collect();
+        assert_eq!(sorted, vec!['a', 'b', 'b', 'b', 'd', 'd', 'd']);
+        assert_eq!(map.nth_smallest(7), None);
+        assert_eq!(map.total_in_range('b'..'d'), 3);
+        assert_eq!(map.total_in_range('b'..='d'), 6);
+        assert_eq!(map.total_in_range(..), 7);
+    }
+
+    #[test]
+    fn add_maps() {
+        let mut total: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        total[0] = 5;
+        total[1] = 5;
+        let mut partial: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        partial[1] = 2;
+        partial[2] = 3;
+        total += partial.clone();
+        assert_eq!(total[0], 5);
+        assert_eq!(total[1], 7);
+        assert_eq!(total[2], 4);
+        assert_eq!(total.len(), 3);
+
+        let sum = partial.clone() + partial;
+        assert_eq!(sum[1], 4);
+        assert_eq!(sum[2], 6);
+        assert_eq!(sum.len(), 2);
+    }
+
+    #[test]
+    fn redacted() {
+        let mut map: DefaultBTreeMap<&str, i32> = DefaultBTreeMap::new();
+        map["secret"] = 1;
+        map["hidden"] = 2;
+        let output = format!("{:?}", map.redacted());
+        assert!(!output.contains("secret"));
+        assert!(!output.contains("hidden"));
+        assert!(output.ends_with("}, default: 0 }"));
+        assert_eq!(output.matches("<redacted ").count(), 2);
+        assert_eq!(output, format!("{:?}", map.redacted()));
+    }
+
+    #[test]
+    fn counter_operators() {
+        let mut a: DefaultBTreeMap<char, i32> = DefaultBTreeMap::new();
+        a['x'] = 3;
+        a['y'] = 1;
+        a['z'] = 2;
+        let mut b: DefaultBTreeMap<char, i32> = DefaultBTreeMap::new();
+        b['x'] = 1;
+        b['y'] = 4;
+        b['w'] = 5;
+
+        let sub = a.clone() - b.clone();
+        assert_eq!(sub[&'x'], 2);
+        assert_eq!(sub[&'z'], 2);
+        assert!(!sub.contains_key(&'y'));
+        assert!(!sub.contains_key(&'w'));
+        assert_eq!(sub.len(), 2);
+
+        let union = a.clone() | b.clone();
+        assert_eq!(union[&'x'], 3);
+        assert_eq!(union[&'y'], 4);
+        assert_eq!(union[&'z'], 2);
+        assert_eq!(union[&'w'], 5);
+        assert_eq!(union.len(), 4);
+
+        let intersection = a & b;
+        assert_eq!(intersection[&'x'], 1);
+        assert_eq!(intersection[&'y'], 1);
+        assert_eq!(intersection.len(), 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn assert_invariants() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(3);
+        map[1] += 1;
+        map.set_default(5);
+        map.assert_invariants();
+    }
+
+    #[test]
+    fn total() {
+        let mut map: DefaultBTreeMap<i32, f64> = DefaultBTreeMap::with_default(1.0);
+        assert_eq!(map.total(), 0.0);
+        map[1] += 0.5;
+        map[2] = 2.0;
+        assert_eq!(map.total(), 3.5);
+    }
+
+    #[test]
+    fn max_min_by_value() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        assert_eq!(map.max_by_value(), None);
+        assert_eq!(map.argmin(), None);
+        map[1] = 5;
+        map[2] = -3;
+        map[3] = 8;
+        assert_eq!(map.max_by_value(), Some((&3, &8)));
+        assert_eq!(map.min_by_value(), Some((&2, &-3)));
+        assert_eq!(map.argmax(), Some(&3));
+        assert_eq!(map.argmin(), Some(&2));
+    }
+
+    #[test]
+    fn most_common() {
+        let map: DefaultBTreeMap<i32, i32> = (0..10).map(|i| (i, (i * 7) % 10)).collect();
+        assert_eq!(map.most_common(3), vec![(&7, &9), (&4, &8), (&1, &7)]);
+        assert_eq!(map.most_common(0), vec![]);
+        assert_eq!(map.most_common(20).len(), 10);
+        assert_eq!(map.most_common(20)[9], (&0, &0));
+    }
+
+    #[test]
+    fn map_values() {
+        let mut map: DefaultBTreeMap<i32, Vec<i32>> = DefaultBTreeMap::with_default(vec![1]);
+        map[0].push(2);
+        let lens = map.map_values(|v| v.len());
+        assert_eq!(lens[0], 2);
+        assert_eq!(lens[1], 1);
+        assert_eq!(lens.get_default(), 1);
+        assert_eq!(lens.len(), 1);
+    }
+
+    #[test]
+    fn reindex_by() {
+        let map: DefaultBTreeMap<i32, i32> = (0..6).map(|i| (i, i)).collect();
+        let sums = map.clone().reindex_by(|key, _| key % 3, |a, b| *a += b);
+        assert_eq!(sums[0], 3);
+        assert_eq!(sums[1], 5);
+        assert_eq!(sums[2], 7);
+        assert_eq!(sums.len(), 3);
+
+        let strings = map.reindex_by(|key, _| key.to_string(), |_, _| unreachable!());
+        assert_eq!(strings["5".to_string()], 5);
+        assert_eq!(strings["6".to_string()], 0);
+    }
+
+    #[test]
+    fn filter() {
+        let map: DefaultBTreeMap<i32, i32> = (0..6).map(|i| (i, i * 10)).collect();
+        let even = map.clone().filter(|key, _| key % 2 == 0);
+        assert_eq!(even.len(), 3);
+        assert_eq!(even[4], 40);
+        assert_eq!(even[5], 0);
+
+        let halved = map.filter_map(|_, value| (value >= 30).then_some(value / 2));
+        assert_eq!(halved.len(), 3);
+        assert_eq!(halved[3], 15);
+        assert_eq!(halved[2], 0);
+    }
+
+    #[test]
+    fn merge() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map[1] = 1;
+        map[2] = 2;
+        let mut other: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(7);
+        other[2] = 20;
+        other[3] = 30;
+        let mut conflicts = Vec::new();
+        map.merge(other, |key, mine, theirs| {
+            conflicts.push(*key);
+            *mine += theirs;
+        });
+        assert_eq!(conflicts, vec![2]);
+        assert_eq!(map[1], 1);
+        assert_eq!(map[2], 22);
+        assert_eq!(map[3], 30);
+        assert_eq!(map[4], 0);
+
+        let mut strict: DefaultBTreeMap<i32, i32> =
+            DefaultBTreeMap::with_fn(|| -> i32 { panic!("no default needed") });
+        strict.insert(1, 1);
+        strict.merge(defaultbtreemap!(1 => 2, 2 => 3), |_, mine, theirs| {
+            *mine += theirs
+        });
+        assert_eq!((strict[1], strict[2]), (3, 3));
+    }
+
+    #[test]
+    fn key_set_operations() {
+        let a: DefaultBTreeMap<i32, i32> = (0..5).map(|i| (i, i)).collect();
+        let b: DefaultBTreeMap<i32, String> = (3..8).map(|i| (i, i.to_string())).collect();
+        let intersection: Vec<_> = a.intersect_keys(&b).copied().collect();
+        assert_eq!(intersection, vec![3, 4]);
+        let difference: Vec<_> = a.difference_keys(&b).copied().collect();
+        assert_eq!(difference, vec![0, 1, 2]);
+        let union: Vec<_> = a.union_keys(&b).copied().collect();
+        assert_eq!(union, (0..8).collect::<Vec<_>>());
+        let union: Vec<_> = b.union_keys(&a).copied().collect();
+        assert_eq!(union, (0..8).collect::<Vec<_>>());
+
+        let mut lazy: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_fn(|| -> i32 { panic!() });
+        lazy.insert(9, 9);
+        assert_eq!(lazy.union_keys(&a).count(), 6);
+    }
+
+    #[test]
+    fn diff() {
+        let mut old: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        old[1] = 1;
+        old[2] = 2;
+        old[3] = 0;
+        let mut new: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        new[2] = 20;
+        new[3] = 0;
+        new[4] = 4;
+        assert!(old.diff(&old).is_empty());
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![(4, 4)]);
+        assert_eq!(diff.removed, vec![(1, 1)]);
+        assert_eq!(diff.changed, vec![(2, 2, 20)]);
+    }
+
+    #[test]
+    fn apply_diff() {
+        let mut old: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        old[1] = 1;
+        old[2] = 2;
+        old[3] = 3;
+        let mut new = old.clone();
+        new.remove(&1);
+        new[2] = 20;
+        new[4] = 4;
+        let mut patched = old.clone();
+        patched.apply_diff(old.diff(&new));
+        assert_eq!(patched, new);
+
+        let mut defaulted = old.clone();
+        defaulted.apply_diff(MapDiff {
+            added: vec![(5, 0)],
+            removed: vec![],
+            changed: vec![(3, 3, 0)],
+        });
+        assert!(!defaulted.contains_key(&3));
+        assert!(!defaulted.contains_key(&5));
+    }
+
+    #[test]
+    fn group_by() {
+        let groups = DefaultBTreeMap::group_by(0..10, |i| i % 3);
+        assert_eq!(groups[0], vec![0, 3, 6, 9]);
+        assert_eq!(groups[1], vec![1, 4, 7]);
+        assert_eq!(groups[2], vec![2, 5, 8]);
+        assert_eq!(groups.len(), 3);
+    }
+
+    #[test]
+    fn count_occurrences() {
+        let counts = DefaultBTreeMap::count_occurrences("hello world".chars());
+        assert_eq!(counts['l'], 3);
+        assert_eq!(counts['o'], 2);
+        assert_eq!(counts['x'], 0);
+        assert_eq!(counts.total(), 11);
+    }
+
+    #[test]
+    fn from_array() {
+        let map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::from([(1, 10), (2, 20)]);
+        assert_eq!(map[1], 10);
+        assert_eq!(map[3], 0);
+        assert_eq!(map.len(), 2);
+
+        let map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::from_array_with_default([(1, 10)], 5);
+        assert_eq!(map[1], 10);
+        assert_eq!(map[3], 5);
+    }
+
+    #[test]
+    fn outer_join() {
+        let mut a: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(-1);
+        a[1] = 1;
+        a[2] = 2;
+        let mut b: DefaultBTreeMap<i32, &str> = DefaultBTreeMap::with_default("none");
+        b[2] = "two";
+        b[3] = "three";
+        b[0] = "zero";
+        let joined: Vec<_> = a.outer_join(&b).collect();
+        assert_eq!(
+            joined,
+            vec![
+                (&0, &-1, &"zero"),
+                (&1, &1, &"none"),
+                (&2, &2, &"two"),
+                (&3, &-1, &"three")
+            ]
+        );
+    }
+
+    #[test]
+    fn iter_with_defaults() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(-1);
+        map[2] = 20;
+        let values: Vec<_> = map.iter_with_defaults(1..4).collect();
+        assert_eq!(values, vec![(1, &-1), (2, &20), (3, &-1)]);
+        let values: Vec<_> = map.iter_with_defaults(&[2]).collect();
+        assert_eq!(values, vec![(&2, &20)]);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn values_cloned() {
+        let mut map: DefaultBTreeMap<i32, String> = DefaultBTreeMap::new();
+        map[1] = "a".to_string();
+        let values: Vec<String> = map.values_cloned().collect();
+        assert_eq!(values, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn debug() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(3);
+        map[1] = 5;
+        assert_eq!(
+            format!("{:?}", map),
+            "DefaultBTreeMap { map: {1: 5}, default: 3 }"
+        );
+
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_named_fn("three", || 3);
+        map[1] = 5;
+        assert_eq!(
+            format!("{:?}", map),
+            r#"DefaultBTreeMap { map: {1: 5}, default: 3, default_fn: "three" }"#
+        );
+        assert!(format!("{:?}", map.redacted()).ends_with(r#"default_fn: "three" }"#));
+    }
+
+    #[test]
+    fn set_default_fn() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map[1] += 1;
+        map.set_default_fn(|| 10);
+        map[2] += 1;
+        assert_eq!(map[1], 1);
+        assert_eq!(map[2], 11);
+        assert_eq!(map[3], 10);
+        assert_eq!(map.get_default(), 10);
     }
 
-    #[inline]
-    pub fn iter(&self) -> Iter<K, V> {
-        self.map.iter()
+    #[test]
+    fn with_scoped_default() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_named_fn("one", || 1);
+        let len = map.with_scoped_default(5, |map| {
+            map[1] += 1;
+            map.len()
+        });
+        map[2] += 1;
+        assert_eq!(len, 1);
+        assert_eq!(map[1], 6);
+        assert_eq!(map[2], 2);
+        assert!(format!("{:?}", map).contains(r#"default_fn: "one""#));
     }
-    #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<K, V> {
-        self.map.iter_mut()
+
+    #[test]
+    fn with_scoped_default_panic() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.with_scoped_default(5, |map| {
+                map[1] += 1;
+                panic!("boom");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(map[1], 6);
+        assert_eq!(map[2], 1);
     }
-    #[inline]
-    pub fn keys(&self) -> Keys<K, V> {
-        self.map.keys()
+
+    #[test]
+    fn macro_fn() {
+        let macro_map: DefaultBTreeMap<i32, Vec<i32>> = defaultbtreemap! { @fn || vec![0] };
+        assert_eq!(macro_map[1], vec![0]);
+        assert!(macro_map.is_empty());
+
+        let mut macro_map: DefaultBTreeMap<_, _> = defaultbtreemap! {
+            @fn || vec![0],
+            1 => vec![],
+            2 => vec![2],
+        };
+        macro_map[3].push(3);
+        assert_eq!(macro_map[1], Vec::<i32>::new());
+        assert_eq!(macro_map[2], vec![2]);
+        assert_eq!(macro_map[3], vec![0, 3]);
     }
-    #[inline]
-    pub fn values(&self) -> Values<K, V> {
-        self.map.values()
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn stats() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map[1] += 1;
+        map.incr(2);
+        map.incr(2);
+        assert_eq!(map[1] + map[3] + map[4], 1);
+        assert_eq!(map.miss_count(), 2);
+        assert_eq!(map.materialized_keys(), 2);
+        let clone = map.clone();
+        map.reset_stats();
+        assert_eq!(map.miss_count(), 0);
+        assert_eq!(map.materialized_keys(), 0);
+        assert_eq!(clone.miss_count(), 2);
     }
-    #[inline]
-    pub fn values_mut(&mut self) -> ValuesMut<K, V> {
-        self.map.values_mut()
+
+    #[test]
+    fn missing_hook() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let keys = Rc::new(RefCell::new(Vec::new()));
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map[1] = 1;
+        let log = keys.clone();
+        map.set_missing_hook(move |key| log.borrow_mut().push(*key));
+        assert_eq!(map[1] + map[2] + *map.get(3), 1);
+        map[4] += 1;
+        map[4] += 1;
+        map.incr(5);
+        *map.entry_with_neighbors(8).into_mut() += 1;
+        *map.entry_with_neighbors(8).into_mut() += 1;
+        let clone = map.clone();
+        assert_eq!(clone[6], 0);
+        map.clear_missing_hook();
+        assert_eq!(map[7], 0);
+        assert_eq!(*keys.borrow(), vec![2, 4, 5, 8, 6]);
     }
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.map.len()
+
+    #[test]
+    fn try_get_mut() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map[1] = 1;
+        let fail = || Err::<i32, &str>("failed");
+        assert_eq!(map.try_get_mut(1, fail), Ok(&mut 1));
+        assert_eq!(map.try_get_mut(2, fail), Err("failed"));
+        assert!(!map.contains_key(&2));
+        *map.try_get_mut(2, || Ok::<_, ()>(5)).unwrap() += 1;
+        assert_eq!(map[2], 6);
     }
-    #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.map.is_empty()
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn get_or_insert_async() {
+        use crate::test_util::block_on;
+
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::new();
+        map[1] = 1;
+        let mut fetches = 0;
+        block_on(async {
+            assert_eq!(
+                *map.get_or_insert_async(1, |_| async { unreachable!() })
+                    .await,
+                1
+            );
+            let value = map
+                .get_or_insert_async(2, |key| {
+                    fetches += 1;
+                    let key = *key;
+                    async move { key * 10 }
+                })
+                .await;
+            *value += 1;
+        });
+        assert_eq!(fetches, 1);
+        assert_eq!(map[2], 21);
     }
-}
-// grcov-excl-stop
 
-impl<K: Eq + Ord, V: Default> FromIterator<(K, V)> for DefaultBTreeMap<K, V> {
-    fn from_iter<I>(iter: I) -> Self
-    where
-        I: IntoIterator<Item = (K, V)>,
-    {
-        Self {
-            map: BTreeMap::from_iter(iter),
-            default: V::default(),
-            default_fn: Box::new(|| V::default()),
-        }
+    #[test]
+    fn with_default_borrowed() {
+        let fallback = String::from("none");
+        let mut map: DefaultBTreeMap<i32, &str> = DefaultBTreeMap::with_default(fallback.as_str());
+        *map.get_mut(1) = "one";
+        map.get_mut(2);
+        assert_eq!(map[1], "one");
+        assert_eq!(map[2], "none");
+        assert_eq!(map.clone()[3], "none");
     }
-}
 
-/// The `defaultbtreemap!` macro can be used to easily initialize a `DefaultBTreeMap` in the
-/// following ways:
-///
-/// ```
-/// # #[macro_use] extern crate defaultmap;
-/// # use defaultmap::*;
-/// // An empty map with the default as default
-/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{};
-///
-/// // An empty map with a specified default
-/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{5};
-///
-/// // A prefilled map with the default as the default
-/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{
-///     1 => 10,
-///     5 => 20,
-///     6 => 30,
-/// };
-///
-/// // A prefilled map with a custom default
-/// let _: DefaultBTreeMap<i32, i32> = defaultbtreemap!{
-///     5,
-///     1 => 10,
-///     5 => 20,
-///     6 => 30,
-/// };
-/// ```
-#[macro_export]
-macro_rules! defaultbtreemap {
-    (@single $($x:tt)*) => (());
-    (@count $($rest:expr),*) => (<[()]>::len(&[$(defaultbtreemap!(@single $rest)),*]));
-    // Copied almost verbatim from maplit
-    (@btreemap $($key:expr => $value:expr),*) => {
-        {
-            let mut _map = ::std::collections::BTreeMap::new();
-            $(
-                _map.insert($key, $value);
-            )*
-            _map
-        }
-    };
+    #[test]
+    fn clone_shares_default_fn() {
+        use std::cell::Cell;
+        use std::rc::Rc;
 
-    ($($key:expr => $value:expr,)+) => { defaultbtreemap!($($key => $value),+) };
-    ($($key:expr => $value:expr),*) => {
-        {
-            let _map = defaultbtreemap!(@btreemap $($key => $value),*);
-            $crate::DefaultBTreeMap::from(_map)
-        }
-    };
+        struct CountClones(Rc<Cell<u32>>);
 
-    ($default:expr$(, $key:expr => $value:expr)+ ,) => { defaultbtreemap!($default, $($key => $value),+) };
-    ($default:expr$(, $key:expr => $value:expr)*) => {
-        {
-            let _map = defaultbtreemap!(@btreemap $($key => $value),*);
-            $crate::DefaultBTreeMap::from_map_with_default(_map, $default)
+        impl Clone for CountClones {
+            fn clone(&self) -> Self {
+                self.0.set(self.0.get() + 1);
+                CountClones(Rc::clone(&self.0))
+            }
         }
-    };
-}
 
-#[cfg(test)]
-mod tests {
-    use super::DefaultBTreeMap;
-    use std::collections::BTreeMap;
+        let clones = Rc::new(Cell::new(0));
+        let counter = CountClones(Rc::clone(&clones));
+        let map: DefaultBTreeMap<i32, u32> = DefaultBTreeMap::with_fn(move || counter.0.get() + 7);
+        let copy = map.clone();
+        assert_eq!(copy[1], 7);
+        assert_eq!(clones.get(), 0);
+    }
 
     #[test]
-    fn macro_test() {
-        // empty default
-        let macro_map: DefaultBTreeMap<i32, i32> = defaultbtreemap! {};
-        let normal_map = DefaultBTreeMap::<i32, i32>::default();
-        assert_eq!(macro_map, normal_map);
+    fn with_fn_lazy() {
+        use std::cell::Cell;
+        use std::rc::Rc;
 
-        // with content
-        let macro_map: DefaultBTreeMap<_, _> = defaultbtreemap! {
-            1 => 2,
-            2 => 3,
-        };
-        let mut normal_map = DefaultBTreeMap::<_, _>::default();
-        normal_map[1] = 2;
-        normal_map[2] = 3;
-        assert_eq!(macro_map, normal_map);
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let mut map: DefaultBTreeMap<i32, u32> = DefaultBTreeMap::with_fn(move || {
+            counter.set(counter.get() + 1);
+            5
+        });
+        map.insert(1, 1);
+        assert_eq!(calls.get(), 0);
+        assert_eq!(map[2], 5);
+        assert_eq!(map[3], 5);
+        assert_eq!(calls.get(), 1);
+        *map.get_mut(4) += 1;
+        assert_eq!(calls.get(), 2);
+    }
 
-        // empty with custom default
-        let macro_map: DefaultBTreeMap<i32, i32> = defaultbtreemap! {5};
-        let normal_map = DefaultBTreeMap::<i32, i32>::with_default(5);
-        assert_eq!(macro_map, normal_map);
+    #[test]
+    fn atomic_counters() {
+        use std::sync::atomic::AtomicU64;
 
-        // filled btreemap with custom default
-        let macro_map: DefaultBTreeMap<_, _> = defaultbtreemap! {
-            5,
-            1 => 2,
-            2 => 3,
-        };
-        let mut normal_map = DefaultBTreeMap::<_, _>::with_default(5);
-        normal_map[1] = 2;
-        normal_map[2] = 3;
-        assert_eq!(macro_map, normal_map);
+        let mut map: DefaultBTreeMap<i32, AtomicU64> = DefaultBTreeMap::new();
+        assert_eq!(map.fetch_add(&1, 5), None);
+        assert_eq!(map.fetch_add_or_insert(1, 5), 0);
+        assert_eq!(map.fetch_add(&1, 5), Some(5));
+        assert_eq!(map.load(&1), 10);
+        assert_eq!(map.load(&2), 0);
+        assert_eq!(map.len(), 1);
     }
 
     #[test]
-    fn add() {
-        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::default();
-        *map.get_mut(0) += 1;
-        map[1] += 4;
-        map[2] = map[0] + map.get(&1);
-        assert_eq!(*map.get(0), 1);
-        assert_eq!(*map.get(&0), 1);
-        assert_eq!(map[0], 1);
-        assert_eq!(map[&0], 1);
-        assert_eq!(*map.get(&1), 4);
-        assert_eq!(*map.get(&2), 5);
-        assert_eq!(*map.get(999), 0);
-        assert_eq!(*map.get(&999), 0);
-        assert_eq!(map[999], 0);
-        assert_eq!(map[&999], 0);
+    fn from_hashmap() {
+        let mut other: crate::DefaultHashMap<i32, Vec<i32>> =
+            crate::DefaultHashMap::with_fn(|| vec![0]);
+        other[1].push(1);
+        let mut map = DefaultBTreeMap::from(other);
+        assert_eq!(map[1], [0, 1]);
+        assert_eq!(map[2], [0]);
+        map[3].push(3);
+        assert_eq!(map[3], [0, 3]);
     }
 
     #[test]
-    fn counter() {
-        let nums = [1, 4, 3, 3, 4, 2, 4];
-        let mut counts: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::default();
-        for num in nums.iter() {
-            counts[*num] += 1;
-        }
+    fn display_empty() {
+        let map: DefaultBTreeMap<i32, &str> = DefaultBTreeMap::with_default("none");
+        assert_eq!(map.to_string(), "{} (default: none)");
+        assert_eq!(format!("{:#}", map), "(default: none)");
+    }
 
-        assert_eq!(1, counts[1]);
-        assert_eq!(1, counts[2]);
-        assert_eq!(2, counts[3]);
-        assert_eq!(3, counts[4]);
-        assert_eq!(0, counts[5]);
+    #[test]
+    fn from_keys() {
+        let map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::from_keys(1..4, 5);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[3], 5);
+        assert_eq!(map[4], 5);
+
+        let mut map: DefaultBTreeMap<i32, Vec<i32>> =
+            DefaultBTreeMap::from_keys_with_fn([1, 2, 1], || vec![0]);
+        map[1].push(1);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[1], [0, 1]);
+        assert_eq!(map[2], [0]);
     }
 
     #[test]
-    fn change_default() {
-        let mut numbers: DefaultBTreeMap<i32, String> =
-            DefaultBTreeMap::with_default("Mexico".to_string());
+    fn update_replaces() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(-1);
+        map.insert(1, 10);
+        map.insert(2, 20);
+        map.update_from(vec![(2, 21), (3, 30)]);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[2], 21);
 
-        assert_eq!("Mexico", numbers.get_mut(1));
-        assert_eq!("Mexico", numbers.get_mut(2));
-        assert_eq!("Mexico", numbers[3]);
+        let mut other: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(7);
+        other.insert(1, 11);
+        map.update_from_map(&other);
+        assert_eq!(map[1], 11);
+        assert_eq!(map[3], 30);
+        assert_eq!(map[4], -1);
+        assert_eq!(other.len(), 1);
+    }
 
-        numbers.set_default("Cybernetics".to_string());
-        assert_eq!("Mexico", numbers[1]);
-        assert_eq!("Mexico", numbers[2]);
-        assert_eq!("Cybernetics", numbers[3]);
-        assert_eq!("Cybernetics", numbers[4]);
-        assert_eq!("Cybernetics", numbers[5]);
+    #[test]
+    fn get_or_fallback() {
+        let mut map: DefaultBTreeMap<String, Vec<i32>> = DefaultBTreeMap::new();
+        map[String::from("a")].push(1);
+        let fallback = vec![0];
+        assert_eq!(map.get_or("a", &fallback), &[1]);
+        assert_eq!(map.get_or("b", &fallback), &fallback);
+        assert_eq!(map.len(), 1);
     }
 
     #[test]
-    fn synonyms() {
-        let synonym_tuples = [
-            ("nice", "sweet"),
-            ("sweet", "candy"),
-            ("nice", "entertaining"),
-            ("nice", "good"),
-            ("entertaining", "absorbing"),
-        ];
+    fn get_or_insert_with() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        *map.get_or_insert_with(1, || 10) += 1;
+        assert_eq!(*map.get_or_insert_with(1, || unreachable!()), 11);
+        *map.get_mut(2) += 1;
+        assert_eq!(map[2], 2);
+        assert_eq!(map[3], 1);
+    }
 
-        let mut synonym_map: DefaultBTreeMap<&str, Vec<&str>> = DefaultBTreeMap::default();
+    #[test]
+    fn modify_chains() {
+        let mut map: DefaultBTreeMap<i32, Vec<i32>> = DefaultBTreeMap::with_default(vec![0]);
+        map.modify(1, |v| v.push(1)).modify(2, |v| v.clear());
+        map.modify(1, |v| v.push(2));
+        assert_eq!(map[1], [0, 1, 2]);
+        assert!(map[2].is_empty());
+        assert_eq!(map[3], [0]);
+    }
 
-        for &(l, r) in synonym_tuples.iter() {
-            synonym_map[l].push(r);
-            synonym_map[r].push(l);
+    #[test]
+    fn update_or_remove() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        map.update_or_remove(1, |v| Some(v + 1));
+        map.update_or_remove(2, |_| None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[1], 2);
+
+        map.update_or_remove(1, |v| if v > 1 { Some(v - 1) } else { None });
+        assert_eq!(map[1], 1);
+        map.update_or_remove(1, |v| if v > 1 { Some(v - 1) } else { None });
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn update_or_remove_panic() {
+        let mut map: DefaultBTreeMap<i32, i32> = DefaultBTreeMap::with_default(1);
+        map[1] = 5;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.update_or_remove(1, |_| panic!("boom"));
+        }));
+        assert!(result.is_err());
+        assert!(map.contains_key(&1));
+        assert_eq!(map[1], 1);
+    }
+
+    #[test]
+    fn partition() {
+        let mut map: DefaultBTreeMap<i32, Vec<i32>> = DefaultBTreeMap::with_fn(|| vec![0]);
+        for i in 0..6 {
+            map[i].push(i);
         }
+        let (even, odd) = map.partition(|k, _| k % 2 == 0);
+        assert_eq!(even.len(), 3);
+        assert_eq!(odd.len(), 3);
+        assert_eq!(even[4], [0, 4]);
+        assert_eq!(even[5], [0]);
+        assert_eq!(odd[5], [0, 5]);
+        assert_eq!(odd[4], [0]);
+    }
 
-        println!("{:#?}", synonym_map);
-        assert_eq!(synonym_map["good"], vec!["nice"]);
-        assert_eq!(synonym_map["nice"], vec!["sweet", "entertaining", "good"]);
-        assert_eq!(synonym_map["evil"], Vec::<&str>::new());
+    #[test]
+    fn value_stats() {
+        let mut map: DefaultBTreeMap<i32, u64> = DefaultBTreeMap::with_default(1);
+        assert_eq!(map.value_stats(), None);
+        map.insert(1, 3);
+        map.insert(2, 5);
+        let stats = map.value_stats().unwrap();
+        assert_eq!((stats.count, stats.mean, stats.variance), (2, 4.0, 1.0));
+        let stats = map.value_stats_over([1, 2, 3]).unwrap();
+        assert_eq!(
+            (stats.count, stats.min, stats.max, stats.mean),
+            (3, 1.0, 5.0, 3.0)
+        );
+        assert_eq!(map.value_stats_over(Vec::<i32>::new()), None);
     }
 
-    #[derive(Clone)]
-    struct Clonable;
+    #[test]
+    fn normalize() {
+        let mut map: DefaultBTreeMap<i32, i64> = DefaultBTreeMap::with_default(-2);
+        map.insert(1, 1);
+        map.insert(2, -5);
+        map.insert(3, 8);
+        let mut normalized = map.normalized();
+        assert_eq!(normalized[1], 0.25);
+        assert_eq!(normalized[2], -1.25);
+        assert_eq!(normalized[4], -2.0);
+        normalized.insert(4, 1.0);
+        normalized.normalize();
+        assert_eq!(normalized[1], 0.125);
+        assert_eq!(normalized[5], -2.0);
 
-    #[derive(Default, Clone)]
-    struct DefaultableValue;
+        let empty: DefaultBTreeMap<i32, f64> = DefaultBTreeMap::new();
+        assert!(empty.normalized().is_empty());
 
-    #[derive(Ord, PartialOrd, Eq, PartialEq)]
-    struct Orderable(i32);
+        let mut zeros: DefaultBTreeMap<i32, f64> = DefaultBTreeMap::new();
+        zeros.insert(1, 0.0);
+        zeros.insert(2, 0.0);
+        zeros.normalize();
+        assert!(zeros[1].is_nan() && zeros[2].is_nan());
+        assert_eq!(zeros[3], 0.0);
+    }
 
     #[test]
-    fn minimal_derives() {
-        let _: DefaultBTreeMap<Orderable, Clonable> = DefaultBTreeMap::with_default(Clonable);
-        let _: DefaultBTreeMap<Orderable, DefaultableValue> = DefaultBTreeMap::default();
+    fn histogram() {
+        let histogram = DefaultBTreeMap::histogram(["a", "bb", "cc", "ddd", ""], str::len);
+        let buckets: Vec<_> = histogram.iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(buckets, [(0, 1), (1, 1), (2, 2), (3, 1)]);
+        assert_eq!(histogram[4], 0);
     }
 
     #[test]
-    fn from() {
-        let normal: BTreeMap<i32, i32> = vec![(0, 1), (2, 3)].into_iter().collect();
-        let mut default: DefaultBTreeMap<_, _> = normal.into();
-        default.get_mut(4);
-        assert_eq!(default[0], 1);
-        assert_eq!(default[2], 3);
-        assert_eq!(default[1], 0);
-        assert_eq!(default[4], 0);
-        let expected: BTreeMap<i32, i32> = vec![(0, 1), (2, 3), (4, 0)].into_iter().collect();
-        assert_eq!(expected, default.into());
+    fn sorted_by_value() {
+        let map: DefaultBTreeMap<i32, i32> = (0..5).map(|i| (i, (i * 3) % 5)).collect();
+        let ascending: Vec<_> = map.iter_sorted_by_value().map(|(_, v)| *v).collect();
+        assert_eq!(ascending, [0, 1, 2, 3, 4]);
+        let descending: Vec<_> = map.iter_sorted_by_value_desc().map(|(k, _)| *k).collect();
+        assert_eq!(descending, [3, 1, 4, 2, 0]);
+        let keys: Vec<_> = map.keys_sorted_by_value().rev().copied().collect();
+        assert_eq!(keys, descending);
     }
 
     #[test]
-    fn with_fn() {
-        let i: i32 = 20;
-        let mut map = DefaultBTreeMap::with_fn(move || i);
-        map[0] += 1;
-        assert_eq!(21, map[0]);
-        assert_eq!(20, map[1]);
+    fn extract_if() {
+        let mut map: DefaultBTreeMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+        let evens: Vec<_> = map
+            .extract_if(|k, v| {
+                *v += 1;
+                k % 2 == 0
+            })
+            .collect();
+        assert_eq!(evens, vec![(0, 1), (2, 3), (4, 5), (6, 7), (8, 9)]);
+        assert_eq!(map.len(), 5);
+        assert_eq!(map[1], 2);
+        assert_eq!(map[2], 0);
     }
 
     #[test]
-    fn from_map_with_fn() {
-        let i: i32 = 20;
-        let normal: BTreeMap<i32, i32> = vec![(0, 1), (2, 3)].into_iter().collect();
-        let mut map = DefaultBTreeMap::from_map_with_fn(normal, move || i);
-        map[0] += 1;
-        assert_eq!(map[0], 2);
-        assert_eq!(map[1], 20);
-        assert_eq!(map[2], 3);
+    fn extract_if_panic() {
+        let mut map: DefaultBTreeMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.extract_if(|k, _| if *k == 5 { panic!("boom") } else { true })
+                .count()
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.len(), 10);
+        assert_eq!(map[7], 7);
     }
 
     #[cfg(feature = "with-serde")]
@@ -594,6 +3635,48 @@ mod tests {
             assert_eq!(h2["answer"], 42);
         }
 
+        #[test]
+        #[cfg(debug_assertions)]
+        #[should_panic(expected = "disagrees with the values created for missing keys")]
+        fn assert_invariants_deserialized_default() {
+            let map: DefaultBTreeMap<&str, i32> =
+                serde_json::from_str(r#"{"map": {}, "default": 15}"#).unwrap();
+            map.assert_invariants();
+        }
+
+        #[test]
+        fn deserialize_plain_map() {
+            let h: DefaultBTreeMap<String, i32> =
+                serde_json::from_str(r#"{"foo": 3, "bar": 5}"#).unwrap();
+            assert_eq!(h.len(), 2);
+            assert_eq!(h[String::from("foo")], 3);
+            assert_eq!(h[String::from("baz")], 0);
+
+            let h: DefaultBTreeMap<&str, i32> = serde_json::from_str("{}").unwrap();
+            assert!(h.is_empty());
+
+            let h: DefaultBTreeMap<&str, i32> =
+                serde_json::from_str(r#"{"map": {"a": 1}, "default": 2}"#).unwrap();
+            assert_eq!(h["a"] + h["b"], 3);
+            let err =
+                serde_json::from_str::<DefaultBTreeMap<&str, i32>>(r#"{"map": {}}"#).unwrap_err();
+            assert!(err.to_string().starts_with("missing field `default`"));
+
+            let h: DefaultBTreeMap<u32, u32> = serde_json::from_str(r#"{"1": 5, "2": 6}"#).unwrap();
+            assert_eq!((h[1], h[2], h[3]), (5, 6, 0));
+
+            let h: DefaultBTreeMap<String, u32> =
+                serde_json::from_str(r#"{"default": 3, "x": 1, "map": 2}"#).unwrap();
+            assert_eq!(h.len(), 3);
+            assert_eq!(h[String::from("default")], 3);
+            assert_eq!(h[String::from("map")], 2);
+            assert_eq!(h[String::from("y")], 0);
+
+            let h: DefaultBTreeMap<&str, i32> =
+                serde_json::from_str(r#"{"default": 2, "map": {"a": 1}}"#).unwrap();
+            assert_eq!(h["a"] + h["b"], 3);
+        }
+
         #[test]
         fn std_btreemap() {
             let h1: DefaultBTreeMap<i32, i32> = defaultbtreemap!(1=> 10, 2=> 20);
@@ -0,0 +1,151 @@
+use std::hash::Hash;
+use std::ops::AddAssign;
+
+use crate::{DefaultFn, DefaultHashMap};
+
+/// A fixed number of `DefaultHashMap`s that are used as rotating buckets, e.g. for aggregating
+/// over a sliding time window.
+///
+/// All updates go into the current bucket. Calling `rotate()` starts a new current bucket by
+/// clearing the oldest one, so the last `N` buckets are kept around as history.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut requests: BucketedMaps<&str, u32, 3> = BucketedMaps::new();
+/// requests.current_mut()["/"] += 1;
+/// requests.rotate();
+/// requests.current_mut()["/"] += 2;
+/// requests.current_mut()["/about"] += 1;
+///
+/// let window = requests.merged_view();
+/// assert_eq!(window["/"], 3);
+/// assert_eq!(window["/about"], 1);
+///
+/// requests.rotate();
+/// requests.rotate();
+/// assert_eq!(requests.merged_view()["/"], 2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct BucketedMaps<K: Eq + Hash, V, const N: usize> {
+    buckets: [DefaultHashMap<K, V>; N],
+    current: usize,
+}
+
+impl<K: Eq + Hash, V: Default, const N: usize> BucketedMaps<K, V, N> {
+    /// Creates `N` empty buckets that use the default of `V` as the default for missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero.
+    pub fn new() -> BucketedMaps<K, V, N> {
+        BucketedMaps::from_fn(DefaultHashMap::new)
+    }
+}
+
+impl<K: Eq + Hash, V: Default, const N: usize> Default for BucketedMaps<K, V, N> {
+    /// The `default()` method is equivalent to `BucketedMaps::new()`.
+    fn default() -> BucketedMaps<K, V, N> {
+        BucketedMaps::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone + 'static, const N: usize> BucketedMaps<K, V, N> {
+    /// Creates `N` empty buckets with `default` as the default for missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero.
+    pub fn with_default(default: V) -> BucketedMaps<K, V, N> {
+        BucketedMaps::from_fn(|| DefaultHashMap::with_default(default.clone()))
+    }
+}
+
+impl<K: Eq + Hash, V, const N: usize> BucketedMaps<K, V, N> {
+    /// Creates `N` empty buckets with `default_fn` as the default value generation function for
+    /// missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is zero.
+    pub fn with_fn(default_fn: impl DefaultFn<V> + Clone + 'static) -> BucketedMaps<K, V, N> {
+        BucketedMaps::from_fn(|| DefaultHashMap::with_fn(default_fn.clone()))
+    }
+
+    fn from_fn(mut new_bucket: impl FnMut() -> DefaultHashMap<K, V>) -> BucketedMaps<K, V, N> {
+        assert!(N > 0, "BucketedMaps needs at least one bucket");
+        BucketedMaps {
+            buckets: std::array::from_fn(|_| new_bucket()),
+            current: 0,
+        }
+    }
+
+    /// Returns the bucket that is currently being filled.
+    pub fn current(&self) -> &DefaultHashMap<K, V> {
+        &self.buckets[self.current]
+    }
+
+    /// Returns a mutable reference to the bucket that is currently being filled.
+    pub fn current_mut(&mut self) -> &mut DefaultHashMap<K, V> {
+        &mut self.buckets[self.current]
+    }
+
+    /// Starts a new current bucket. The oldest bucket is cleared and reused for this, so its
+    /// capacity and default are kept.
+    pub fn rotate(&mut self) {
+        self.current = (self.current + 1) % N;
+        self.buckets[self.current].clear();
+    }
+
+    /// Returns an iterator over all buckets, starting with the current one and ending with the
+    /// oldest one.
+    pub fn buckets(&self) -> impl Iterator<Item = &DefaultHashMap<K, V>> {
+        (0..N).map(move |age| &self.buckets[(self.current + N - age) % N])
+    }
+
+    /// Returns a single map that contains the sum of the values in all buckets. Keys that are
+    /// missing from the current bucket start out at its default.
+    pub fn merged_view(&self) -> DefaultHashMap<K, V>
+    where
+        K: Clone,
+        V: Clone + AddAssign,
+    {
+        let mut buckets = self.buckets();
+        let mut merged = buckets.next().expect("there is always a bucket").clone();
+        for bucket in buckets {
+            for (key, value) in bucket.iter() {
+                merged[key.clone()] += value.clone();
+            }
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BucketedMaps;
+
+    #[test]
+    fn rotate() {
+        let mut maps: BucketedMaps<i32, i32, 2> = BucketedMaps::with_default(1);
+        maps.current_mut()[0] += 1;
+        maps.rotate();
+        maps.current_mut()[0] += 10;
+        maps.current_mut()[1] += 10;
+        assert_eq!(maps.current()[0], 11);
+        assert_eq!(maps.merged_view()[0], 13);
+        assert_eq!(maps.merged_view()[1], 11);
+        assert_eq!(maps.merged_view()[2], 1);
+
+        maps.rotate();
+        assert!(maps.current().is_empty());
+        let lens: Vec<_> = maps.buckets().map(|b| b.len()).collect();
+        assert_eq!(lens, vec![0, 2]);
+        assert_eq!(maps.merged_view()[0], 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one bucket")]
+    fn zero_buckets() {
+        let _: BucketedMaps<i32, i32, 0> = BucketedMaps::new();
+    }
+}
@@ -0,0 +1,113 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// A string key that ignores case when it's hashed and compared, so it can be used as the key
+/// of a `DefaultHashMap` or `DefaultBTreeMap` to make lookups case-insensitive.
+///
+/// Case is folded by comparing the lowercase versions of the characters. The original string
+/// is kept, so iterating over the map returns keys in the case they were first inserted in.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut headers: DefaultHashMap<CaseInsensitive<&str>, Vec<&str>> = DefaultHashMap::new();
+/// headers[CaseInsensitive("Accept")].push("text/html");
+/// headers[CaseInsensitive("accept")].push("application/json");
+///
+/// assert_eq!(headers[CaseInsensitive("ACCEPT")], ["text/html", "application/json"]);
+/// assert!(headers[CaseInsensitive("Content-Type")].is_empty());
+/// assert_eq!(headers.keys().next().unwrap().0, "Accept");
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct CaseInsensitive<T>(pub T);
+
+impl<T: AsRef<str>> CaseInsensitive<T> {
+    fn folded(&self) -> impl Iterator<Item = char> + '_ {
+        self.0.as_ref().chars().flat_map(char::to_lowercase)
+    }
+}
+
+impl<T> From<T> for CaseInsensitive<T> {
+    fn from(value: T) -> CaseInsensitive<T> {
+        CaseInsensitive(value)
+    }
+}
+
+impl<T: AsRef<str>> AsRef<str> for CaseInsensitive<T> {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+impl<T: AsRef<str>> PartialEq for CaseInsensitive<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.folded().eq(other.folded())
+    }
+}
+
+impl<T: AsRef<str>> Eq for CaseInsensitive<T> {}
+
+impl<T: AsRef<str>> PartialOrd for CaseInsensitive<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: AsRef<str>> Ord for CaseInsensitive<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.folded().cmp(other.folded())
+    }
+}
+
+impl<T: AsRef<str>> Hash for CaseInsensitive<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for c in self.folded() {
+            state.write_u32(u32::from(c));
+        }
+        // Like `str`, end with a byte that can't be part of the string, so that keys which are
+        // concatenated in a tuple don't collide.
+        state.write_u8(0xff);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CaseInsensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for CaseInsensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CaseInsensitive;
+    use crate::{DefaultBTreeMap, DefaultHashMap};
+
+    #[test]
+    fn folds_case() {
+        assert_eq!(CaseInsensitive("Straße"), CaseInsensitive("STRAßE"));
+        assert_ne!(CaseInsensitive("a"), CaseInsensitive("ab"));
+        assert!(CaseInsensitive("apple") < CaseInsensitive("Banana"));
+
+        let mut counts: DefaultHashMap<CaseInsensitive<String>, u32> = DefaultHashMap::new();
+        counts[CaseInsensitive("Content-Type".to_string())] += 1;
+        counts[CaseInsensitive("content-type".to_string())] += 1;
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[CaseInsensitive("CONTENT-TYPE".to_string())], 2);
+
+        let mut ordered: DefaultBTreeMap<CaseInsensitive<&str>, u32> = DefaultBTreeMap::new();
+        ordered[CaseInsensitive("b")] += 1;
+        ordered[CaseInsensitive("A")] += 1;
+        ordered[CaseInsensitive("B")] += 1;
+        let keys: Vec<String> = ordered.keys().map(|key| key.to_string()).collect();
+        assert_eq!(keys, ["A", "b"]);
+        assert_eq!(
+            format!("{:?}", ordered),
+            r#"DefaultBTreeMap { map: {"A": 1, "b": 2}, default: 0 }"#
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
+
+use crate::default_fn::LazyDefault;
+use crate::DefaultFn;
+
+/// A stack of maps that are consulted in order, that returns a default for keys that none of
+/// them contain. This is Python's `ChainMap` combined with a default.
+///
+/// The first layer has the highest priority and is the only one that is written to. Lower
+/// layers are typically defaults that should not be changed, like the system configuration
+/// below the user configuration. Changing a value through `get_mut` or `map[key]` first copies it
+/// from the layer it was found in into the first layer.
+///
+/// ```
+/// # use defaultmap::*;
+/// use std::collections::HashMap;
+///
+/// let system = HashMap::from([("editor", "vi"), ("pager", "less")]);
+/// let mut config: ChainDefaultMap<&str, &str> = ChainDefaultMap::with_default("unset");
+/// config.add_layer(system);
+/// config.insert("editor", "nano");
+///
+/// assert_eq!(config["editor"], "nano");
+/// assert_eq!(config["pager"], "less");
+/// assert_eq!(config["shell"], "unset");
+/// assert_eq!(config.layer(1).unwrap()["editor"], "vi");
+/// ```
+#[derive(Clone)]
+pub struct ChainDefaultMap<K: Eq + Hash, V> {
+    // Never empty, the first layer is the one that is written to.
+    layers: Vec<HashMap<K, V>>,
+    default: LazyDefault<V>,
+}
+
+impl<K: Eq + Hash, V: Default> ChainDefaultMap<K, V> {
+    /// Creates a map with a single empty layer, with the default of `V` as the default for keys
+    /// that no layer contains.
+    pub fn new() -> ChainDefaultMap<K, V> {
+        ChainDefaultMap::with_lazy_default(LazyDefault::std_default())
+    }
+}
+
+impl<K: Eq + Hash, V: Default> Default for ChainDefaultMap<K, V> {
+    /// The `default()` method is equivalent to `ChainDefaultMap::new()`.
+    fn default() -> ChainDefaultMap<K, V> {
+        ChainDefaultMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> ChainDefaultMap<K, V> {
+    /// Creates a map with a single empty layer, with `default` as the default for keys that no
+    /// layer contains.
+    pub fn with_default(default: V) -> ChainDefaultMap<K, V> {
+        ChainDefaultMap::with_lazy_default(LazyDefault::value(default))
+    }
+}
+
+impl<K: Eq + Hash, V> ChainDefaultMap<K, V> {
+    /// Creates a map with a single empty layer, with `default_fn` as the default value generation
+    /// function for keys that no layer contains.
+    pub fn with_fn(default_fn: impl DefaultFn<V> + 'static) -> ChainDefaultMap<K, V> {
+        ChainDefaultMap::with_lazy_default(LazyDefault::with_fn(default_fn))
+    }
+
+    fn with_lazy_default(default: LazyDefault<V>) -> ChainDefaultMap<K, V> {
+        ChainDefaultMap {
+            layers: vec![HashMap::new()],
+            default,
+        }
+    }
+
+    /// Adds `layer` below all existing layers, so it's consulted last before the default.
+    pub fn add_layer(&mut self, layer: HashMap<K, V>) {
+        self.layers.push(layer);
+    }
+
+    /// Adds a new empty layer above all existing layers. Writes go to the new layer from now on,
+    /// so they can be undone by `pop_layer`.
+    pub fn push_layer(&mut self) {
+        self.layers.insert(0, HashMap::new());
+    }
+
+    /// Removes the first layer and returns it, so that writes go to the layer below it. Returns
+    /// `None` when there is only one layer, which is never removed.
+    pub fn pop_layer(&mut self) -> Option<HashMap<K, V>> {
+        if self.layers.len() == 1 {
+            return None;
+        }
+        Some(self.layers.remove(0))
+    }
+
+    /// Returns the layer at `index`, where `0` is the first layer.
+    pub fn layer(&self, index: usize) -> Option<&HashMap<K, V>> {
+        self.layers.get(index)
+    }
+
+    /// Returns the number of layers, which is at least one.
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns a reference to the value of the key in the first layer that contains it, or to
+    /// the default if no layer does.
+    pub fn get<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.layers
+            .iter()
+            .find_map(|layer| layer.get(key))
+            .unwrap_or_else(|| self.default.get())
+    }
+
+    /// Returns `true` if any layer contains the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.layers.iter().any(|layer| layer.contains_key(key))
+    }
+
+    /// Inserts a value for the key into the first layer and returns the previous value of the
+    /// first layer, if it had one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.layers[0].insert(key, value)
+    }
+
+    /// Removes the key from the first layer and returns its value, if it had one. The lower
+    /// layers aren't changed, so they can still contain the key.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.layers[0].remove(key)
+    }
+
+    /// Returns an iterator over the keys of all layers together with the values that `get`
+    /// returns for them. Every key is returned once, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.layers.iter().enumerate().flat_map(move |(i, layer)| {
+            layer
+                .iter()
+                .filter(move |(key, _)| !self.layers[..i].iter().any(|l| l.contains_key(*key)))
+        })
+    }
+
+    /// Returns the number of distinct keys in all layers.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns `true` if no layer contains any key.
+    pub fn is_empty(&self) -> bool {
+        self.layers.iter().all(HashMap::is_empty)
+    }
+
+    /// Returns a reference to the default value of the map.
+    pub fn default_value(&self) -> &V {
+        self.default.get()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> ChainDefaultMap<K, V> {
+    /// Returns a mutable reference to the value of the key in the first layer. If the first layer
+    /// doesn't contain the key, the value from the first lower layer that does is cloned into it,
+    /// or the default if no layer contains the key.
+    pub fn get_mut(&mut self, key: K) -> &mut V {
+        let (first, lower) = self
+            .layers
+            .split_first_mut()
+            .expect("there is always a layer");
+        first.entry(key).or_insert_with_key(|key| {
+            match lower.iter().find_map(|layer| layer.get(key)) {
+                Some(value) => value.clone(),
+                None => self.default.create(),
+            }
+        })
+    }
+}
+
+/// Implements the `Index` trait so you can do `map[key]`, see `get`.
+impl<K: Eq + Hash, KB: Borrow<K>, V> Index<KB> for ChainDefaultMap<K, V> {
+    type Output = V;
+
+    fn index(&self, index: KB) -> &V {
+        self.get(index.borrow())
+    }
+}
+
+/// Implements the `IndexMut` trait so you can do `map[key] = val`, see `get_mut`.
+impl<K: Eq + Hash, V: Clone> IndexMut<K> for ChainDefaultMap<K, V> {
+    fn index_mut(&mut self, index: K) -> &mut V {
+        self.get_mut(index)
+    }
+}
+
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug> fmt::Debug for ChainDefaultMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainDefaultMap")
+            .field("layers", &self.layers)
+            .field("default", self.default.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::ChainDefaultMap;
+
+    #[test]
+    fn layered_lookups() {
+        let mut map: ChainDefaultMap<&str, i32> = ChainDefaultMap::with_default(-1);
+        map.add_layer(HashMap::from([("a", 1), ("b", 2)]));
+        map.add_layer(HashMap::from([("b", 20), ("c", 30)]));
+        assert_eq!(map.layer_count(), 3);
+        assert_eq!((map["a"], map["b"], map["c"], map["d"]), (1, 2, 30, -1));
+        assert_eq!(map.len(), 3);
+        assert!(map.contains_key("c"));
+
+        map["c"] += 1;
+        map["d"] += 1;
+        assert_eq!(map.layer(0).unwrap().len(), 2);
+        assert_eq!((map["c"], map["d"]), (31, 0));
+        assert_eq!(map.layer(2).unwrap()["c"], 30);
+        assert_eq!(map.remove("c"), Some(31));
+        assert_eq!(map["c"], 30);
+
+        let mut entries: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+        entries.sort();
+        assert_eq!(entries, [("a", 1), ("b", 2), ("c", 30), ("d", 0)]);
+    }
+
+    #[test]
+    fn push_and_pop_layers() {
+        let mut map: ChainDefaultMap<i32, Vec<i32>> = ChainDefaultMap::new();
+        assert!(map.is_empty());
+        assert_eq!(map.pop_layer(), None);
+        map.insert(1, vec![1]);
+        map.push_layer();
+        map[1].push(2);
+        map.insert(2, vec![2]);
+        assert_eq!(map[1], [1, 2]);
+        let popped = map.pop_layer().unwrap();
+        assert_eq!(popped.len(), 2);
+        assert_eq!(map[1], [1]);
+        assert!(map[2].is_empty());
+        assert_eq!(
+            format!("{:?}", map),
+            "ChainDefaultMap { layers: [{1: [1]}], default: [] }"
+        );
+    }
+}
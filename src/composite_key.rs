@@ -0,0 +1,105 @@
+/// A key that is made up of multiple components, of which the first one can be used on its own
+/// to find all entries that share it.
+///
+/// This is implemented for tuples of up to four elements, so a map keyed by `(tenant, metric)`
+/// can be queried for all metrics of a single tenant without building joined string keys.
+/// Struct keys can implement it by returning the field that should act as the first component.
+/// `DefaultBTreeMap::get_by_first` additionally needs [`RangeByFirst`].
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut counts: DefaultHashMap<(&str, &str), u32> = DefaultHashMap::new();
+/// counts[("acme", "requests")] += 3;
+/// counts[("acme", "errors")] += 1;
+/// counts[("globex", "requests")] += 7;
+///
+/// let acme_total: u32 = counts.get_by_first("acme").map(|(_, v)| v).sum();
+/// assert_eq!(acme_total, 4);
+/// ```
+pub trait CompositeKey {
+    /// The type of the first component of the key.
+    type First;
+
+    /// Returns a reference to the first component of the key.
+    fn first(&self) -> &Self::First;
+}
+
+/// A composite key that can create the smallest key with a given first component. This lets
+/// `DefaultBTreeMap::get_by_first` find the matching entries with a single range lookup instead
+/// of scanning the map, so the `Ord` implementation of the key has to order by the first
+/// component before anything else, like it does for tuples.
+///
+/// This is implemented for tuples whose other components are integers, `bool`, `char`, `()`,
+/// `&str` or `String`, where `Q` is a borrowed form of the first component that can be converted
+/// to it with `ToOwned`.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut counts: DefaultBTreeMap<(String, u32), u32> = DefaultBTreeMap::new();
+/// counts[("acme".to_string(), 2)] += 3;
+/// counts[("acme".to_string(), 1)] += 1;
+/// counts[("globex".to_string(), 1)] += 7;
+///
+/// let acme: Vec<_> = counts.get_by_first("acme").map(|(key, v)| (key.1, *v)).collect();
+/// assert_eq!(acme, [(1, 1), (2, 3)]);
+/// ```
+pub trait RangeByFirst<Q: ?Sized>: CompositeKey + Sized {
+    /// Returns the smallest key whose first component is `first`.
+    fn first_of_range(first: &Q) -> Self;
+}
+
+mod private {
+    /// Types with a smallest value, used for the components of a tuple key after the first one.
+    /// This is public in a private module, so it can't be implemented outside this crate.
+    pub trait Minimum {
+        fn minimum() -> Self;
+    }
+}
+
+use private::Minimum;
+
+macro_rules! impl_minimum {
+    ($($t:ty => $min:expr),* $(,)?) => {
+        $(impl Minimum for $t {
+            #[inline]
+            fn minimum() -> Self {
+                $min
+            }
+        })*
+    };
+}
+
+impl_minimum!(
+    u8 => u8::MIN, u16 => u16::MIN, u32 => u32::MIN, u64 => u64::MIN, u128 => u128::MIN,
+    usize => usize::MIN, i8 => i8::MIN, i16 => i16::MIN, i32 => i32::MIN, i64 => i64::MIN,
+    i128 => i128::MIN, isize => isize::MIN, bool => false, char => '\0', () => (),
+    &str => "", String => String::new(),
+);
+
+macro_rules! impl_composite_key {
+    ($first:ident $(, $rest:ident)+) => {
+        impl<$first, $($rest),+> CompositeKey for ($first, $($rest),+) {
+            type First = $first;
+
+            #[inline]
+            fn first(&self) -> &$first {
+                &self.0
+            }
+        }
+
+        impl<Q, $first, $($rest),+> RangeByFirst<Q> for ($first, $($rest),+)
+        where
+            Q: ?Sized + ToOwned<Owned = $first>,
+            $($rest: Minimum),+
+        {
+            #[inline]
+            fn first_of_range(first: &Q) -> Self {
+                (first.to_owned(), $($rest::minimum()),+)
+            }
+        }
+    };
+}
+
+impl_composite_key!(A, B);
+impl_composite_key!(A, B, C);
+impl_composite_key!(A, B, C, D);
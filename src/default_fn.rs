@@ -1,7 +1,16 @@
+use std::cell::OnceCell;
+use std::sync::Arc;
+
 use dyn_clone::DynClone;
 
 pub trait DefaultFn<V>: DynClone {
     fn call(&self) -> V;
+
+    /// Returns the name of the function that is shown in the `Debug` output of the maps, if it
+    /// has one.
+    fn name(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl<F, V> DefaultFn<V> for F
@@ -13,6 +22,216 @@ where
     }
 }
 
+/// A default value generation function that can fail, as used by the `try_get_mut` methods of
+/// the maps. It's implemented for all closures that return a `Result`.
+pub trait TryDefaultFn<V, E> {
+    fn try_call(&self) -> Result<V, E>;
+}
+
+impl<F, V, E> TryDefaultFn<V, E> for F
+where
+    F: Fn() -> Result<V, E>,
+{
+    fn try_call(&self) -> Result<V, E> {
+        self()
+    }
+}
+
+/// A default value generation function with a name, as created by the `with_named_fn`
+/// constructors of the maps.
+#[derive(Clone)]
+pub(crate) struct NamedFn<F> {
+    pub(crate) name: &'static str,
+    pub(crate) f: F,
+}
+
+impl<F, V> DefaultFn<V> for NamedFn<F>
+where
+    F: DefaultFn<V> + Clone,
+{
+    fn call(&self) -> V {
+        self.f.call()
+    }
+
+    fn name(&self) -> Option<&str> {
+        Some(self.name)
+    }
+}
+
+/// Where a map gets the values for missing keys from. Using `V::default()` and cloning the default
+/// of the map are the common cases, so they are stored as plain function pointers, which means
+/// creating a map with `new()` or `with_default()` doesn't allocate. Other functions are shared
+/// between clones of a map, so cloning a map doesn't clone the function.
+enum DefaultSource<V> {
+    StdDefault(fn() -> V),
+    CloneDefault(fn(&V) -> V),
+    Fn(Arc<dyn DefaultFn<V>>),
+}
+
+impl<V> Clone for DefaultSource<V> {
+    fn clone(&self) -> Self {
+        match self {
+            DefaultSource::StdDefault(f) => DefaultSource::StdDefault(*f),
+            DefaultSource::CloneDefault(f) => DefaultSource::CloneDefault(*f),
+            DefaultSource::Fn(f) => DefaultSource::Fn(Arc::clone(f)),
+        }
+    }
+}
+
+/// The default of a map, together with the way new copies of it are created. The default itself
+/// is only created when it's first needed, so an expensive default value generation function
+/// isn't called for maps that never see a missing key.
+pub(crate) struct LazyDefault<V> {
+    value: OnceCell<V>,
+    source: DefaultSource<V>,
+}
+
+impl<V> LazyDefault<V> {
+    pub(crate) fn std_default() -> LazyDefault<V>
+    where
+        V: Default,
+    {
+        LazyDefault {
+            value: OnceCell::new(),
+            source: DefaultSource::StdDefault(V::default),
+        }
+    }
+
+    /// Uses `value` as the default, and clones it to create new copies.
+    pub(crate) fn value(value: V) -> LazyDefault<V>
+    where
+        V: Clone,
+    {
+        LazyDefault {
+            value: OnceCell::from(value),
+            source: DefaultSource::CloneDefault(V::clone),
+        }
+    }
+
+    /// Uses `value` as the default, and `V::default()` to create new copies, see the
+    /// `Deserialize` implementation.
+    #[cfg(feature = "with-serde")]
+    pub(crate) fn deserialized(value: V) -> LazyDefault<V>
+    where
+        V: Default,
+    {
+        LazyDefault {
+            value: OnceCell::from(value),
+            source: DefaultSource::StdDefault(V::default),
+        }
+    }
+
+    pub(crate) fn with_fn(default_fn: impl DefaultFn<V> + 'static) -> LazyDefault<V> {
+        LazyDefault {
+            value: OnceCell::new(),
+            source: DefaultSource::Fn(Arc::new(default_fn)),
+        }
+    }
+
+    fn init(&self) -> V {
+        match &self.source {
+            DefaultSource::StdDefault(f) => f(),
+            DefaultSource::Fn(f) => f.call(),
+            DefaultSource::CloneDefault(_) => unreachable!("a default that is cloned is stored"),
+        }
+    }
+
+    /// Returns the default, creating it first if that didn't happen yet.
+    #[inline]
+    pub(crate) fn get(&self) -> &V {
+        self.value.get_or_init(|| self.init())
+    }
+
+    /// Creates a new copy of the default, e.g. to insert it for a missing key.
+    #[inline]
+    pub(crate) fn create(&self) -> V {
+        match &self.source {
+            DefaultSource::StdDefault(f) => f(),
+            DefaultSource::CloneDefault(f) => f(self.get()),
+            DefaultSource::Fn(f) => f.call(),
+        }
+    }
+
+    /// Returns a default that creates its values in the same way, but that has its own copy of
+    /// the default.
+    pub(crate) fn fresh(&self) -> LazyDefault<V> {
+        let value = match self.source {
+            DefaultSource::CloneDefault(_) => OnceCell::from(self.create()),
+            _ => OnceCell::new(),
+        };
+        LazyDefault {
+            value,
+            source: self.source.clone(),
+        }
+    }
+
+    pub(crate) fn into_value(mut self) -> V {
+        match self.value.take() {
+            Some(value) => value,
+            None => self.init(),
+        }
+    }
+
+    /// Returns a default that applies `f` to the values created by this one. `f` is only called
+    /// once the new default is needed.
+    pub(crate) fn map<W>(self, f: impl Fn(V) -> W + Clone + 'static) -> LazyDefault<W>
+    where
+        V: 'static,
+    {
+        let default = Arc::new(self);
+        LazyDefault::with_fn(move || f(default.create()))
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        match &self.source {
+            DefaultSource::Fn(f) => f.name(),
+            _ => None,
+        }
+    }
+
+    /// Returns `false` if the stored default differs from the values that the source creates
+    /// for missing keys, e.g. for a deserialized default that isn't `V::default()`. Functions set
+    /// by the user can return a different value on every call, so those aren't checked.
+    #[cfg(debug_assertions)]
+    pub(crate) fn agrees_with_source(&self) -> bool
+    where
+        V: PartialEq,
+    {
+        match &self.source {
+            DefaultSource::StdDefault(f) => self.value.get().map_or(true, |value| *value == f()),
+            DefaultSource::CloneDefault(f) => {
+                self.value.get().is_some_and(|value| f(value) == *value)
+            }
+            DefaultSource::Fn(_) => true,
+        }
+    }
+}
+
+impl<V: Clone> Clone for LazyDefault<V> {
+    fn clone(&self) -> Self {
+        LazyDefault {
+            value: self.value.clone(),
+            source: self.source.clone(),
+        }
+    }
+}
+
+/// The default is serialized as its value. A deserialized default uses `V::default()` to create
+/// new copies, like maps that are deserialized always did.
+#[cfg(feature = "with-serde")]
+impl<V: serde::Serialize> serde::Serialize for LazyDefault<V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "with-serde")]
+impl<'de, V: serde::Deserialize<'de> + Default> serde::Deserialize<'de> for LazyDefault<V> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        V::deserialize(deserializer).map(LazyDefault::deserialized)
+    }
+}
+
 impl<V> Default for Box<dyn DefaultFn<V>>
 where
     V: Default,
@@ -0,0 +1,85 @@
+/// The common interface of `DefaultHashMap` and `DefaultBTreeMap`, so code can be generic over
+/// the kind of map and leave the choice between hashed and ordered keys to its callers.
+///
+/// The methods behave like the inherent methods of the maps with the same name, which take
+/// precedence when the concrete map type is known.
+///
+/// ```
+/// # use defaultmap::*;
+/// fn count_words<M: DefaultMap<String, u32>>(map: &mut M, text: &str) {
+///     for word in text.split_whitespace() {
+///         *map.get_mut(word.to_string()) += 1;
+///     }
+/// }
+///
+/// let mut hashed: DefaultHashMap<String, u32> = DefaultHashMap::new();
+/// let mut ordered: DefaultBTreeMap<String, u32> = DefaultBTreeMap::new();
+/// count_words(&mut hashed, "a b a");
+/// count_words(&mut ordered, "a b a");
+/// assert_eq!(hashed[String::from("a")], 2);
+/// assert_eq!(ordered[String::from("a")], 2);
+/// ```
+pub trait DefaultMap<K, V> {
+    /// The iterator over the stored entries that is returned by `iter`.
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    /// Returns a reference to the value stored for the key, or to the default if there is none.
+    fn get(&self, key: &K) -> &V;
+
+    /// Returns a mutable reference to the value stored for the key, inserting the default first
+    /// if there is none.
+    fn get_mut(&mut self, key: K) -> &mut V;
+
+    /// Inserts a value for the key and returns the previous value, if there was one.
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+    /// Removes the key from the map and returns its value, if it was stored.
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    /// Returns an iterator over the stored entries.
+    fn iter(&self) -> Self::Iter<'_>;
+
+    /// Returns a new copy of the default value.
+    fn get_default(&self) -> V;
+
+    /// Returns a reference to the default value.
+    fn default_value(&self) -> &V;
+
+    /// Returns the number of stored entries.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if no entries are stored.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DefaultMap;
+    use crate::{DefaultBTreeMap, DefaultHashMap};
+
+    fn exercise<M: DefaultMap<i32, i32>>(mut map: M) {
+        assert!(map.is_empty());
+        *map.get_mut(1) += 2;
+        assert_eq!(map.insert(2, 5), None);
+        assert_eq!(map.insert(2, 6), Some(5));
+        assert_eq!(*map.get(&1), 9);
+        assert_eq!(*map.get(&3), 7);
+        assert_eq!(map.get_default(), 7);
+        assert_eq!(*map.default_value(), 7);
+        assert_eq!(map.iter().map(|(_, v)| v).sum::<i32>(), 15);
+        assert_eq!(map.remove(&1), Some(9));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn both_maps() {
+        exercise(DefaultHashMap::with_default(7));
+        exercise(DefaultBTreeMap::with_default(7));
+    }
+}
@@ -0,0 +1,49 @@
+/// The changes between two maps, as returned by the `diff` method of the maps.
+///
+/// Keys that are missing from a map are treated as having the default value of that map. So a
+/// key is only reported as added if its old value was the default, and only as removed if its new
+/// value is the default.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut old: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+/// old["timeout"] = 30;
+/// old["retries"] = 3;
+/// let mut new = old.clone();
+/// new["timeout"] = 60;
+/// new["retries"] = 0;
+/// new["workers"] = 4;
+///
+/// let diff = old.diff(&new);
+/// assert_eq!(diff.added, vec![("workers", 4)]);
+/// assert_eq!(diff.removed, vec![("retries", 3)]);
+/// assert_eq!(diff.changed, vec![("timeout", 30, 60)]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MapDiff<K, V> {
+    /// The keys that only have a non-default value in the new map, together with that value.
+    pub added: Vec<(K, V)>,
+    /// The keys that only have a non-default value in the old map, together with that value.
+    pub removed: Vec<(K, V)>,
+    /// The keys that have different non-default values in both maps, together with the old and
+    /// the new value.
+    pub changed: Vec<(K, V, V)>,
+}
+
+impl<K, V> MapDiff<K, V> {
+    /// Returns `true` if there are no changes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl<K, V> Default for MapDiff<K, V> {
+    fn default() -> Self {
+        MapDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
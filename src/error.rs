@@ -0,0 +1,26 @@
+use std::error::Error;
+use std::fmt;
+
+/// The error returned by `try_insert` when the key already has a value stored in the map.
+///
+/// It contains the occupied entry of the map, so the existing value can still be inspected or
+/// modified, and the value that was not inserted.
+#[derive(Debug)]
+pub struct OccupiedError<E, V> {
+    /// The entry in the map that was already occupied.
+    pub entry: E,
+    /// The value which was not inserted, because the entry was already occupied.
+    pub value: V,
+}
+
+impl<E: fmt::Debug, V: fmt::Debug> fmt::Display for OccupiedError<E, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, key already exists: {:?}",
+            self.value, self.entry,
+        )
+    }
+}
+
+impl<E: fmt::Debug, V: fmt::Debug> Error for OccupiedError<E, V> {}
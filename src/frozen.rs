@@ -0,0 +1,125 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::{Iter, Keys, RandomState, Values};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Index;
+
+use crate::DefaultHashMap;
+
+/// A read-only version of a `DefaultHashMap`, which is created by
+/// [`DefaultHashMap::freeze`]. Missing keys still return the default, but nothing can be
+/// inserted anymore.
+///
+/// Only the default value is kept, not the default value generation function. So, unlike a
+/// `DefaultHashMap`, a `FrozenDefaultMap` is `Send` and `Sync` whenever its keys, values and
+/// hasher are, which makes it easy to share it between threads after building it.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+/// counts["a"] += 1;
+/// let counts = std::sync::Arc::new(counts.freeze());
+///
+/// let reader = {
+///     let counts = counts.clone();
+///     std::thread::spawn(move || counts["a"] + counts["b"])
+/// };
+/// assert_eq!(reader.join().unwrap(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FrozenDefaultMap<K: Eq + Hash, V, S = RandomState> {
+    pub(crate) map: HashMap<K, V, S>,
+    pub(crate) default: V,
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> FrozenDefaultMap<K, V, S> {
+    /// Returns a reference to the value stored for the provided key.
+    /// If the key is not in the map a reference to the default value is returned.
+    pub fn get<Q, QB: Borrow<Q>>(&self, key: QB) -> &V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.get(key.borrow()).unwrap_or(&self.default)
+    }
+
+    /// Returns a reference to the default value.
+    pub fn default_value(&self) -> &V {
+        &self.default
+    }
+
+    /// Returns a reference to the underlying `HashMap`.
+    pub fn as_map(&self) -> &HashMap<K, V, S> {
+        &self.map
+    }
+
+    /// Converts the map back into a `DefaultHashMap` that uses the default value as its default.
+    pub fn thaw(self) -> DefaultHashMap<K, V, S>
+    where
+        V: Clone + 'static,
+    {
+        DefaultHashMap::from_map_with_default(self.map, self.default)
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.contains_key(k)
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        self.map.keys()
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        self.map.values()
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.map.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Implements the `Index` trait so you can do `map[key]`.
+impl<K: Eq + Hash, KB: Borrow<K>, V, S: BuildHasher> Index<KB> for FrozenDefaultMap<K, V, S> {
+    type Output = V;
+
+    fn index(&self, index: KB) -> &V {
+        self.get(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FrozenDefaultMap;
+    use crate::DefaultHashMap;
+
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    #[test]
+    fn freeze() {
+        let mut map: DefaultHashMap<i32, String> = DefaultHashMap::with_default("-".to_string());
+        map[1].push('a');
+        let frozen: FrozenDefaultMap<i32, String> = map.freeze();
+        assert_send_sync(&frozen);
+        assert_eq!(frozen[1], "-a");
+        assert_eq!(frozen[2], "-");
+        assert_eq!(frozen.default_value(), "-");
+        assert_eq!(frozen.len(), 1);
+        assert!(!frozen.contains_key(&2));
+
+        let mut thawed = frozen.thaw();
+        thawed[2].push('b');
+        assert_eq!(thawed[2], "-b");
+    }
+}
@@ -0,0 +1,251 @@
+use std::fmt;
+use std::ops::{Index, IndexMut, RangeInclusive};
+
+use crate::DefaultHashMap;
+
+/// The offsets of the neighbors that share an edge with a cell, in row-major order.
+const NEIGHBORS4: [(i64, i64); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// The offsets of all neighbors of a cell, in row-major order.
+const NEIGHBORS8: [(i64, i64); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A sparse two dimensional grid where every cell that was never set contains the default.
+///
+/// The cells are stored in a `DefaultHashMap` with `(x, y)` keys, so the grid has no fixed size
+/// and coordinates can be negative. `y` grows downwards, so iterating over a region goes row by
+/// row from the top left.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut grid: DefaultGrid2D<char> = DefaultGrid2D::with_default('.');
+/// grid[(0, 0)] = '#';
+/// grid[(2, 1)] = '#';
+///
+/// assert_eq!(grid.get(1, 1), &'.');
+/// let walls = grid.neighbors8(1, 0).filter(|(_, &cell)| cell == '#').count();
+/// assert_eq!(walls, 2);
+/// assert_eq!(grid.bounds(), Some(((0, 0), (2, 1))));
+///
+/// let rows: Vec<String> = grid.rows().map(|row| row.map(|(_, c)| c).collect()).collect();
+/// assert_eq!(rows, ["#..", "..#"]);
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct DefaultGrid2D<V> {
+    cells: DefaultHashMap<(i64, i64), V>,
+}
+
+impl<V: Default> DefaultGrid2D<V> {
+    /// Creates an empty grid with the default of `V` in every cell.
+    pub fn new() -> DefaultGrid2D<V> {
+        DefaultGrid2D {
+            cells: DefaultHashMap::new(),
+        }
+    }
+}
+
+impl<V: Default> Default for DefaultGrid2D<V> {
+    /// The `default()` method is equivalent to `DefaultGrid2D::new()`.
+    fn default() -> DefaultGrid2D<V> {
+        DefaultGrid2D::new()
+    }
+}
+
+impl<V: Clone> DefaultGrid2D<V> {
+    /// Creates an empty grid with `default` in every cell.
+    pub fn with_default(default: V) -> DefaultGrid2D<V> {
+        DefaultGrid2D {
+            cells: DefaultHashMap::with_default(default),
+        }
+    }
+}
+
+impl<V> From<DefaultHashMap<(i64, i64), V>> for DefaultGrid2D<V> {
+    /// Creates a grid from a map with `(x, y)` keys, using the default of the map as the default
+    /// of the grid.
+    fn from(cells: DefaultHashMap<(i64, i64), V>) -> DefaultGrid2D<V> {
+        DefaultGrid2D { cells }
+    }
+}
+
+impl<V> DefaultGrid2D<V> {
+    /// Returns a reference to the value of the cell, or to the default if the cell was never set.
+    pub fn get(&self, x: i64, y: i64) -> &V {
+        self.cells.get((x, y))
+    }
+
+    /// Returns a mutable reference to the value of the cell, inserting the default first if the
+    /// cell was never set.
+    pub fn get_mut(&mut self, x: i64, y: i64) -> &mut V {
+        self.cells.get_mut((x, y))
+    }
+
+    /// Sets the value of the cell and returns its previous value, if it was set.
+    pub fn insert(&mut self, x: i64, y: i64, value: V) -> Option<V> {
+        self.cells.insert((x, y), value)
+    }
+
+    /// Removes the value of the cell, so that it contains the default again. Returns the value
+    /// if the cell was set.
+    pub fn remove(&mut self, x: i64, y: i64) -> Option<V> {
+        self.cells.remove(&(x, y))
+    }
+
+    /// Returns `true` if a value is stored for the cell.
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        self.cells.contains_key(&(x, y))
+    }
+
+    /// Returns the number of cells that are stored.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns `true` if no cells are stored.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns a reference to the value that cells contain when they were never set.
+    pub fn default_value(&self) -> &V {
+        self.cells.default_value()
+    }
+
+    /// Returns an iterator over the stored cells and their values, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), &V)> {
+        self.cells
+            .iter()
+            .map(|(&position, value)| (position, value))
+    }
+
+    /// Returns an iterator over the up to four cells that share an edge with the cell, with the
+    /// default for cells that were never set. Cells outside of the range of `i64` are skipped.
+    pub fn neighbors4(&self, x: i64, y: i64) -> impl Iterator<Item = ((i64, i64), &V)> {
+        self.offsets(x, y, &NEIGHBORS4)
+    }
+
+    /// Returns an iterator over the up to eight cells that share an edge or a corner with the
+    /// cell, see `neighbors4`.
+    pub fn neighbors8(&self, x: i64, y: i64) -> impl Iterator<Item = ((i64, i64), &V)> {
+        self.offsets(x, y, &NEIGHBORS8)
+    }
+
+    fn offsets<'a>(
+        &'a self,
+        x: i64,
+        y: i64,
+        offsets: &'static [(i64, i64)],
+    ) -> impl Iterator<Item = ((i64, i64), &'a V)> {
+        offsets.iter().filter_map(move |&(dx, dy)| {
+            let position = (x.checked_add(dx)?, y.checked_add(dy)?);
+            Some((position, self.cells.get(position)))
+        })
+    }
+
+    /// Returns the top left and the bottom right corner of the smallest rectangle that contains
+    /// all stored cells, or `None` if no cells are stored.
+    pub fn bounds(&self) -> Option<((i64, i64), (i64, i64))> {
+        let mut positions = self.cells.keys();
+        let &(x, y) = positions.next()?;
+        let bounds = positions.fold(((x, y), (x, y)), |((x0, y0), (x1, y1)), &(x, y)| {
+            ((x0.min(x), y0.min(y)), (x1.max(x), y1.max(y)))
+        });
+        Some(bounds)
+    }
+
+    /// Returns an iterator over all cells in the rectangle, row by row, with the default for
+    /// cells that were never set.
+    pub fn region(
+        &self,
+        xs: RangeInclusive<i64>,
+        ys: RangeInclusive<i64>,
+    ) -> impl Iterator<Item = ((i64, i64), &V)> {
+        ys.flat_map(move |y| xs.clone().map(move |x| ((x, y), self.get(x, y))))
+    }
+
+    /// Returns an iterator over the rows of the rectangle from `bounds`, where every row is an
+    /// iterator over its cells. This makes it easy to print the grid.
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = ((i64, i64), &V)> + '_> + '_ {
+        let ((x0, y0), (x1, y1)) = self.bounds().unwrap_or(((0, 0), (-1, -1)));
+        (y0..=y1).map(move |y| (x0..=x1).map(move |x| ((x, y), self.get(x, y))))
+    }
+
+    /// Returns the map that stores the cells.
+    pub fn into_map(self) -> DefaultHashMap<(i64, i64), V> {
+        self.cells
+    }
+}
+
+/// Implements the `Index` trait so you can do `grid[(x, y)]`.
+impl<V> Index<(i64, i64)> for DefaultGrid2D<V> {
+    type Output = V;
+
+    fn index(&self, (x, y): (i64, i64)) -> &V {
+        self.get(x, y)
+    }
+}
+
+/// Implements the `IndexMut` trait so you can do `grid[(x, y)] = val`.
+impl<V> IndexMut<(i64, i64)> for DefaultGrid2D<V> {
+    fn index_mut(&mut self, (x, y): (i64, i64)) -> &mut V {
+        self.get_mut(x, y)
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for DefaultGrid2D<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DefaultGrid2D")
+            .field("cells", &self.cells.sorted_debug())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DefaultGrid2D;
+
+    #[test]
+    fn neighbors_and_bounds() {
+        let mut grid: DefaultGrid2D<u8> = DefaultGrid2D::new();
+        assert_eq!(grid.bounds(), None);
+        assert_eq!(grid.rows().count(), 0);
+        grid[(-1, 2)] = 1;
+        grid.insert(3, -4, 2);
+        *grid.get_mut(0, 0) += 3;
+        assert_eq!(grid.bounds(), Some(((-1, -4), (3, 2))));
+
+        let neighbors: Vec<_> = grid.neighbors4(0, 1).collect();
+        assert_eq!(
+            neighbors,
+            [((0, 0), &3), ((-1, 1), &0), ((1, 1), &0), ((0, 2), &0)]
+        );
+        assert_eq!(grid.neighbors8(0, 1).map(|(_, v)| v).sum::<u8>(), 4);
+        assert_eq!(grid.neighbors8(i64::MAX, 0).count(), 5);
+
+        let region: Vec<_> = grid.region(-1..=0, 1..=2).map(|(_, v)| *v).collect();
+        assert_eq!(region, [0, 0, 1, 0]);
+        assert_eq!(grid.rows().count(), 7);
+        assert_eq!(grid.remove(0, 0), Some(3));
+        assert!(!grid.contains(0, 0));
+        assert_eq!(grid.len(), 2);
+    }
+
+    #[test]
+    fn debug() {
+        let mut grid: DefaultGrid2D<char> = DefaultGrid2D::with_default('.');
+        grid[(1, 0)] = '#';
+        grid[(0, 1)] = '#';
+        assert_eq!(
+            format!("{:?}", grid),
+            "DefaultGrid2D { cells: DefaultHashMap { map: {(0, 1): '#', (1, 0): '#'}, default: '.' } }"
+        );
+    }
+}
@@ -1,199 +1,2097 @@
-use derive_more::Debug;
+//! A `HashMap` that returns a default for missing keys, and the types used by its methods.
+
 use std::borrow::Borrow;
 use std::collections::hash_map::*;
 use std::collections::HashMap;
 use std::collections::TryReserveError;
-use std::hash::Hash;
-use std::iter::{FromIterator, IntoIterator};
-use std::ops::{Index, IndexMut};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::iter::{Cloned, FromIterator, IntoIterator, Sum};
+use std::ops::{
+    Add, AddAssign, BitAnd, BitAndAssign, BitOr, BitOrAssign, Deref, DerefMut, Index, IndexMut,
+    Sub, SubAssign,
+};
+#[cfg(target_has_atomic = "64")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::default_fn::{LazyDefault, NamedFn};
+use crate::missing::Missing;
+use crate::redacted::MaskedEntries;
+use crate::{
+    AsF64, CompositeKey, DefaultBTreeMap, DefaultFn, DefaultMap, FrozenDefaultMap, MapDiff,
+    OccupiedError, One, Redacted, TryDefaultFn, ValueStats,
+};
+
+/// A `HashMap` that returns a default when keys are accessed that are not present.
+#[derive(Clone)]
+#[cfg_attr(feature = "with-serde", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "with-serde",
+    serde(bound(serialize = "K: serde::Serialize, V: serde::Serialize"))
+)]
+pub struct DefaultHashMap<K: Eq + Hash, V, S = RandomState> {
+    map: HashMap<K, V, S>,
+    default: LazyDefault<V>,
+    #[cfg_attr(feature = "with-serde", serde(skip))]
+    missing: Missing<K>,
+}
+
+impl<K: Eq + Hash, V: PartialEq, S: BuildHasher> PartialEq for DefaultHashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map && self.default.get() == other.default.get()
+    }
+}
+
+impl<K: Eq + Hash, V: Eq, S: BuildHasher> Eq for DefaultHashMap<K, V, S> {}
+
+/// The `Debug` output contains the stored entries and the default. When the map was created with
+/// a named default value generation function, its name is included too.
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug, S> fmt::Debug for DefaultHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("DefaultHashMap");
+        debug
+            .field("map", &self.map)
+            .field("default", self.default.get());
+        if let Some(name) = self.default.name() {
+            debug.field("default_fn", &name);
+        }
+        debug.finish()
+    }
+}
+
+impl<K: Eq + Hash, V: Default> DefaultHashMap<K, V> {
+    /// The `new()` constructor creates an empty DefaultHashMap with the default of `V`
+    /// as the default for missing keys.
+    /// This is desired default for most use cases, if your case requires a
+    /// different default you should use the `with_default()` constructor.
+    pub fn new() -> DefaultHashMap<K, V> {
+        DefaultHashMap {
+            map: HashMap::default(),
+            default: LazyDefault::std_default(),
+            missing: Missing::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Default, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Creates an empty `DefaultHashMap` which will use the given hash builder to hash keys,
+    /// with the default of `V` as the default for missing keys.
+    pub fn with_hasher(hash_builder: S) -> DefaultHashMap<K, V, S> {
+        DefaultHashMap {
+            map: HashMap::with_hasher(hash_builder),
+            default: LazyDefault::std_default(),
+            missing: Missing::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Default, S: BuildHasher + Default> Default for DefaultHashMap<K, V, S> {
+    /// The `default()` method is equivalent to `DefaultHashMap::new()`.
+    fn default() -> DefaultHashMap<K, V, S> {
+        DefaultHashMap::with_hasher(S::default())
+    }
+}
+
+impl<K: Eq + Hash, V: Default, S: BuildHasher> From<HashMap<K, V, S>> for DefaultHashMap<K, V, S> {
+    /// If you already have a `HashMap` that you would like to convert to a
+    /// `DefaultHashMap` you can use the `into()` method on the `HashMap` or the
+    /// `from()` constructor of `DefaultHashMap`.
+    /// The default value for missing keys will be `V::default()`,
+    /// if this is not desired `DefaultHashMap::from_map_with_default()` should be used.
+    fn from(map: HashMap<K, V, S>) -> DefaultHashMap<K, V, S> {
+        DefaultHashMap {
+            map,
+            default: LazyDefault::std_default(),
+            missing: Missing::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Default, const N: usize> From<[(K, V); N]> for DefaultHashMap<K, V> {
+    /// Creates a `DefaultHashMap` from an array of key-value pairs, like `HashMap::from` does.
+    /// The default value for missing keys will be `V::default()`,
+    /// if this is not desired `DefaultHashMap::from_array_with_default()` should be used.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let map = DefaultHashMap::from([("a", 1), ("b", 2)]);
+    /// assert_eq!(map["b"], 2);
+    /// assert_eq!(map["c"], 0);
+    /// ```
+    fn from(entries: [(K, V); N]) -> DefaultHashMap<K, V> {
+        DefaultHashMap::from(HashMap::from(entries))
+    }
+}
+
+impl<K: Eq + Hash, V, S> From<DefaultHashMap<K, V, S>> for HashMap<K, V, S> {
+    /// The into method can be used to convert a `DefaultHashMap` back into a
+    /// `HashMap`.
+    fn from(default_map: DefaultHashMap<K, V, S>) -> HashMap<K, V, S> {
+        default_map.map
+    }
+}
+
+impl<K: Eq + Hash + Ord, V> From<DefaultBTreeMap<K, V>> for DefaultHashMap<K, V> {
+    /// Converts a `DefaultBTreeMap` into a `DefaultHashMap` with the same entries and the same
+    /// default for missing keys.
+    fn from(default_map: DefaultBTreeMap<K, V>) -> DefaultHashMap<K, V> {
+        let (map, default, missing) = default_map.into_parts();
+        DefaultHashMap {
+            map: map.into_iter().collect(),
+            default,
+            missing,
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, S> DefaultHashMap<K, V, S> {
+    pub(crate) fn into_parts(self) -> (HashMap<K, V, S>, LazyDefault<V>, Missing<K>) {
+        (self.map, self.default, self.missing)
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> DefaultHashMap<K, V> {
+    /// Creates an empty `DefaultHashMap` with `default` as the default for missing keys.
+    /// When the provided `default` is equivalent to `V::default()` it is preferred to use
+    /// `DefaultHashMap::default()` instead.
+    pub fn with_default(default: V) -> DefaultHashMap<K, V> {
+        DefaultHashMap {
+            map: HashMap::new(),
+            default: LazyDefault::value(default),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Creates a `DefaultHashMap` from an array of key-value pairs, with `default` as the default
+    /// for missing keys.
+    pub fn from_array_with_default<const N: usize>(
+        entries: [(K, V); N],
+        default: V,
+    ) -> DefaultHashMap<K, V> {
+        DefaultHashMap::from_map_with_default(HashMap::from(entries), default)
+    }
+
+    /// Creates a `DefaultHashMap` that contains a clone of `default` for every key, with `default` as
+    /// the default for missing keys too. Like Python's `dict.fromkeys`, this makes sure that the
+    /// keys are present when iterating over the map.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let totals: DefaultHashMap<&str, u32> = DefaultHashMap::from_keys(["food", "rent"], 0);
+    /// assert_eq!(totals.len(), 2);
+    /// assert_eq!(totals["food"], 0);
+    /// ```
+    pub fn from_keys(keys: impl IntoIterator<Item = K>, default: V) -> DefaultHashMap<K, V> {
+        let map = keys.into_iter().map(|key| (key, default.clone())).collect();
+        DefaultHashMap::from_map_with_default(map, default)
+    }
+}
+
+impl<K: Eq + Hash, V: Clone, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Creates a `DefaultHashMap` based on a default and an already existing `HashMap`.
+    /// If `V::default()` is the supplied default, usage of the `from()` constructor or the
+    /// `into()` method on the original `HashMap` is preferred.
+    pub fn from_map_with_default(map: HashMap<K, V, S>, default: V) -> DefaultHashMap<K, V, S> {
+        DefaultHashMap {
+            map,
+            default: LazyDefault::value(default),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Changes the default value permanently or until `set_default()` is called again.
+    pub fn set_default(&mut self, new_default: V) {
+        self.default = LazyDefault::value(new_default);
+    }
+
+    /// Creates a clone of the stored entries that uses `new_default` as the default for missing
+    /// keys.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, i32> = DefaultHashMap::new();
+    /// counts["a"] += 1;
+    /// let marked = counts.clone_with_default(-1);
+    /// assert_eq!(marked["a"], 1);
+    /// assert_eq!(marked["b"], -1);
+    /// assert_eq!(counts["b"], 0);
+    /// ```
+    pub fn clone_with_default(&self, new_default: V) -> DefaultHashMap<K, V, S>
+    where
+        K: Clone,
+        S: Clone,
+    {
+        DefaultHashMap::from_map_with_default(self.map.clone(), new_default)
+    }
+
+    /// Calls `f` with the map while `temp_default` is used as its default, and restores the
+    /// previous default afterwards. Values that were inserted by `f` keep the temporary default.
+    /// The previous default is also restored if `f` panics.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut limits: DefaultHashMap<&str, u32> = DefaultHashMap::with_default(10);
+    /// limits.with_scoped_default(100, |limits| {
+    ///     limits.get_mut("upload");
+    ///     assert_eq!(limits["download"], 100);
+    /// });
+    /// assert_eq!(limits["upload"], 100);
+    /// assert_eq!(limits["download"], 10);
+    /// ```
+    pub fn with_scoped_default<R>(&mut self, temp_default: V, f: impl FnOnce(&mut Self) -> R) -> R {
+        // Swaps the previous default back in when dropped, which also happens during unwinding.
+        struct Restore<'a, K: Eq + Hash, V, S> {
+            map: &'a mut DefaultHashMap<K, V, S>,
+            old_default: LazyDefault<V>,
+        }
+
+        impl<K: Eq + Hash, V, S> Drop for Restore<'_, K, V, S> {
+            fn drop(&mut self) {
+                std::mem::swap(&mut self.map.default, &mut self.old_default);
+            }
+        }
+
+        let old_default = std::mem::replace(&mut self.default, LazyDefault::value(temp_default));
+        let guard = Restore {
+            map: self,
+            old_default,
+        };
+        f(guard.map)
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Returns a reference to the value stored for the provided key.
+    /// If the key is not in the `DefaultHashMap` a reference to the default value is returned.
+    /// Usually the `map[key]` method of retrieving keys is preferred over using `get` directly.
+    /// This method accepts both references and owned values as the key.
+    pub fn get<Q, QB: Borrow<Q>>(&self, key: QB) -> &V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        match self.map.get(key.borrow()) {
+            Some(value) => value,
+            None => {
+                self.missing.record_miss(None);
+                self.default.get()
+            }
+        }
+    }
+
+    /// Returns a reference to the value stored for the provided key, or `fallback` if the key is
+    /// not in the map. This is for a single lookup that needs a different value for missing keys
+    /// than the default of the map, which is left alone.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut limits: DefaultHashMap<&str, u32> = DefaultHashMap::with_default(10);
+    /// limits["upload"] = 5;
+    /// assert_eq!(limits.get_or("upload", &0), &5);
+    /// assert_eq!(limits.get_or("download", &0), &0);
+    /// assert_eq!(limits["download"], 10);
+    /// ```
+    pub fn get_or<'a, Q>(&'a self, key: &Q, fallback: &'a V) -> &'a V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.get(key).unwrap_or(fallback)
+    }
+
+    /// Returns an iterator over the provided keys together with their values. Keys that are not
+    /// in the map get a reference to the default value, without inserting anything. This is
+    /// useful when a fixed set of keys should always be shown, even if there's no data for them.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// counts["error"] += 2;
+    /// let rows: Vec<_> = counts.iter_with_defaults(["info", "error"]).collect();
+    /// assert_eq!(rows, vec![("info", &0), ("error", &2)]);
+    /// assert_eq!(counts.len(), 1);
+    /// ```
+    pub fn iter_with_defaults<'a, I>(&'a self, keys: I) -> impl Iterator<Item = (I::Item, &'a V)>
+    where
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: Borrow<K>,
+    {
+        keys.into_iter().map(move |key| {
+            let value = self.map.get(key.borrow()).unwrap_or(self.default.get());
+            (key, value)
+        })
+    }
+
+    /// Returns the an owned version of the default value
+    /// ```
+    /// use defaultmap::DefaultHashMap;
+    /// assert_eq!(DefaultHashMap::<String, i32>::new().get_default(), 0);
+    /// ```
+    pub fn get_default(&self) -> V {
+        self.default.create()
+    }
+
+    /// Returns a reference to the default value, without creating a new one like `get_default`
+    /// does.
+    /// ```
+    /// use defaultmap::DefaultHashMap;
+    /// assert_eq!(DefaultHashMap::<String, i32>::with_default(3).default_value(), &3);
+    /// ```
+    pub fn default_value(&self) -> &V {
+        self.default.get()
+    }
+
+    /// Returns how often `get` or indexing returned the default because the key was missing.
+    /// This is only available with the `stats` feature.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut map: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// map["a"] += 1;
+    /// let _ = map["a"] + map["b"] + map["c"];
+    /// assert_eq!(map.miss_count(), 2);
+    /// assert_eq!(map.materialized_keys(), 1);
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn miss_count(&self) -> usize {
+        self.missing.stats.misses()
+    }
+
+    /// Returns how often the default was inserted for a missing key by `get_mut`, mutable
+    /// indexing or one of the methods that build on them. This is only available with the
+    /// `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn materialized_keys(&self) -> usize {
+        self.missing.stats.materialized()
+    }
+
+    /// Resets the counters of `miss_count` and `materialized_keys` to zero. This is only
+    /// available with the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&self) {
+        self.missing.stats.reset();
+    }
+
+    /// Converts the map into a read-only [`FrozenDefaultMap`], which can be shared between
+    /// threads.
+    pub fn freeze(self) -> FrozenDefaultMap<K, V, S> {
+        FrozenDefaultMap {
+            map: self.map,
+            default: self.default.into_value(),
+        }
+    }
+
+    /// Changes the default value generation function permanently or until `set_default()` or
+    /// `set_default_fn()` is called again. Unlike `set_default` this doesn't require the default
+    /// to implement `Clone`. Values that are already stored are not changed.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut map: DefaultHashMap<i32, Vec<i32>> = DefaultHashMap::new();
+    /// map.set_default_fn(|| Vec::with_capacity(16));
+    /// assert!(map.get_mut(1).capacity() >= 16);
+    /// ```
+    pub fn set_default_fn(&mut self, default_fn: impl DefaultFn<V> + 'static) {
+        self.default = LazyDefault::with_fn(default_fn);
+    }
+
+    /// Sets a function that is called with the key whenever the default is used for a missing
+    /// key, e.g. to log lookups of unknown keys. It's called when indexing returns the default,
+    /// and when the default is inserted by `get_mut`, mutable indexing or one of the methods that
+    /// build on them. It's not called by `get`, because that only gets a borrowed form of the
+    /// key.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    ///
+    /// let unknown = Rc::new(RefCell::new(Vec::new()));
+    /// let mut config: DefaultHashMap<&str, u32> = DefaultHashMap::with_default(30);
+    /// config["timeout"] = 10;
+    /// let log = unknown.clone();
+    /// config.set_missing_hook(move |key| log.borrow_mut().push(*key));
+    ///
+    /// assert_eq!(config["timeout"], 10);
+    /// assert_eq!(config["timeuot"], 30);
+    /// assert_eq!(*unknown.borrow(), vec!["timeuot"]);
+    /// ```
+    pub fn set_missing_hook(&mut self, hook: impl Fn(&K) + 'static) {
+        self.missing.set_hook(Some(Arc::new(hook)));
+    }
+
+    /// Removes the function that was set with `set_missing_hook`.
+    pub fn clear_missing_hook(&mut self) {
+        self.missing.set_hook(None);
+    }
+
+    /// Creates an empty `DefaultHashMap` with the same default, hasher and capacity as this one.
+    /// This is useful for creating scratch maps repeatedly, without having to specify the
+    /// configuration again or having the map grow from zero capacity every time.
+    pub fn clone_empty(&self) -> DefaultHashMap<K, V, S>
+    where
+        S: Clone,
+    {
+        DefaultHashMap {
+            map: HashMap::with_capacity_and_hasher(self.map.capacity(), self.map.hasher().clone()),
+            default: self.default.fresh(),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Creates a `DefaultHashMap` based on an existing map and using `default_fn` as the default
+    /// value generation function for missing keys. When the provided `default_fn` is equivalent to
+    /// V::default(), then using `DefaultHashMap::from(map)` is preferred.
+    pub fn from_map_with_fn(
+        map: HashMap<K, V, S>,
+        default_fn: impl DefaultFn<V> + 'static,
+    ) -> DefaultHashMap<K, V, S> {
+        DefaultHashMap {
+            map,
+            default: LazyDefault::with_fn(default_fn),
+            missing: Missing::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
+    /// Creates an empty `DefaultHashMap` with `default_fn` as the default value generation
+    /// function for missing keys. When the provided `default_fn` only calls clone on a value,
+    /// using `DefaultHashMap::new` is preferred. The function isn't called until the default is first
+    /// needed.
+    pub fn with_fn(default_fn: impl DefaultFn<V> + 'static) -> DefaultHashMap<K, V> {
+        DefaultHashMap {
+            map: HashMap::new(),
+            default: LazyDefault::with_fn(default_fn),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Creates an empty `DefaultHashMap` like `with_fn` does, but gives the function a name. The
+    /// name is shown in the `Debug` output of the map, which otherwise doesn't show anything
+    /// about the default value generation function.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let map: DefaultHashMap<i32, Vec<i32>> = DefaultHashMap::with_named_fn("empty vec", Vec::new);
+    /// assert_eq!(
+    ///     format!("{:?}", map),
+    ///     r#"DefaultHashMap { map: {}, default: [], default_fn: "empty vec" }"#
+    /// );
+    /// ```
+    pub fn with_named_fn(
+        name: &'static str,
+        default_fn: impl DefaultFn<V> + Clone + 'static,
+    ) -> DefaultHashMap<K, V> {
+        DefaultHashMap::with_fn(NamedFn {
+            name,
+            f: default_fn,
+        })
+    }
+
+    /// Creates a `DefaultHashMap` that contains a value created by `default_fn` for every key, with
+    /// `default_fn` as the default value generation function for missing keys too, see
+    /// `from_keys`.
+    pub fn from_keys_with_fn(
+        keys: impl IntoIterator<Item = K>,
+        default_fn: impl DefaultFn<V> + 'static,
+    ) -> DefaultHashMap<K, V> {
+        let default = LazyDefault::with_fn(default_fn);
+        DefaultHashMap {
+            map: keys
+                .into_iter()
+                .map(|key| (key, default.create()))
+                .collect(),
+            default,
+            missing: Missing::default(),
+        }
+    }
+}
+
+/// Helpers for maps of atomic counters, whose stored counters can be changed through a shared
+/// reference, e.g. while other code holds references into the map. The map isn't `Sync`, because
+/// the default value generation function isn't required to be, so these are single threaded
+/// interior mutability helpers and not a way to share counters between threads. `new()` creates
+/// such a map with an atomic zero as the default. All operations use `Ordering::Relaxed`.
+///
+/// ```
+/// # use defaultmap::*;
+/// use std::sync::atomic::AtomicU64;
+///
+/// let mut hits: DefaultHashMap<&str, AtomicU64> = DefaultHashMap::new();
+/// hits.fetch_add_or_insert("/", 1);
+/// let shared = &hits;
+/// assert_eq!(shared.fetch_add("/", 2), Some(1));
+/// assert_eq!(shared.fetch_add("/about", 1), None);
+/// assert_eq!(shared.load("/"), 3);
+/// assert_eq!(shared.load("/about"), 0);
+/// ```
+#[cfg(target_has_atomic = "64")]
+impl<K: Eq + Hash, S: BuildHasher> DefaultHashMap<K, AtomicU64, S> {
+    /// Adds `n` to the counter of the key and returns its previous value. Inserting a counter
+    /// needs a mutable reference, so keys that have no counter stored yet are left alone and
+    /// `None` is returned. Use `fetch_add_or_insert` for those.
+    pub fn fetch_add<Q>(&self, key: &Q, n: u64) -> Option<u64>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        Some(self.map.get(key)?.fetch_add(n, Ordering::Relaxed))
+    }
+
+    /// Adds `n` to the counter of the key, inserting the default counter first if there is none,
+    /// and returns its previous value.
+    pub fn fetch_add_or_insert(&mut self, key: K, n: u64) -> u64 {
+        self.get_mut(key).fetch_add(n, Ordering::Relaxed)
+    }
+
+    /// Returns the value of the counter of the key, or of the default counter if there is none.
+    pub fn load<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).load(Ordering::Relaxed)
+    }
+}
+
+impl<K: Eq + Hash, S: BuildHasher> DefaultHashMap<K, f64, S> {
+    /// Divides every value by the sum of all values in place, see `normalized`. The default
+    /// isn't changed. When the sum is zero the values become NaN.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut weights: DefaultHashMap<&str, f64> = DefaultHashMap::new();
+    /// weights["a"] = 1.0;
+    /// weights["b"] = 3.0;
+    /// weights.normalize();
+    /// assert_eq!(weights["a"], 0.25);
+    /// assert_eq!(weights.total(), 1.0);
+    /// ```
+    pub fn normalize(&mut self) {
+        let total: f64 = self.map.values().sum();
+        for value in self.map.values_mut() {
+            *value /= total;
+        }
+    }
+}
+
+impl<K: Eq + Hash> DefaultHashMap<K, usize> {
+    /// Creates a map that counts how often every key occurs in `iter`. Capacity is reserved up
+    /// front based on the size hint of the iterator.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let counts = DefaultHashMap::count_occurrences([1, 4, 3, 3, 4, 2, 4]);
+    /// assert_eq!(counts[4], 3);
+    /// assert_eq!(counts[3], 2);
+    /// assert_eq!(counts[5], 0);
+    /// ```
+    pub fn count_occurrences<I>(iter: I) -> DefaultHashMap<K, usize>
+    where
+        I: IntoIterator<Item = K>,
+    {
+        let iter = iter.into_iter();
+        let mut map: DefaultHashMap<K, usize> = DefaultHashMap::new();
+        map.reserve(iter.size_hint().0);
+        for key in iter {
+            map[key] += 1;
+        }
+        map
+    }
+}
+
+impl<K: Eq + Hash, T> DefaultHashMap<K, Vec<T>> {
+    /// Creates a map that groups the items of `iter` by the key that `f` returns for them. The
+    /// items of every group keep the order in which they were returned by `iter`.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let words = ["apple", "bob", "avocado", "cat"];
+    /// let by_letter = DefaultHashMap::group_by(words, |word| word.chars().next().unwrap());
+    /// assert_eq!(by_letter['a'], vec!["apple", "avocado"]);
+    /// assert_eq!(by_letter['b'], vec!["bob"]);
+    /// assert!(by_letter['z'].is_empty());
+    /// ```
+    pub fn group_by<I, F>(iter: I, mut f: F) -> DefaultHashMap<K, Vec<T>>
+    where
+        I: IntoIterator<Item = T>,
+        F: FnMut(&T) -> K,
+    {
+        let mut map: DefaultHashMap<K, Vec<T>> = DefaultHashMap::new();
+        for item in iter {
+            map[f(&item)].push(item);
+        }
+        map
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Returns a mutable reference to the value stored for the provided key.
+    /// If there is no value stored for the key the default value is first inserted for this
+    /// key before returning the reference.
+    /// Usually the `map[key] = new_val` is prefered over using `get_mut` directly.
+    /// This method only accepts owned values as the key.
+    pub fn get_mut(&mut self, key: K) -> &mut V {
+        let entry = self.map.entry(key);
+        match entry {
+            Entry::Occupied(occupied) => occupied.into_mut(),
+            Entry::Vacant(vacant) => {
+                self.missing.record_materialized(vacant.key());
+                vacant.insert(self.default.create())
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the value stored for the provided key, like `get_mut`.
+    /// If there is no value stored for the key, `default_fn` is used to create it instead of the
+    /// default of the map. When `default_fn` fails its error is returned and nothing is inserted.
+    /// [`TryDefaultMap`](crate::TryDefaultMap) stores such a function, so it doesn't have to be
+    /// passed on every call.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut templates: DefaultHashMap<&str, String> = DefaultHashMap::new();
+    /// fn load(name: &'static str) -> impl Fn() -> Result<String, String> {
+    ///     move || match name {
+    ///         "greeting" => Ok("Hello {name}!".to_string()),
+    ///         _ => Err(format!("no template named {name}")),
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(templates.try_get_mut("greeting", load("greeting")).unwrap(), "Hello {name}!");
+    /// assert!(templates.try_get_mut("farewell", load("farewell")).is_err());
+    /// assert_eq!(templates.len(), 1);
+    /// ```
+    pub fn try_get_mut<E>(
+        &mut self,
+        key: K,
+        default_fn: impl TryDefaultFn<V, E>,
+    ) -> Result<&mut V, E> {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => Ok(occupied.into_mut()),
+            Entry::Vacant(vacant) => {
+                let value = default_fn.try_call()?;
+                self.missing.record_materialized(vacant.key());
+                Ok(vacant.insert(value))
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the value stored for the provided key, like `get_mut`.
+    /// If there is no value stored for the key, it's created by `f` instead of using the default
+    /// of the map. Later lookups of other missing keys still get the default.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut sessions: DefaultHashMap<u32, Vec<&str>> = DefaultHashMap::new();
+    /// sessions.get_or_insert_with(1, || vec!["login"]).push("view");
+    /// sessions.get_or_insert_with(1, || vec!["login"]).push("logout");
+    /// assert_eq!(sessions[1], ["login", "view", "logout"]);
+    /// assert!(sessions[2].is_empty());
+    /// ```
+    pub fn get_or_insert_with(&mut self, key: K, f: impl FnOnce() -> V) -> &mut V {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => occupied.into_mut(),
+            Entry::Vacant(vacant) => {
+                self.missing.record_materialized(vacant.key());
+                vacant.insert(f())
+            }
+        }
+    }
+
+    /// Calls `f` with a mutable reference to the value of the key, inserting the default first
+    /// if there is no value stored yet, like `get_mut`. The map is returned so that calls can be
+    /// chained.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut stock: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// stock
+    ///     .modify("apples", |n| *n += 5)
+    ///     .modify("pears", |n| *n += 2)
+    ///     .modify("apples", |n| *n *= 2);
+    /// assert_eq!(stock["apples"], 10);
+    /// assert_eq!(stock["pears"], 2);
+    /// ```
+    pub fn modify(&mut self, key: K, f: impl FnOnce(&mut V)) -> &mut Self {
+        f(self.get_mut(key));
+        self
+    }
+
+    /// Calls `f` with the value of the key, or the default if there is no value stored, and
+    /// stores what `f` returns. When `f` returns `None` the key is removed instead. This is meant
+    /// for maps of reference counts, where a key should be freed when its count drops to zero.
+    /// While `f` runs the key holds a fresh default, so if `f` panics the key is kept with the
+    /// default as its value.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut refs: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// refs.update_or_remove("a", |n| Some(n + 1));
+    /// refs.update_or_remove("a", |n| n.checked_sub(1).filter(|&n| n > 0));
+    /// assert!(!refs.contains_key("a"));
+    /// ```
+    pub fn update_or_remove(&mut self, key: K, f: impl FnOnce(V) -> Option<V>) {
+        match self.map.entry(key) {
+            Entry::Occupied(mut occupied) => {
+                let value = std::mem::replace(occupied.get_mut(), self.default.create());
+                match f(value) {
+                    Some(value) => *occupied.get_mut() = value,
+                    None => {
+                        occupied.remove_entry();
+                    }
+                }
+            }
+            Entry::Vacant(vacant) => {
+                if let Some(value) = f(self.default.create()) {
+                    self.missing.record_materialized(vacant.key());
+                    vacant.insert(value);
+                }
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the value stored for the provided key. If there is no value
+    /// stored for the key, the future returned by `f` is awaited to create it instead of using
+    /// the default of the map. This allows using the map as a read-through cache, where missing
+    /// values are fetched over the network. Values that are already stored are returned without
+    /// waiting for anything. This is only available with the `async` feature.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// async fn fetch_user_name(id: u32) -> String {
+    ///     // Fetch the name over the network
+    ///     # format!("user {id}")
+    /// }
+    ///
+    /// async fn user_name(cache: &mut DefaultHashMap<u32, String>, id: u32) -> &str {
+    ///     cache.get_or_insert_async(id, |id| fetch_user_name(*id)).await
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    pub async fn get_or_insert_async<F, Fut>(&mut self, key: K, f: F) -> &mut V
+    where
+        F: FnOnce(&K) -> Fut,
+        Fut: std::future::Future<Output = V>,
+    {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => occupied.into_mut(),
+            Entry::Vacant(vacant) => {
+                let value = f(vacant.key()).await;
+                self.missing.record_materialized(vacant.key());
+                vacant.insert(value)
+            }
+        }
+    }
+
+    /// Returns mutable references to the values stored for several keys at once.
+    /// Keys that have no value stored get the default value inserted first, just like `get_mut`
+    /// does. Missing keys are converted to owned keys using `ToOwned`. The values are collected in
+    /// a single pass over the entries, so this takes time linear in the size of the map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any two of the keys refer to the same entry.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut balances: DefaultHashMap<&str, i32> = DefaultHashMap::new();
+    /// balances.insert("alice", 10);
+    /// let [alice, bob] = balances.get_disjoint_mut([&"alice", &"bob"]);
+    /// *alice -= 3;
+    /// *bob += 3;
+    /// assert_eq!(balances["alice"], 7);
+    /// assert_eq!(balances["bob"], 3);
+    /// ```
+    pub fn get_disjoint_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> [&mut V; N]
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + ToOwned<Owned = K>,
+    {
+        for key in keys {
+            if !self.map.contains_key(key) {
+                self.map.insert(key.to_owned(), self.default.create());
+            }
+        }
+        // All values are taken from a single pass over the entries, so no reference is held across
+        // a later lookup.
+        let entries = self.map.iter_mut();
+        let mut values: [Option<&mut V>; N] = std::array::from_fn(|_| None);
+        for (k, value) in entries {
+            let mut matches = keys
+                .iter()
+                .enumerate()
+                .filter(|(_, key)| k.borrow() == **key);
+            if let Some((i, _)) = matches.next() {
+                assert!(
+                    matches.next().is_none(),
+                    "duplicate keys passed to get_disjoint_mut"
+                );
+                values[i].get_or_insert(value);
+            }
+        }
+        values.map(|value| value.expect("key was just inserted"))
+    }
+
+    /// Converts the map into one with different keys, keeping the default for missing keys.
+    /// The new key of every entry is computed by `f`. When multiple entries end up with the same
+    /// new key, `merge` is called to fold the value of the later entry into the earlier one.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut hits: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// hits["example.com/a"] += 2;
+    /// hits["example.com/b"] += 3;
+    /// hits["example.org/"] += 1;
+    ///
+    /// let per_domain = hits.reindex_by(|url, _| url.split('/').next().unwrap(), |a, b| *a += b);
+    /// assert_eq!(per_domain["example.com"], 5);
+    /// assert_eq!(per_domain["example.org"], 1);
+    /// assert_eq!(per_domain["example.net"], 0);
+    /// ```
+    pub fn reindex_by<K2, F, M>(self, mut f: F, mut merge: M) -> DefaultHashMap<K2, V, S>
+    where
+        K2: Eq + Hash,
+        F: FnMut(&K, &V) -> K2,
+        M: FnMut(&mut V, V),
+        S: Clone,
+    {
+        let mut map = HashMap::with_capacity_and_hasher(self.map.len(), self.map.hasher().clone());
+        for (key, value) in self.map {
+            match map.entry(f(&key, &value)) {
+                Entry::Occupied(occupied) => merge(occupied.into_mut(), value),
+                Entry::Vacant(vacant) => {
+                    vacant.insert(value);
+                }
+            }
+        }
+        DefaultHashMap {
+            map,
+            default: self.default,
+            missing: Missing::default(),
+        }
+    }
+
+    /// Removes all entries for which `pred` returns `true` and returns them as an iterator.
+    /// The predicate also gets a mutable reference to the value, so entries that are kept can be
+    /// updated in the same pass.
+    ///
+    /// Unlike `HashMap::extract_if`, which needs a newer Rust version than this crate supports,
+    /// the entries are removed right away and not when the iterator is consumed. The keys of
+    /// matching entries are cloned and the entries are only removed after `pred` was called for
+    /// every entry, so if `pred` panics no entries are removed.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut sessions: DefaultHashMap<u32, Vec<&str>> = DefaultHashMap::new();
+    /// sessions[1].extend(["login", "logout"]);
+    /// sessions[2].push("login");
+    ///
+    /// let finished: Vec<_> = sessions
+    ///     .extract_if(|_, events| events.last() == Some(&"logout"))
+    ///     .collect();
+    /// assert_eq!(finished, vec![(1, vec!["login", "logout"])]);
+    /// assert_eq!(sessions.len(), 1);
+    /// ```
+    pub fn extract_if<F>(&mut self, mut pred: F) -> std::vec::IntoIter<(K, V)>
+    where
+        K: Clone,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let keys: Vec<K> = self
+            .map
+            .iter_mut()
+            .filter_map(|(key, value)| pred(key, value).then(|| key.clone()))
+            .collect();
+        let extracted: Vec<_> = keys
+            .iter()
+            .map(|key| self.map.remove_entry(key).expect("key was just found"))
+            .collect();
+        extracted.into_iter()
+    }
+
+    /// Splits the map into the entries for which `pred` returns `true` and the ones for which it
+    /// returns `false`. Both maps create their defaults in the same way as this map.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// counts["error"] = 120;
+    /// counts["warning"] = 3;
+    /// let (frequent, rare) = counts.partition(|_, &count| count >= 100);
+    /// assert_eq!(frequent.len(), 1);
+    /// assert_eq!(rare["warning"], 3);
+    /// assert_eq!(rare["error"], 0);
+    /// ```
+    pub fn partition<F>(self, mut pred: F) -> (Self, Self)
+    where
+        F: FnMut(&K, &V) -> bool,
+        S: Clone,
+    {
+        let (map, default, _) = self.into_parts();
+        let mut matching = DefaultHashMap {
+            map: HashMap::with_hasher(map.hasher().clone()),
+            default: default.fresh(),
+            missing: Missing::default(),
+        };
+        let mut rest = DefaultHashMap {
+            map: HashMap::with_hasher(map.hasher().clone()),
+            default,
+            missing: Missing::default(),
+        };
+        for (key, value) in map {
+            if pred(&key, &value) {
+                matching.map.insert(key, value);
+            } else {
+                rest.map.insert(key, value);
+            }
+        }
+        (matching, rest)
+    }
+
+    /// Retains only the entries for which `pred` returns `true` for their key. This is the same
+    /// as `retain`, but it doesn't borrow the values mutably when the predicate doesn't need
+    /// them.
+    pub fn retain_keys<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.map.retain(|key, _| pred(key))
+    }
+
+    /// Sets the value of the key back to a freshly generated default and returns the previous
+    /// value. Keys that have no value stored are left alone, because they already resolve to the
+    /// default. Use `remove` to get rid of the entry instead.
+    pub fn reset<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let value = self.map.get_mut(key)?;
+        Some(std::mem::replace(value, self.default.create()))
+    }
+
+    /// Sets the values of all keys back to a freshly generated default, while keeping the keys
+    /// in the map. This is useful to zero counters between measurement windows.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// counts["a"] += 3;
+    /// counts.reset_all();
+    /// assert_eq!(counts["a"], 0);
+    /// assert!(counts.contains_key("a"));
+    /// ```
+    pub fn reset_all(&mut self) {
+        for value in self.map.values_mut() {
+            *value = self.default.create();
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: PartialEq, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Checks the internal consistency of the map and panics if it's violated. This is only
+    /// available in debug builds, and is meant for wrappers and fuzzers that want to check the map
+    /// after unusual sequences of operations.
+    ///
+    /// Currently this checks that the stored default matches the values that are created for
+    /// missing keys, unless those are created by a default value generation function.
+    #[cfg(debug_assertions)]
+    pub fn assert_invariants(&self) {
+        assert!(
+            self.default.agrees_with_source(),
+            "the default value disagrees with the values created for missing keys"
+        );
+    }
+
+    /// Returns `true` if a value is stored for the key and that value is not equal to the default.
+    /// Unlike `contains_key` this ignores entries that only hold the default, e.g. because they
+    /// were created by mutably indexing the map without changing the value.
+    pub fn contains_non_default<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map
+            .get(key)
+            .is_some_and(|value| *value != *self.default.get())
+    }
+
+    /// Removes all entries whose value is equal to the default. Mutably indexing the map inserts
+    /// the default for missing keys, so this can be used to shrink a map again after many such
+    /// reads. Lookups behave exactly the same after pruning.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, i32> = DefaultHashMap::new();
+    /// counts["a"] += 1;
+    /// counts["b"] += 0;
+    /// assert_eq!(counts.len(), 2);
+    /// assert_eq!(counts.pruned_len(), 1);
+    /// counts.prune();
+    /// assert_eq!(counts.len(), 1);
+    /// assert_eq!(counts["b"], 0);
+    /// ```
+    pub fn prune(&mut self) {
+        let default = self.default.get();
+        self.map.retain(|_, value| value != default);
+    }
+
+    /// Returns the number of entries the map would have after calling `prune()`, i.e. the number
+    /// of entries whose value is not equal to the default.
+    pub fn pruned_len(&self) -> usize {
+        self.map
+            .values()
+            .filter(|value| **value != *self.default.get())
+            .count()
+    }
+
+    /// Returns `true` if looking up the key returns the default, either because no value is
+    /// stored for it or because the stored value is equal to the default.
+    pub fn is_default<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        !self.contains_non_default(key)
+    }
+
+    /// Returns `true` if both maps return the same value for every possible key. Unlike `==`,
+    /// this treats a key that is missing the same as a key that has the default stored.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut a: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+    /// let b: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+    /// a[1] += 0;
+    /// assert_ne!(a, b);
+    /// assert!(a.semantic_eq(&b));
+    /// ```
+    pub fn semantic_eq<S2: BuildHasher>(&self, other: &DefaultHashMap<K, V, S2>) -> bool {
+        self.default.get() == other.default.get()
+            && self.map.iter().all(|(key, value)| value == other.get(key))
+            && other.map.iter().all(|(key, value)| value == self.get(key))
+    }
+
+    /// Returns the changes that turn this map into `other`, see [`MapDiff`].
+    pub fn diff<S2: BuildHasher>(&self, other: &DefaultHashMap<K, V, S2>) -> MapDiff<K, V>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let mut diff = MapDiff::default();
+        for key in self.union_keys(other) {
+            let old = self.get(key);
+            let new = other.get(key);
+            if old == new {
+                continue;
+            }
+            if *old == *self.default.get() {
+                diff.added.push((key.clone(), new.clone()));
+            } else if *new == *other.default.get() {
+                diff.removed.push((key.clone(), old.clone()));
+            } else {
+                diff.changed.push((key.clone(), old.clone(), new.clone()));
+            }
+        }
+        diff
+    }
+
+    /// Applies the changes of `diff`, as returned by `diff`, to this map. Keys whose new value is
+    /// equal to the default of this map are removed instead of being stored. The old values in
+    /// the diff are not checked against the current values.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut primary: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// let mut replica = primary.clone();
+    /// let before = primary.clone();
+    /// primary["a"] = 1;
+    /// primary["b"] = 2;
+    ///
+    /// replica.apply_diff(before.diff(&primary));
+    /// assert_eq!(replica, primary);
+    /// ```
+    pub fn apply_diff(&mut self, diff: MapDiff<K, V>) {
+        for (key, _) in diff.removed {
+            self.map.remove(&key);
+        }
+        let new_values = diff
+            .added
+            .into_iter()
+            .chain(diff.changed.into_iter().map(|(key, _, new)| (key, new)));
+        for (key, value) in new_values {
+            if value == *self.default.get() {
+                self.map.remove(&key);
+            } else {
+                self.map.insert(key, value);
+            }
+        }
+    }
+
+    /// Returns a guard that gives mutable access to the value of the key, like `get_mut`.
+    /// When the guard is dropped and the value is equal to the default, the entry is removed
+    /// from the map again, or never inserted if the key was missing. This keeps the map sparse
+    /// in code that accesses many keys mutably without changing most of them.
+    ///
+    /// `IndexMut` has to return a plain reference, so `map[key]` can't prune automatically.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, i32> = DefaultHashMap::new();
+    /// *counts.get_mut_pruned("a") += 1;
+    /// *counts.get_mut_pruned("b") += 0;
+    /// *counts.get_mut_pruned("a") -= 1;
+    /// assert!(counts.is_empty());
+    /// ```
+    pub fn get_mut_pruned(&mut self, key: K) -> PruneGuard<'_, K, V> {
+        let state = match self.map.entry(key) {
+            Entry::Occupied(occupied) => GuardState::Occupied(occupied),
+            Entry::Vacant(vacant) => GuardState::Vacant(vacant, self.default.create()),
+        };
+        PruneGuard {
+            state: Some(state),
+            default: self.default.get(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, T: ?Sized + ToOwned, S: BuildHasher + Clone> DefaultHashMap<K, &T, S>
+where
+    T::Owned: Clone + 'static,
+{
+    /// Converts a map that borrows its values into a map that owns them, by calling `to_owned()`
+    /// on every value and on the default.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let text = String::from("a b a");
+    /// let mut last_word: DefaultHashMap<usize, &str> = DefaultHashMap::new();
+    /// for (i, word) in text.split(' ').enumerate() {
+    ///     last_word[i % 2] = word;
+    /// }
+    /// let owned: DefaultHashMap<usize, String> = last_word.to_owned_values();
+    /// drop(text);
+    /// assert_eq!(owned[0], "a");
+    /// assert_eq!(owned[2], "");
+    /// ```
+    pub fn to_owned_values(self) -> DefaultHashMap<K, T::Owned, S> {
+        let mut map = HashMap::with_capacity_and_hasher(self.map.len(), self.map.hasher().clone());
+        map.extend(
+            self.map
+                .into_iter()
+                .map(|(key, value)| (key, value.to_owned())),
+        );
+        DefaultHashMap::from_map_with_default(map, (*self.default.get()).to_owned())
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Adds one to the value of the key, starting from the default if the key is missing.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u64> = DefaultHashMap::new();
+    /// for word in "the cat saw the dog".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts["the"], 2);
+    /// ```
+    pub fn incr(&mut self, key: K) -> &mut V
+    where
+        V: AddAssign + One,
+    {
+        self.incr_by(key, V::one())
+    }
+
+    /// Adds `n` to the value of the key, starting from the default if the key is missing.
+    pub fn incr_by(&mut self, key: K, n: V) -> &mut V
+    where
+        V: AddAssign,
+    {
+        let value = self.get_mut(key);
+        *value += n;
+        value
+    }
+
+    /// Subtracts one from the value of the key, starting from the default if the key is missing.
+    pub fn decr(&mut self, key: K) -> &mut V
+    where
+        V: SubAssign + One,
+    {
+        self.decr_by(key, V::one())
+    }
+
+    /// Subtracts `n` from the value of the key, starting from the default if the key is missing.
+    pub fn decr_by(&mut self, key: K, n: V) -> &mut V
+    where
+        V: SubAssign,
+    {
+        let value = self.get_mut(key);
+        *value -= n;
+        value
+    }
+
+    /// Updates the value of the key with the result of `f`, which gets the current value or the
+    /// default if the key is missing. When `f` returns an error, the map is left unchanged. This
+    /// can be used with checked arithmetic, so counters of bounded types can't silently overflow.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u8> = DefaultHashMap::with_default(250);
+    /// assert_eq!(counts.try_update("a", |v| v.checked_add(5).ok_or("overflow")), Ok(&mut 255));
+    /// assert_eq!(counts.try_update("a", |v| v.checked_add(1).ok_or("overflow")), Err("overflow"));
+    /// assert_eq!(counts.try_update("b", |v| v.checked_add(10).ok_or("overflow")), Err("overflow"));
+    /// assert_eq!(counts["a"], 255);
+    /// assert!(!counts.contains_key("b"));
+    /// ```
+    pub fn try_update<E, F>(&mut self, key: K, f: F) -> Result<&mut V, E>
+    where
+        F: FnOnce(&V) -> Result<V, E>,
+    {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => {
+                let value = occupied.into_mut();
+                *value = f(value)?;
+                Ok(value)
+            }
+            Entry::Vacant(vacant) => Ok(vacant.insert(f(self.default.get())?)),
+        }
+    }
+
+    /// Moves all entries of `other` into this map. For keys that are stored in both maps `f` is
+    /// called with the key, the value in this map and the value from `other`, so it can combine
+    /// them. Unlike `extend`, this never silently overwrites values.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut latest: DefaultHashMap<&str, (u32, &str)> = DefaultHashMap::new();
+    /// latest["a"] = (1, "old");
+    /// let mut update: DefaultHashMap<&str, (u32, &str)> = DefaultHashMap::new();
+    /// update["a"] = (2, "new");
+    /// update["b"] = (1, "added");
+    ///
+    /// latest.merge(update, |_, mine, theirs| {
+    ///     if theirs.0 > mine.0 {
+    ///         *mine = theirs;
+    ///     }
+    /// });
+    /// assert_eq!(latest["a"], (2, "new"));
+    /// assert_eq!(latest["b"], (1, "added"));
+    /// ```
+    pub fn merge<F>(&mut self, other: Self, mut f: F)
+    where
+        F: FnMut(&K, &mut V, V),
+    {
+        for (key, value) in other.map {
+            if let Some(mine) = self.map.get_mut(&key) {
+                f(&key, mine, value);
+            } else {
+                self.map.insert(key, value);
+            }
+        }
+    }
+
+    /// Inserts all entries of `iter`, like Python's `dict.update`. Values of keys that are
+    /// already present are replaced, use `merge` to combine them instead. The default of the map
+    /// isn't changed.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut config: DefaultHashMap<&str, u32> = DefaultHashMap::with_default(1);
+    /// config["retries"] = 3;
+    /// config.update_from([("retries", 5), ("workers", 8)]);
+    /// assert_eq!(config["retries"], 5);
+    /// assert_eq!(config["workers"], 8);
+    /// ```
+    pub fn update_from(&mut self, iter: impl IntoIterator<Item = (K, V)>) {
+        self.map.extend(iter);
+    }
+
+    /// Inserts clones of all entries that are stored in `other`, replacing the values of keys
+    /// that are already present, see `update_from`. The default of `other` is ignored.
+    pub fn update_from_map(&mut self, other: &Self)
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.update_from(other.map.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Creates a new map by applying `f` to every stored value. The default and the default value
+    /// generation function are transformed by `f` too, so missing keys of the new map behave like
+    /// missing keys of this map after transforming them.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::with_default(1);
+    /// counts["a"] = 4;
+    /// let halves = counts.map_values(|v| f64::from(v) / 2.0);
+    /// assert_eq!(halves["a"], 2.0);
+    /// assert_eq!(halves["b"], 0.5);
+    /// ```
+    pub fn map_values<W, F>(self, f: F) -> DefaultHashMap<K, W, S>
+    where
+        V: 'static,
+        F: Fn(V) -> W + Clone + 'static,
+        S: Clone,
+    {
+        let mut map = HashMap::with_capacity_and_hasher(self.map.len(), self.map.hasher().clone());
+        map.extend(self.map.into_iter().map(|(key, value)| (key, f(value))));
+        DefaultHashMap {
+            map,
+            default: self.default.map(f),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Creates a new map that only contains the entries for which `pred` returns `true`, keeping
+    /// the default for missing keys.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// counts["rare"] += 1;
+    /// counts["common"] += 10;
+    /// let frequent = counts.filter(|_, count| *count >= 5);
+    /// assert_eq!(frequent["common"], 10);
+    /// assert!(!frequent.contains_key("rare"));
+    /// ```
+    pub fn filter<F>(mut self, mut pred: F) -> DefaultHashMap<K, V, S>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        self.map.retain(|key, value| pred(key, value));
+        self
+    }
+
+    /// Creates a new map from the entries for which `f` returns `Some`, using the returned value
+    /// as the new value. The default for missing keys is kept.
+    pub fn filter_map<F>(self, mut f: F) -> DefaultHashMap<K, V, S>
+    where
+        F: FnMut(&K, V) -> Option<V>,
+        S: Clone,
+    {
+        let mut map = HashMap::with_capacity_and_hasher(self.map.len(), self.map.hasher().clone());
+        map.extend(
+            self.map
+                .into_iter()
+                .filter_map(|(key, value)| f(&key, value).map(|value| (key, value))),
+        );
+        DefaultHashMap {
+            map,
+            default: self.default,
+            missing: Missing::default(),
+        }
+    }
+}
 
-use crate::DefaultFn;
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Returns an iterator over clones of the stored values.
+    pub fn values_cloned(&self) -> Cloned<Values<'_, K, V>>
+    where
+        V: Clone,
+    {
+        self.map.values().cloned()
+    }
 
-/// A `HashMap` that returns a default when keys are accessed that are not present.
-#[derive(Clone, Debug)]
-#[cfg_attr(feature = "with-serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct DefaultHashMap<K: Eq + Hash, V> {
-    map: HashMap<K, V>,
-    default: V,
-    #[debug(skip)]
-    #[cfg_attr(feature = "with-serde", serde(skip))]
-    default_fn: Box<dyn DefaultFn<V>>,
-}
+    /// Returns all stored entries sorted by key, e.g. for deterministic output in reports and
+    /// tests.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let map = DefaultHashMap::from([("b", 2), ("a", 1)]);
+    /// assert_eq!(map.to_sorted_vec(), vec![(&"a", &1), (&"b", &2)]);
+    /// assert_eq!(map.into_sorted_vec(), vec![("a", 1), ("b", 2)]);
+    /// ```
+    pub fn to_sorted_vec(&self) -> Vec<(&K, &V)>
+    where
+        K: Ord,
+    {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
 
-impl<K: Eq + Hash, V: PartialEq> PartialEq for DefaultHashMap<K, V> {
-    fn eq(&self, other: &Self) -> bool {
-        self.map == other.map && self.default == other.default
+    /// Converts the map into a `Vec` of all stored entries sorted by key, see `to_sorted_vec`.
+    pub fn into_sorted_vec(self) -> Vec<(K, V)>
+    where
+        K: Ord,
+    {
+        let mut entries: Vec<_> = self.map.into_iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        entries
     }
-}
 
-impl<K: Eq + Hash, V: Eq> Eq for DefaultHashMap<K, V> {}
+    /// Returns the sum of all values stored in the map, e.g. the total number of events seen by
+    /// a counter map.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u64> = DefaultHashMap::new();
+    /// for word in "the cat saw the dog".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts.total(), 5);
+    /// ```
+    pub fn total(&self) -> V
+    where
+        V: for<'a> Sum<&'a V>,
+    {
+        self.map.values().sum()
+    }
 
-impl<K: Eq + Hash, V: Default> DefaultHashMap<K, V> {
-    /// The `new()` constructor creates an empty DefaultHashMap with the default of `V`
-    /// as the default for missing keys.
-    /// This is desired default for most use cases, if your case requires a
-    /// different default you should use the `with_default()` constructor.
-    pub fn new() -> DefaultHashMap<K, V> {
-        DefaultHashMap {
-            map: HashMap::default(),
-            default_fn: Box::new(|| V::default()),
-            default: V::default(),
-        }
+    /// Returns the count, minimum, maximum, mean and variance of the values stored in the map,
+    /// or `None` if the map is empty. See [`ValueStats`] for an example.
+    pub fn value_stats(&self) -> Option<ValueStats>
+    where
+        V: AsF64,
+    {
+        ValueStats::from_values(self.map.values())
     }
-}
 
-impl<K: Eq + Hash, V: Default> Default for DefaultHashMap<K, V> {
-    /// The `default()` method is equivalent to `DefaultHashMap::new()`.
-    fn default() -> DefaultHashMap<K, V> {
-        DefaultHashMap::new()
+    /// Returns the statistics of the values of the provided keys, like `value_stats`. Keys that
+    /// are not in the map count as the default, so the statistics include keys that were never
+    /// seen. Keys that are stored but not provided are left out.
+    pub fn value_stats_over<I>(&self, keys: I) -> Option<ValueStats>
+    where
+        V: AsF64,
+        I: IntoIterator,
+        I::Item: Borrow<K>,
+    {
+        ValueStats::from_values(self.iter_with_defaults(keys).map(|(_, value)| value))
     }
-}
 
-impl<K: Eq + Hash, V: Default> From<HashMap<K, V>> for DefaultHashMap<K, V> {
-    /// If you already have a `HashMap` that you would like to convert to a
-    /// `DefaultHashMap` you can use the `into()` method on the `HashMap` or the
-    /// `from()` constructor of `DefaultHashMap`.
-    /// The default value for missing keys will be `V::default()`,
-    /// if this is not desired `DefaultHashMap::from_map_with_default()` should be used.
-    fn from(map: HashMap<K, V>) -> DefaultHashMap<K, V> {
+    /// Returns a map with every value divided by the sum of all values, which turns a map of
+    /// counts into a probability distribution. The default is converted to an `f64`, but isn't
+    /// divided. When the sum is zero the values become NaN.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// counts["the"] = 3;
+    /// counts["cat"] = 1;
+    /// let probabilities = counts.normalized();
+    /// assert_eq!(probabilities["the"], 0.75);
+    /// assert_eq!(probabilities["dog"], 0.0);
+    /// ```
+    pub fn normalized(&self) -> DefaultHashMap<K, f64, S>
+    where
+        K: Clone,
+        V: AsF64,
+        S: Clone,
+    {
+        let total: f64 = self.map.values().map(AsF64::as_f64).sum();
+        let mut map = HashMap::with_capacity_and_hasher(self.map.len(), self.map.hasher().clone());
+        map.extend(
+            self.map
+                .iter()
+                .map(|(key, value)| (key.clone(), value.as_f64() / total)),
+        );
         DefaultHashMap {
             map,
-            default_fn: Box::new(|| V::default()),
-            default: V::default(),
+            default: LazyDefault::value(self.default.get().as_f64()),
+            missing: Missing::default(),
+        }
+    }
+
+    /// Returns the entry with the largest value, or `None` if the map is empty. If several
+    /// entries have the largest value, it's unspecified which of them is returned.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u64> = DefaultHashMap::new();
+    /// for word in "the cat saw the dog".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts.max_by_value(), Some((&"the", &2)));
+    /// assert_eq!(counts.argmax(), Some(&"the"));
+    /// ```
+    pub fn max_by_value(&self) -> Option<(&K, &V)>
+    where
+        V: Ord,
+    {
+        self.map.iter().max_by(|a, b| a.1.cmp(b.1))
+    }
+
+    /// Returns the entry with the smallest value, or `None` if the map is empty. If several
+    /// entries have the smallest value, it's unspecified which of them is returned.
+    pub fn min_by_value(&self) -> Option<(&K, &V)>
+    where
+        V: Ord,
+    {
+        self.map.iter().min_by(|a, b| a.1.cmp(b.1))
+    }
+
+    /// Returns the key with the largest value, see `max_by_value`.
+    pub fn argmax(&self) -> Option<&K>
+    where
+        V: Ord,
+    {
+        self.max_by_value().map(|(key, _)| key)
+    }
+
+    /// Returns the key with the smallest value, see `min_by_value`.
+    pub fn argmin(&self) -> Option<&K>
+    where
+        V: Ord,
+    {
+        self.min_by_value().map(|(key, _)| key)
+    }
+
+    /// Returns the `n` entries with the largest values, ordered from largest to smallest value.
+    /// When the map has fewer than `n` entries all of them are returned. Only the returned
+    /// entries are sorted, so this is faster than sorting the whole map when `n` is small. The
+    /// order of entries with equal values is unspecified.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u64> = DefaultHashMap::new();
+    /// for word in "the cat saw the dog and the cat".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// assert_eq!(counts.most_common(2), vec![(&"the", &3), (&"cat", &2)]);
+    /// ```
+    pub fn most_common(&self, n: usize) -> Vec<(&K, &V)>
+    where
+        V: Ord,
+    {
+        if n == 0 {
+            return Vec::new();
+        }
+        let mut entries: Vec<_> = self.map.iter().collect();
+        if n < entries.len() {
+            entries.select_nth_unstable_by(n - 1, |a, b| b.1.cmp(a.1));
+            entries.truncate(n);
         }
+        entries.sort_unstable_by(|a, b| b.1.cmp(a.1));
+        entries
+    }
+
+    /// Returns an iterator over all stored entries, sorted from the smallest to the largest value.
+    /// Entries with equal values are returned in an unspecified order. The entries are collected
+    /// and sorted when this is called.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut counts: DefaultHashMap<&str, u64> = DefaultHashMap::new();
+    /// for word in "the cat saw the dog and the cat".split(' ') {
+    ///     counts.incr(word);
+    /// }
+    /// let top: Vec<_> = counts.iter_sorted_by_value_desc().take(2).collect();
+    /// assert_eq!(top, [(&"the", &3), (&"cat", &2)]);
+    /// assert_eq!(counts.keys_sorted_by_value().last(), Some(&"the"));
+    /// ```
+    pub fn iter_sorted_by_value(&self) -> std::vec::IntoIter<(&K, &V)>
+    where
+        V: Ord,
+    {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by(|a, b| a.1.cmp(b.1));
+        entries.into_iter()
+    }
+
+    /// Returns an iterator over all stored entries, sorted from the largest to the smallest
+    /// value, see `iter_sorted_by_value`.
+    pub fn iter_sorted_by_value_desc(&self) -> std::vec::IntoIter<(&K, &V)>
+    where
+        V: Ord,
+    {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by(|a, b| b.1.cmp(a.1));
+        entries.into_iter()
+    }
+
+    /// Returns an iterator over the keys, sorted from the smallest to the largest value, see
+    /// `iter_sorted_by_value`. Use `rev()` to get the keys with the largest values first.
+    pub fn keys_sorted_by_value(&self) -> impl DoubleEndedIterator<Item = &K>
+    where
+        V: Ord,
+    {
+        self.iter_sorted_by_value().map(|(key, _)| key)
     }
 }
 
-impl<K: Eq + Hash, V> From<DefaultHashMap<K, V>> for HashMap<K, V> {
-    /// The into method can be used to convert a `DefaultHashMap` back into a
-    /// `HashMap`.
-    fn from(default_map: DefaultHashMap<K, V>) -> HashMap<K, V> {
-        default_map.map
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Returns an iterator over the keys that are stored in both this map and `other`.
+    /// The values of the maps don't need to have the same type.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut yesterday: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// yesterday["a"] = 1;
+    /// yesterday["b"] = 2;
+    /// let mut today: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// today["b"] = 3;
+    /// today["c"] = 4;
+    ///
+    /// assert_eq!(yesterday.intersect_keys(&today).collect::<Vec<_>>(), vec![&"b"]);
+    /// assert_eq!(yesterday.difference_keys(&today).collect::<Vec<_>>(), vec![&"a"]);
+    /// assert_eq!(yesterday.union_keys(&today).count(), 3);
+    /// ```
+    pub fn intersect_keys<'a, V2, S2: BuildHasher>(
+        &'a self,
+        other: &'a DefaultHashMap<K, V2, S2>,
+    ) -> impl Iterator<Item = &'a K> + 'a {
+        self.map
+            .keys()
+            .filter(move |key| other.map.contains_key(*key))
+    }
+
+    /// Returns an iterator over the keys that are stored in this map but not in `other`.
+    pub fn difference_keys<'a, V2, S2: BuildHasher>(
+        &'a self,
+        other: &'a DefaultHashMap<K, V2, S2>,
+    ) -> impl Iterator<Item = &'a K> + 'a {
+        self.map
+            .keys()
+            .filter(move |key| !other.map.contains_key(*key))
+    }
+
+    /// Returns an iterator over the keys that are stored in this map or in `other`, without
+    /// duplicates. The keys of this map come first, followed by the keys that are only stored in
+    /// `other`.
+    pub fn union_keys<'a, V2, S2: BuildHasher>(
+        &'a self,
+        other: &'a DefaultHashMap<K, V2, S2>,
+    ) -> impl Iterator<Item = &'a K> + 'a {
+        self.map.keys().chain(other.difference_keys(self))
+    }
+
+    /// Returns an iterator over the keys that are stored in this map or in `other`, together with
+    /// the values of both maps for these keys. Missing values are filled in with the default of
+    /// the map they are missing from. The keys are returned in the same order as by `union_keys`.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let mut before: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// before["a"] = 1;
+    /// let mut after: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+    /// after["b"] = 2;
+    ///
+    /// let mut joined: Vec<_> = before.outer_join(&after).collect();
+    /// joined.sort();
+    /// assert_eq!(joined, vec![(&"a", &1, &0), (&"b", &0, &2)]);
+    /// ```
+    pub fn outer_join<'a, V2, S2: BuildHasher>(
+        &'a self,
+        other: &'a DefaultHashMap<K, V2, S2>,
+    ) -> impl Iterator<Item = (&'a K, &'a V, &'a V2)> + 'a {
+        self.union_keys(other)
+            .map(move |key| (key, self.get(key), other.get(key)))
     }
 }
 
-impl<K: Eq + Hash, V: Clone + 'static> DefaultHashMap<K, V> {
-    /// Creates an empty `DefaultHashMap` with `default` as the default for missing keys.
-    /// When the provided `default` is equivalent to `V::default()` it is preferred to use
-    /// `DefaultHashMap::default()` instead.
-    pub fn with_default(default: V) -> DefaultHashMap<K, V> {
-        DefaultHashMap {
-            map: HashMap::new(),
-            default: default.clone(),
-            default_fn: Box::new(move || default.clone()),
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Returns a wrapper whose `Debug` output has all keys masked, see [`Redacted`].
+    pub fn redacted(&self) -> Redacted<'_, Self> {
+        Redacted { map: self }
+    }
+
+    /// Returns a wrapper whose `Debug` output lists the entries sorted by key, see
+    /// [`SortedDebug`]. This makes the output deterministic, e.g. for snapshot tests.
+    ///
+    /// ```
+    /// # use defaultmap::*;
+    /// let map: DefaultHashMap<i32, i32> = defaulthashmap! {3 => 1, 1 => 2, 2 => 3};
+    /// assert_eq!(
+    ///     format!("{:?}", map.sorted_debug()),
+    ///     "DefaultHashMap { map: {1: 2, 2: 3, 3: 1}, default: 0 }"
+    /// );
+    /// ```
+    pub fn sorted_debug(&self) -> SortedDebug<'_, K, V, S>
+    where
+        K: Ord,
+    {
+        SortedDebug { map: self }
+    }
+}
+
+impl<K: Eq + Hash, V: fmt::Debug, S: BuildHasher> fmt::Debug
+    for Redacted<'_, DefaultHashMap<K, V, S>>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("DefaultHashMap");
+        debug
+            .field("map", &MaskedEntries(self.map.map.iter()))
+            .field("default", self.map.default.get());
+        if let Some(name) = self.map.default.name() {
+            debug.field("default_fn", &name);
         }
+        debug.finish()
     }
+}
 
-    /// Creates a `DefaultHashMap` based on a default and an already existing `HashMap`.
-    /// If `V::default()` is the supplied default, usage of the `from()` constructor or the
-    /// `into()` method on the original `HashMap` is preferred.
-    pub fn from_map_with_default(map: HashMap<K, V>, default: V) -> DefaultHashMap<K, V> {
-        DefaultHashMap {
-            map,
-            default: default.clone(),
-            default_fn: Box::new(move || default.clone()),
+/// A wrapper that formats a `DefaultHashMap` like its `Debug` implementation does, but with the
+/// entries sorted by key. It's returned by [`DefaultHashMap::sorted_debug`].
+pub struct SortedDebug<'a, K: Eq + Hash, V, S> {
+    map: &'a DefaultHashMap<K, V, S>,
+}
+
+impl<K: Eq + Hash, V, S> Clone for SortedDebug<'_, K, V, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K: Eq + Hash, V, S> Copy for SortedDebug<'_, K, V, S> {}
+
+impl<K: Eq + Hash + Ord + fmt::Debug, V: fmt::Debug, S> fmt::Debug for SortedDebug<'_, K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<_> = self.map.map.iter().collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        let mut debug = f.debug_struct("DefaultHashMap");
+        debug
+            .field("map", &SortedEntries(entries))
+            .field("default", self.map.default.get());
+        if let Some(name) = self.map.default.name() {
+            debug.field("default_fn", &name);
         }
+        debug.finish()
     }
+}
 
-    /// Changes the default value permanently or until `set_default()` is called again.
-    pub fn set_default(&mut self, new_default: V) {
-        self.default = new_default.clone();
-        self.default_fn = Box::new(move || new_default.clone());
+struct SortedEntries<'a, K, V>(Vec<(&'a K, &'a V)>);
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for SortedEntries<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter().copied()).finish()
     }
 }
 
-impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
-    /// Returns a reference to the value stored for the provided key.
-    /// If the key is not in the `DefaultHashMap` a reference to the default value is returned.
-    /// Usually the `map[key]` method of retrieving keys is preferred over using `get` directly.
-    /// This method accepts both references and owned values as the key.
-    pub fn get<Q, QB: Borrow<Q>>(&self, key: QB) -> &V
+impl<K: Eq + Hash + CompositeKey, V, S: BuildHasher> DefaultHashMap<K, V, S> {
+    /// Returns an iterator over all entries whose key has `first` as its first component.
+    /// Keys that are missing from the map are not included, so no defaults are returned.
+    pub fn get_by_first<'a, Q>(&'a self, first: &'a Q) -> impl Iterator<Item = (&'a K, &'a V)>
     where
-        K: Borrow<Q>,
-        Q: ?Sized + Hash + Eq,
+        K::First: Borrow<Q>,
+        Q: ?Sized + Eq,
     {
-        self.map.get(key.borrow()).unwrap_or(&self.default)
+        self.map
+            .iter()
+            .filter(move |(key, _)| key.first().borrow() == first)
     }
+}
 
-    /// Returns the an owned version of the default value
-    /// ```
-    /// use defaultmap::DefaultHashMap;
-    /// assert_eq!(DefaultHashMap::<String, i32>::new().get_default(), 0);
-    /// ```
-    pub fn get_default(&self) -> V {
-        self.default_fn.call()
+/// A guard that gives mutable access to a value of a `DefaultHashMap`, and removes the entry again
+/// when it's dropped while the value is equal to the default. It's returned by
+/// [`DefaultHashMap::get_mut_pruned`].
+pub struct PruneGuard<'a, K, V: PartialEq> {
+    state: Option<GuardState<'a, K, V>>,
+    default: &'a V,
+}
+
+enum GuardState<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>, V),
+}
+
+impl<K, V: PartialEq> Deref for PruneGuard<'_, K, V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        match self.state.as_ref().expect("state is only taken on drop") {
+            GuardState::Occupied(occupied) => occupied.get(),
+            GuardState::Vacant(_, value) => value,
+        }
     }
+}
 
-    /// Creates an empty `DefaultHashMap` with `default_fn` as the default value generation
-    /// function for missing keys. When the provided `default_fn` only calls clone on a value,
-    /// using `DefaultHashMap::new` is preferred.
-    pub fn with_fn(default_fn: impl DefaultFn<V> + 'static) -> DefaultHashMap<K, V> {
-        DefaultHashMap {
-            map: HashMap::new(),
-            default: default_fn.call(),
-            default_fn: Box::new(default_fn),
+impl<K, V: PartialEq> DerefMut for PruneGuard<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut V {
+        match self.state.as_mut().expect("state is only taken on drop") {
+            GuardState::Occupied(occupied) => occupied.get_mut(),
+            GuardState::Vacant(_, value) => value,
         }
     }
+}
 
-    /// Creates a `DefaultHashMap` based on an existing map and using `default_fn` as the default
-    /// value generation function for missing keys. When the provided `default_fn` is equivalent to
-    /// V::default(), then using `DefaultHashMap::from(map)` is preferred.
-    pub fn from_map_with_fn(
-        map: HashMap<K, V>,
-        default_fn: impl DefaultFn<V> + 'static,
-    ) -> DefaultHashMap<K, V> {
-        DefaultHashMap {
-            map,
-            default: default_fn.call(),
-            default_fn: Box::new(default_fn),
+impl<K, V: PartialEq> Drop for PruneGuard<'_, K, V> {
+    fn drop(&mut self) {
+        match self.state.take() {
+            Some(GuardState::Occupied(occupied)) if occupied.get() == self.default => {
+                occupied.remove();
+            }
+            Some(GuardState::Vacant(vacant, value)) if value != *self.default => {
+                vacant.insert(value);
+            }
+            _ => {}
         }
     }
 }
 
-impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
-    /// Returns a mutable reference to the value stored for the provided key.
-    /// If there is no value stored for the key the default value is first inserted for this
-    /// key before returning the reference.
-    /// Usually the `map[key] = new_val` is prefered over using `get_mut` directly.
-    /// This method only accepts owned values as the key.
-    pub fn get_mut(&mut self, key: K) -> &mut V {
-        let entry = self.map.entry(key);
-        match entry {
-            Entry::Occupied(occupied) => occupied.into_mut(),
-            Entry::Vacant(vacant) => vacant.insert(self.default_fn.call()),
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultMap<K, V> for DefaultHashMap<K, V, S> {
+    type Iter<'a>
+        = Iter<'a, K, V>
+    where
+        K: 'a,
+        V: 'a,
+        S: 'a;
+
+    fn get(&self, key: &K) -> &V {
+        DefaultHashMap::get(self, key)
+    }
+
+    fn get_mut(&mut self, key: K) -> &mut V {
+        DefaultHashMap::get_mut(self, key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.map.remove(key)
+    }
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.map.iter()
+    }
+
+    fn get_default(&self) -> V {
+        self.default.create()
+    }
+
+    fn default_value(&self) -> &V {
+        self.default.get()
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+/// Shows the entries as `key: value` pairs, followed by the default. The alternate form `{:#}`
+/// puts every entry on its own line, which is useful for printing counters in command line tools.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+/// counts["a"] += 3;
+/// assert_eq!(format!("{}", counts), "{a: 3} (default: 0)");
+/// assert_eq!(format!("{:#}", counts), "a: 3\n(default: 0)");
+/// ```
+impl<K: Eq + Hash + fmt::Display, V: fmt::Display, S> fmt::Display for DefaultHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            for (key, value) in &self.map {
+                writeln!(f, "{}: {}", key, value)?;
+            }
+        } else {
+            f.write_str("{")?;
+            for (i, (key, value)) in self.map.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}: {}", key, value)?;
+            }
+            f.write_str("} ")?;
         }
+        write!(f, "(default: {})", self.default.get())
+    }
+}
+
+/// Implements the `Deref` trait, so all read-only methods of `HashMap` can be used on the map,
+/// like `get_key_value`. Methods of `DefaultHashMap` with the same name, such as `get`, take
+/// precedence and return the default for missing keys. There is no `DerefMut`, because that
+/// would allow changing the map without going through its methods.
+///
+/// ```
+/// # use defaultmap::*;
+/// let map: DefaultHashMap<&str, u32> = defaulthashmap! {"a" => 1};
+/// assert_eq!(map.get_key_value("a"), Some((&"a", &1)));
+/// ```
+impl<K: Eq + Hash, V, S> Deref for DefaultHashMap<K, V, S> {
+    type Target = HashMap<K, V, S>;
+
+    fn deref(&self) -> &HashMap<K, V, S> {
+        &self.map
+    }
+}
+
+/// Deserializes the format the map is serialized in, a struct with a `map` and a `default` field,
+/// or a plain map of entries. A plain map gets the default of `V` as its default for missing
+/// keys. The input is only read as the struct when its `map` key holds a map, see
+/// [`serde_helpers`](crate::serde_helpers) to only accept plain maps.
+#[cfg(feature = "with-serde")]
+impl<'de, K, V, S> serde::Deserialize<'de> for DefaultHashMap<K, V, S>
+where
+    K: Eq + Hash + serde::Deserialize<'de>,
+    V: serde::Deserialize<'de> + Default,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (map, default) =
+            crate::serde_helpers::deserialize_entries(deserializer, "DefaultHashMap")?;
+        Ok(DefaultHashMap {
+            map,
+            default,
+            missing: Missing::default(),
+        })
     }
 }
 
 /// Implements the `Index` trait so you can do `map[key]`.
 /// Nonmutable indexing can be done both by passing a reference or an owned value as the key.
-impl<K: Eq + Hash, KB: Borrow<K>, V> Index<KB> for DefaultHashMap<K, V> {
+impl<K: Eq + Hash, KB: Borrow<K>, V, S: BuildHasher> Index<KB> for DefaultHashMap<K, V, S> {
     type Output = V;
 
     fn index(&self, index: KB) -> &V {
-        self.get(index)
+        let key = index.borrow();
+        match self.map.get(key) {
+            Some(value) => value,
+            None => {
+                self.missing.record_miss(Some(key));
+                self.default.get()
+            }
+        }
     }
 }
 
 /// Implements the `IndexMut` trait so you can do `map[key] = val`.
 /// Mutably indexing can only be done when passing an owned value as the key.
-impl<K: Eq + Hash, V> IndexMut<K> for DefaultHashMap<K, V> {
+impl<K: Eq + Hash, V, S: BuildHasher> IndexMut<K> for DefaultHashMap<K, V, S> {
     #[inline]
     fn index_mut(&mut self, index: K) -> &mut V {
         self.get_mut(index)
     }
 }
 
+/// Implements the `AddAssign` trait so you can do `map += other` to add the values of two maps
+/// together key-wise, like for Python's `Counter`. Keys that are missing from `map` start out at
+/// its default.
+impl<K: Eq + Hash, V: AddAssign, S: BuildHasher> AddAssign for DefaultHashMap<K, V, S> {
+    fn add_assign(&mut self, other: Self) {
+        for (key, value) in other.map {
+            *self.get_mut(key) += value;
+        }
+    }
+}
+
+/// Implements the `Add` trait so you can do `map + other`, see the `AddAssign` implementation.
+/// The result keeps the default of `map`.
+impl<K: Eq + Hash, V: AddAssign, S: BuildHasher> Add for DefaultHashMap<K, V, S> {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+/// Implements the `SubAssign` trait so you can do `map -= other` to subtract the values of
+/// `other` from `map` key-wise, like for Python's `Counter`. The result is clamped at the default:
+/// keys whose value ends up less than or equal to the default are removed.
+impl<K: Eq + Hash, V: SubAssign + PartialOrd, S: BuildHasher> SubAssign
+    for DefaultHashMap<K, V, S>
+{
+    fn sub_assign(&mut self, other: Self) {
+        for (key, value) in other.map {
+            match self.map.entry(key) {
+                Entry::Occupied(mut occupied) => {
+                    *occupied.get_mut() -= value;
+                    if *occupied.get() <= *self.default.get() {
+                        occupied.remove();
+                    }
+                }
+                Entry::Vacant(vacant) => {
+                    let mut new = self.default.create();
+                    new -= value;
+                    if new > *self.default.get() {
+                        vacant.insert(new);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Implements the `Sub` trait so you can do `map - other`, see the `SubAssign` implementation.
+impl<K: Eq + Hash, V: SubAssign + PartialOrd, S: BuildHasher> Sub for DefaultHashMap<K, V, S> {
+    type Output = Self;
+
+    fn sub(mut self, other: Self) -> Self {
+        self -= other;
+        self
+    }
+}
+
+/// Implements the `BitOrAssign` trait so you can do `map |= other` to keep the maximum of the
+/// values of both maps for every key, like for Python's `Counter`. Keys that are missing from
+/// `map` are compared against its default.
+impl<K: Eq + Hash, V: PartialOrd, S: BuildHasher> BitOrAssign for DefaultHashMap<K, V, S> {
+    fn bitor_assign(&mut self, other: Self) {
+        for (key, value) in other.map {
+            match self.map.entry(key) {
+                Entry::Occupied(mut occupied) => {
+                    if value > *occupied.get() {
+                        occupied.insert(value);
+                    }
+                }
+                Entry::Vacant(vacant) => {
+                    if value > *self.default.get() {
+                        vacant.insert(value);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Implements the `BitOr` trait so you can do `map | other`, see the `BitOrAssign`
+/// implementation.
+impl<K: Eq + Hash, V: PartialOrd, S: BuildHasher> BitOr for DefaultHashMap<K, V, S> {
+    type Output = Self;
+
+    fn bitor(mut self, other: Self) -> Self {
+        self |= other;
+        self
+    }
+}
+
+/// Implements the `BitAndAssign` trait so you can do `map &= other` to keep the minimum of the
+/// values of both maps for every key, like for Python's `Counter`. Only the keys that are stored
+/// in both maps are kept.
+impl<K: Eq + Hash, V: PartialOrd, S: BuildHasher> BitAndAssign for DefaultHashMap<K, V, S> {
+    fn bitand_assign(&mut self, mut other: Self) {
+        self.map.retain(|key, value| match other.map.remove(key) {
+            Some(other_value) => {
+                if other_value < *value {
+                    *value = other_value;
+                }
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+/// Implements the `BitAnd` trait so you can do `map & other`, see the `BitAndAssign`
+/// implementation.
+impl<K: Eq + Hash, V: PartialOrd, S: BuildHasher> BitAnd for DefaultHashMap<K, V, S> {
+    type Output = Self;
+
+    fn bitand(mut self, other: Self) -> Self {
+        self &= other;
+        self
+    }
+}
+
 // grcov-excl-start
 /// These methods simply forward to the underlying `HashMap`, see that
 /// [documentation](https://doc.rust-lang.org/std/collections/struct.HashMap.html) for
 /// the usage of these methods.
-impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
+impl<K: Eq + Hash, V, S: BuildHasher> DefaultHashMap<K, V, S> {
     pub fn capacity(&self) -> usize {
         self.map.capacity()
     }
     #[inline]
-    pub fn keys(&self) -> Keys<K, V> {
+    pub fn keys(&self) -> Keys<'_, K, V> {
         self.map.keys()
     }
     #[inline]
@@ -201,11 +2099,11 @@ impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
         self.map.into_keys()
     }
     #[inline]
-    pub fn values(&self) -> Values<K, V> {
+    pub fn values(&self) -> Values<'_, K, V> {
         self.map.values()
     }
     #[inline]
-    pub fn values_mut(&mut self) -> ValuesMut<K, V> {
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
         self.map.values_mut()
     }
     #[inline]
@@ -213,11 +2111,11 @@ impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
         self.map.into_values()
     }
     #[inline]
-    pub fn iter(&self) -> Iter<K, V> {
+    pub fn iter(&self) -> Iter<'_, K, V> {
         self.map.iter()
     }
     #[inline]
-    pub fn iter_mut(&mut self) -> IterMut<K, V> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
         self.map.iter_mut()
     }
     #[inline]
@@ -229,7 +2127,7 @@ impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
         self.map.is_empty()
     }
     #[inline]
-    pub fn drain(&mut self) -> Drain<K, V> {
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
         self.map.drain()
     }
     #[inline]
@@ -260,14 +2158,29 @@ impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
         self.map.shrink_to(min_capacity);
     }
     #[inline]
-    pub fn entry(&mut self, key: K) -> Entry<K, V> {
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
         self.map.entry(key)
     }
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
 
     #[inline]
     pub fn insert(&mut self, k: K, v: V) -> Option<V> {
         self.map.insert(k, v)
     }
+    /// A stable version of the unstable `try_insert` method of the underlying map.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<&mut V, OccupiedError<OccupiedEntry<'_, K, V>, V>> {
+        match self.map.entry(key) {
+            Entry::Occupied(entry) => Err(OccupiedError { entry, value }),
+            Entry::Vacant(entry) => Ok(entry.insert(value)),
+        }
+    }
     #[inline]
     pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
@@ -285,25 +2198,27 @@ impl<K: Eq + Hash, V> DefaultHashMap<K, V> {
         self.map.remove(k)
     }
     #[inline]
-    pub fn remove_entry<Q: ?Sized>(&mut self, k: &Q) -> Option<(K, V)>
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: ?Sized + Hash + Eq,
     {
         self.map.remove_entry(k)
     }
 }
 // grcov-excl-stop
 
-impl<K: Eq + Hash, V: Default> FromIterator<(K, V)> for DefaultHashMap<K, V> {
+impl<K: Eq + Hash, V: Default, S: BuildHasher + Default> FromIterator<(K, V)>
+    for DefaultHashMap<K, V, S>
+{
     fn from_iter<I>(iter: I) -> Self
     where
         I: IntoIterator<Item = (K, V)>,
     {
         Self {
             map: HashMap::from_iter(iter),
-            default: V::default(),
-            default_fn: Box::new(|| V::default()),
+            default: LazyDefault::std_default(),
+            missing: Missing::default(),
         }
     }
 }
@@ -334,6 +2249,12 @@ impl<K: Eq + Hash, V: Default> FromIterator<(K, V)> for DefaultHashMap<K, V> {
 ///     5 => 20,
 ///     6 => 30,
 /// };
+///
+/// // A prefilled map with a function that creates the default
+/// let _: DefaultHashMap<&str, Vec<i32>> = defaulthashmap!{
+///     @fn || Vec::with_capacity(8),
+///     "a" => vec![1],
+/// };
 /// ```
 #[macro_export]
 macro_rules! defaulthashmap {
@@ -351,6 +2272,14 @@ macro_rules! defaulthashmap {
         }
     };
 
+    (@fn $default_fn:expr$(, $key:expr => $value:expr)+ ,) => { defaulthashmap!(@fn $default_fn, $($key => $value),+) };
+    (@fn $default_fn:expr$(, $key:expr => $value:expr)*) => {
+        {
+            let _map = defaulthashmap!(@hashmap $($key => $value),*);
+            $crate::DefaultHashMap::from_map_with_fn(_map, $default_fn)
+        }
+    };
+
     ($($key:expr => $value:expr,)+) => { defaulthashmap!($($key => $value),+) };
     ($($key:expr => $value:expr),*) => {
         {
@@ -371,6 +2300,7 @@ macro_rules! defaulthashmap {
 #[cfg(test)]
 mod tests {
     use super::DefaultHashMap;
+    use crate::MapDiff;
     use std::collections::HashMap;
 
     #[test]
@@ -408,6 +2338,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrows_for_generic_args)]
     fn add() {
         let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::default();
         *map.get_mut(0) += 1;
@@ -528,6 +2459,980 @@ mod tests {
         assert_eq!(map[2], 3);
     }
 
+    #[test]
+    #[cfg(feature = "int-hasher")]
+    fn with_hasher() {
+        use crate::{BuildIntHasher, DefaultIntMap};
+
+        let mut map: DefaultIntMap<u32, i32> =
+            DefaultHashMap::with_hasher(BuildIntHasher::default());
+        map[3] += 1;
+        map[u32::MAX] += 2;
+        assert_eq!(map[3], 1);
+        assert_eq!(map[u32::MAX], 2);
+        assert_eq!(map[4], 0);
+        assert_eq!(map, DefaultIntMap::from_iter([(3, 1), (u32::MAX, 2)]));
+    }
+
+    #[test]
+    fn get_disjoint_mut() {
+        let mut map: DefaultHashMap<String, i32> = DefaultHashMap::with_default(5);
+        map.insert("a".to_string(), 1);
+        let [a, b, c] = map.get_disjoint_mut(["a", "b", "c"]);
+        *a += 10;
+        std::mem::swap(b, c);
+        *c += 1;
+        assert_eq!(map["a".to_string()], 11);
+        assert_eq!(map["b".to_string()], 5);
+        assert_eq!(map["c".to_string()], 6);
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate keys")]
+    fn get_disjoint_mut_duplicate() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map.get_disjoint_mut([&1, &2, &1]);
+    }
+
+    /// Equal to every key that differs by at most one, so `0` and `2` aren't equal to each other
+    /// but both find the entry for `1`.
+    #[derive(Clone, Copy, Debug)]
+    struct Close(i32);
+
+    impl PartialEq for Close {
+        fn eq(&self, other: &Close) -> bool {
+            (self.0 - other.0).abs() <= 1
+        }
+    }
+
+    impl Eq for Close {}
+
+    impl std::hash::Hash for Close {
+        fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate keys")]
+    fn get_disjoint_mut_inconsistent_eq() {
+        let mut map: DefaultHashMap<Close, i32> = DefaultHashMap::new();
+        map.insert(Close(1), 1);
+        map.get_disjoint_mut([&Close(0), &Close(2)]);
+    }
+
+    #[test]
+    fn reindex_by() {
+        let map: DefaultHashMap<i32, i32> = defaulthashmap! {7, 1 => 1, 2 => 2, 3 => 3, 4 => 4};
+        let map = map.reindex_by(|k, _| k % 2 == 0, |a, b| *a += b);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[true], 6);
+        assert_eq!(map[false], 4);
+        assert_eq!(map.get_default(), 7);
+    }
+
+    #[test]
+    fn try_insert() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        *map.try_insert(1, 10).unwrap() += 1;
+        let err = map.try_insert(1, 20).unwrap_err();
+        assert_eq!(err.value, 20);
+        assert_eq!(err.entry.get(), &11);
+        assert_eq!(map[1], 11);
+    }
+
+    #[test]
+    fn extract_if() {
+        let mut map: DefaultHashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+        let mut evens: Vec<_> = map
+            .extract_if(|k, v| {
+                *v += 1;
+                k % 2 == 0
+            })
+            .collect();
+        evens.sort();
+        assert_eq!(evens, vec![(0, 1), (2, 3), (4, 5), (6, 7), (8, 9)]);
+        assert_eq!(map.len(), 5);
+        assert_eq!(map[1], 2);
+        assert_eq!(map[2], 0);
+    }
+
+    #[test]
+    fn extract_if_panic() {
+        let mut map: DefaultHashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.extract_if(|k, _| if *k == 5 { panic!("boom") } else { true })
+                .count()
+        }));
+        assert!(result.is_err());
+        assert_eq!(map.len(), 10);
+        assert_eq!(map[7], 7);
+    }
+
+    #[test]
+    fn contains_non_default() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map[1] += 1;
+        map[2] += 0;
+        assert!(map.contains_non_default(&1));
+        assert!(!map.contains_non_default(&2));
+        assert!(map.contains_key(&2));
+        assert!(!map.contains_non_default(&3));
+    }
+
+    #[test]
+    fn to_owned_values() {
+        let words = vec!["x".to_string(), "y".to_string()];
+        let mut map: DefaultHashMap<i32, &str> = DefaultHashMap::new();
+        map[1] = &words[0];
+        map[2] = &words[1];
+        let owned = map.to_owned_values();
+        drop(words);
+        assert_eq!(owned[1], "x");
+        assert_eq!(owned[2], "y");
+        assert_eq!(owned[3], "");
+        assert_eq!(owned.get_default(), "");
+    }
+
+    #[test]
+    fn get_by_first() {
+        let mut map: DefaultHashMap<(&str, &str), i32> = DefaultHashMap::new();
+        map[("a", "x")] += 1;
+        map[("a", "y")] += 2;
+        map[("b", "x")] += 4;
+
+        let mut a: Vec<_> = map.get_by_first("a").collect();
+        a.sort();
+        assert_eq!(a, vec![(&("a", "x"), &1), (&("a", "y"), &2)]);
+        assert_eq!(map.get_by_first("b").count(), 1);
+        assert_eq!(map.get_by_first("c").count(), 0);
+    }
+
+    #[test]
+    fn prune() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(3);
+        map[1] += 1;
+        map[2] += 0;
+        map[3] = 3;
+        map[4] = 0;
+        assert_eq!(map.pruned_len(), 2);
+        let before = map.clone();
+        map.prune();
+        assert_eq!(map.len(), 2);
+        for key in 0..5 {
+            assert_eq!(map[key], before[key]);
+        }
+    }
+
+    #[test]
+    fn get_mut_pruned() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        map[1] = 5;
+        *map.get_mut_pruned(1) -= 4;
+        *map.get_mut_pruned(2) += 0;
+        *map.get_mut_pruned(3) += 1;
+        {
+            let mut guard = map.get_mut_pruned(4);
+            *guard += 1;
+            assert_eq!(*guard, 2);
+            *guard -= 1;
+        }
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[1], 1);
+        assert_eq!(map[3], 2);
+    }
+
+    #[test]
+    fn clone_empty() {
+        let mut map: DefaultHashMap<i32, Vec<i32>> = DefaultHashMap::with_fn(|| vec![1]);
+        map.reserve(100);
+        map[1].push(2);
+        let mut empty = map.clone_empty();
+        assert!(empty.is_empty());
+        assert!(empty.capacity() >= 100);
+        empty[2].push(3);
+        assert_eq!(empty[2], vec![1, 3]);
+        assert_eq!(map[1], vec![1, 2]);
+    }
+
+    #[test]
+    fn semantic_eq() {
+        let mut a: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        let mut b: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        assert!(a.semantic_eq(&b));
+        a[1] += 1;
+        b[2] += 0;
+        assert!(!a.semantic_eq(&b));
+        b[1] = 2;
+        assert!(a.semantic_eq(&b));
+        assert!(b.semantic_eq(&a));
+        b.set_default(2);
+        assert!(!a.semantic_eq(&b));
+    }
+
+    #[test]
+    fn retain_keys() {
+        let mut map: DefaultHashMap<i32, i32> = (0..6).map(|i| (i, i * 10)).collect();
+        map.retain_keys(|k| k % 3 == 0);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[3], 30);
+        assert_eq!(map[4], 0);
+    }
+
+    #[test]
+    fn is_default() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(2);
+        map[1] += 1;
+        map[2] += 0;
+        assert!(!map.is_default(&1));
+        assert!(map.is_default(&2));
+        assert!(map.is_default(&3));
+        assert_eq!(map.pruned_len(), 1);
+    }
+
+    #[test]
+    fn reset() {
+        let mut map: DefaultHashMap<i32, Vec<i32>> = DefaultHashMap::with_fn(|| vec![0]);
+        map[1].push(1);
+        map[2].push(2);
+        assert_eq!(map.reset(&1), Some(vec![0, 1]));
+        assert_eq!(map.reset(&3), None);
+        assert_eq!(map[1], vec![0]);
+        assert_eq!(map.len(), 2);
+        map.reset_all();
+        assert_eq!(map[2], vec![0]);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn incr_decr() {
+        let mut map: DefaultHashMap<&str, i64> = DefaultHashMap::with_default(10);
+        assert_eq!(*map.incr("a"), 11);
+        assert_eq!(*map.incr_by("a", 5), 16);
+        assert_eq!(*map.decr("b"), 9);
+        assert_eq!(*map.decr_by("a", 20), -4);
+        let mut floats: DefaultHashMap<&str, f64> = DefaultHashMap::new();
+        floats.incr_by("x", 0.5);
+        floats.incr("x");
+        assert_eq!(floats["x"], 1.5);
+        let mut small: DefaultHashMap<&str, i8> = DefaultHashMap::new();
+        small.decr("x");
+        assert_eq!(small["x"], -1);
+    }
+
+    #[test]
+    fn try_update() {
+        let mut map: DefaultHashMap<i32, i8> = DefaultHashMap::new();
+        assert_eq!(
+            map.try_update(1, |v| v.checked_sub(100).ok_or(())),
+            Ok(&mut -100)
+        );
+        assert_eq!(map.try_update(1, |v| v.checked_sub(100).ok_or(())), Err(()));
+        assert_eq!(map[1], -100);
+        assert_eq!(map.try_update(2, |_| Err::<i8, _>(())), Err(()));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn add_maps() {
+        let mut total: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        total[0] = 5;
+        total[1] = 5;
+        let mut partial: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        partial[1] = 2;
+        partial[2] = 3;
+        total += partial.clone();
+        assert_eq!(total[0], 5);
+        assert_eq!(total[1], 7);
+        assert_eq!(total[2], 4);
+        assert_eq!(total.len(), 3);
+
+        let sum = partial.clone() + partial;
+        assert_eq!(sum[1], 4);
+        assert_eq!(sum[2], 6);
+        assert_eq!(sum.len(), 2);
+    }
+
+    #[test]
+    fn redacted() {
+        let mut map: DefaultHashMap<&str, i32> = DefaultHashMap::new();
+        map["secret"] = 1;
+        map["hidden"] = 2;
+        let output = format!("{:?}", map.redacted());
+        assert!(!output.contains("secret"));
+        assert!(!output.contains("hidden"));
+        assert!(output.ends_with("}, default: 0 }"));
+        assert_eq!(output.matches("<redacted ").count(), 2);
+        assert_eq!(output, format!("{:?}", map.redacted()));
+    }
+
+    #[test]
+    fn counter_operators() {
+        let mut a: DefaultHashMap<char, i32> = DefaultHashMap::new();
+        a['x'] = 3;
+        a['y'] = 1;
+        a['z'] = 2;
+        let mut b: DefaultHashMap<char, i32> = DefaultHashMap::new();
+        b['x'] = 1;
+        b['y'] = 4;
+        b['w'] = 5;
+
+        let sub = a.clone() - b.clone();
+        assert_eq!(sub[&'x'], 2);
+        assert_eq!(sub[&'z'], 2);
+        assert!(!sub.contains_key(&'y'));
+        assert!(!sub.contains_key(&'w'));
+        assert_eq!(sub.len(), 2);
+
+        let union = a.clone() | b.clone();
+        assert_eq!(union[&'x'], 3);
+        assert_eq!(union[&'y'], 4);
+        assert_eq!(union[&'z'], 2);
+        assert_eq!(union[&'w'], 5);
+        assert_eq!(union.len(), 4);
+
+        let intersection = a & b;
+        assert_eq!(intersection[&'x'], 1);
+        assert_eq!(intersection[&'y'], 1);
+        assert_eq!(intersection.len(), 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn assert_invariants() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(3);
+        map[1] += 1;
+        map.set_default(5);
+        map.assert_invariants();
+    }
+
+    #[test]
+    fn total() {
+        let mut map: DefaultHashMap<i32, f64> = DefaultHashMap::with_default(1.0);
+        assert_eq!(map.total(), 0.0);
+        map[1] += 0.5;
+        map[2] = 2.0;
+        assert_eq!(map.total(), 3.5);
+    }
+
+    #[test]
+    fn max_min_by_value() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        assert_eq!(map.max_by_value(), None);
+        assert_eq!(map.argmin(), None);
+        map[1] = 5;
+        map[2] = -3;
+        map[3] = 8;
+        assert_eq!(map.max_by_value(), Some((&3, &8)));
+        assert_eq!(map.min_by_value(), Some((&2, &-3)));
+        assert_eq!(map.argmax(), Some(&3));
+        assert_eq!(map.argmin(), Some(&2));
+    }
+
+    #[test]
+    fn most_common() {
+        let map: DefaultHashMap<i32, i32> = (0..10).map(|i| (i, (i * 7) % 10)).collect();
+        assert_eq!(map.most_common(3), vec![(&7, &9), (&4, &8), (&1, &7)]);
+        assert_eq!(map.most_common(0), vec![]);
+        assert_eq!(map.most_common(20).len(), 10);
+        assert_eq!(map.most_common(20)[9], (&0, &0));
+    }
+
+    #[test]
+    fn map_values() {
+        let mut map: DefaultHashMap<i32, Vec<i32>> = DefaultHashMap::with_default(vec![1]);
+        map[0].push(2);
+        let lens = map.map_values(|v| v.len());
+        assert_eq!(lens[0], 2);
+        assert_eq!(lens[1], 1);
+        assert_eq!(lens.get_default(), 1);
+        assert_eq!(lens.len(), 1);
+    }
+
+    #[test]
+    fn filter() {
+        let map: DefaultHashMap<i32, i32> = (0..6).map(|i| (i, i * 10)).collect();
+        let even = map.clone().filter(|key, _| key % 2 == 0);
+        assert_eq!(even.len(), 3);
+        assert_eq!(even[4], 40);
+        assert_eq!(even[5], 0);
+
+        let halved = map.filter_map(|_, value| (value >= 30).then_some(value / 2));
+        assert_eq!(halved.len(), 3);
+        assert_eq!(halved[3], 15);
+        assert_eq!(halved[2], 0);
+    }
+
+    #[test]
+    fn merge() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map[1] = 1;
+        map[2] = 2;
+        let mut other: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(7);
+        other[2] = 20;
+        other[3] = 30;
+        let mut conflicts = Vec::new();
+        map.merge(other, |key, mine, theirs| {
+            conflicts.push(*key);
+            *mine += theirs;
+        });
+        assert_eq!(conflicts, vec![2]);
+        assert_eq!(map[1], 1);
+        assert_eq!(map[2], 22);
+        assert_eq!(map[3], 30);
+        assert_eq!(map[4], 0);
+
+        let mut strict: DefaultHashMap<i32, i32> =
+            DefaultHashMap::with_fn(|| -> i32 { panic!("no default needed") });
+        strict.insert(1, 1);
+        strict.merge(defaulthashmap!(1 => 2, 2 => 3), |_, mine, theirs| {
+            *mine += theirs
+        });
+        assert_eq!((strict[1], strict[2]), (3, 3));
+    }
+
+    #[test]
+    fn key_set_operations() {
+        let a: DefaultHashMap<i32, i32> = (0..5).map(|i| (i, i)).collect();
+        let b: DefaultHashMap<i32, String> = (3..8).map(|i| (i, i.to_string())).collect();
+        let mut intersection: Vec<_> = a.intersect_keys(&b).copied().collect();
+        intersection.sort();
+        assert_eq!(intersection, vec![3, 4]);
+        let mut difference: Vec<_> = a.difference_keys(&b).copied().collect();
+        difference.sort();
+        assert_eq!(difference, vec![0, 1, 2]);
+        let mut union: Vec<_> = a.union_keys(&b).copied().collect();
+        union.sort();
+        assert_eq!(union, (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn diff() {
+        let mut old: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        old[1] = 1;
+        old[2] = 2;
+        old[3] = 0;
+        let mut new: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        new[2] = 20;
+        new[3] = 0;
+        new[4] = 4;
+        assert!(old.diff(&old).is_empty());
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![(4, 4)]);
+        assert_eq!(diff.removed, vec![(1, 1)]);
+        assert_eq!(diff.changed, vec![(2, 2, 20)]);
+    }
+
+    #[test]
+    fn apply_diff() {
+        let mut old: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        old[1] = 1;
+        old[2] = 2;
+        old[3] = 3;
+        let mut new = old.clone();
+        new.remove(&1);
+        new[2] = 20;
+        new[4] = 4;
+        let mut patched = old.clone();
+        patched.apply_diff(old.diff(&new));
+        assert_eq!(patched, new);
+
+        let mut defaulted = old.clone();
+        defaulted.apply_diff(MapDiff {
+            added: vec![(5, 0)],
+            removed: vec![],
+            changed: vec![(3, 3, 0)],
+        });
+        assert!(!defaulted.contains_key(&3));
+        assert!(!defaulted.contains_key(&5));
+    }
+
+    #[test]
+    fn group_by() {
+        let groups = DefaultHashMap::group_by(0..10, |i| i % 3);
+        assert_eq!(groups[0], vec![0, 3, 6, 9]);
+        assert_eq!(groups[1], vec![1, 4, 7]);
+        assert_eq!(groups[2], vec![2, 5, 8]);
+        assert_eq!(groups.len(), 3);
+    }
+
+    #[test]
+    fn count_occurrences() {
+        let counts = DefaultHashMap::count_occurrences("hello world".chars());
+        assert_eq!(counts['l'], 3);
+        assert_eq!(counts['o'], 2);
+        assert_eq!(counts['x'], 0);
+        assert_eq!(counts.total(), 11);
+    }
+
+    #[test]
+    fn from_array() {
+        let map: DefaultHashMap<i32, i32> = DefaultHashMap::from([(1, 10), (2, 20)]);
+        assert_eq!(map[1], 10);
+        assert_eq!(map[3], 0);
+        assert_eq!(map.len(), 2);
+
+        let map: DefaultHashMap<i32, i32> = DefaultHashMap::from_array_with_default([(1, 10)], 5);
+        assert_eq!(map[1], 10);
+        assert_eq!(map[3], 5);
+    }
+
+    #[test]
+    fn outer_join() {
+        let mut a: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(-1);
+        a[1] = 1;
+        a[2] = 2;
+        let mut b: DefaultHashMap<i32, &str> = DefaultHashMap::with_default("none");
+        b[2] = "two";
+        b[3] = "three";
+        let mut joined: Vec<_> = a.outer_join(&b).collect();
+        joined.sort();
+        assert_eq!(
+            joined,
+            vec![(&1, &1, &"none"), (&2, &2, &"two"), (&3, &-1, &"three")]
+        );
+    }
+
+    #[test]
+    fn iter_with_defaults() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(-1);
+        map[2] = 20;
+        let values: Vec<_> = map.iter_with_defaults(1..4).collect();
+        assert_eq!(values, vec![(1, &-1), (2, &20), (3, &-1)]);
+        let values: Vec<_> = map.iter_with_defaults(&[2]).collect();
+        assert_eq!(values, vec![(&2, &20)]);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn values_cloned() {
+        let mut map: DefaultHashMap<i32, String> = DefaultHashMap::new();
+        map[1] = "a".to_string();
+        let values: Vec<String> = map.values_cloned().collect();
+        assert_eq!(values, vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn sorted_vec() {
+        let map: DefaultHashMap<i32, i32> = (0..5).rev().map(|i| (i, i * 2)).collect();
+        let sorted = map.to_sorted_vec();
+        assert_eq!(sorted[0], (&0, &0));
+        assert_eq!(sorted[4], (&4, &8));
+        assert_eq!(
+            map.into_sorted_vec(),
+            (0..5).map(|i| (i, i * 2)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn debug() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(3);
+        map[1] = 5;
+        assert_eq!(
+            format!("{:?}", map),
+            "DefaultHashMap { map: {1: 5}, default: 3 }"
+        );
+
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_named_fn("three", || 3);
+        map[1] = 5;
+        assert_eq!(
+            format!("{:?}", map),
+            r#"DefaultHashMap { map: {1: 5}, default: 3, default_fn: "three" }"#
+        );
+        assert!(format!("{:?}", map.redacted()).ends_with(r#"default_fn: "three" }"#));
+    }
+
+    #[test]
+    fn set_default_fn() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map[1] += 1;
+        map.set_default_fn(|| 10);
+        map[2] += 1;
+        assert_eq!(map[1], 1);
+        assert_eq!(map[2], 11);
+        assert_eq!(map[3], 10);
+        assert_eq!(map.get_default(), 10);
+    }
+
+    #[test]
+    fn with_scoped_default() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_named_fn("one", || 1);
+        let len = map.with_scoped_default(5, |map| {
+            map[1] += 1;
+            map.len()
+        });
+        map[2] += 1;
+        assert_eq!(len, 1);
+        assert_eq!(map[1], 6);
+        assert_eq!(map[2], 2);
+        assert!(format!("{:?}", map).contains(r#"default_fn: "one""#));
+    }
+
+    #[test]
+    fn with_scoped_default_panic() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.with_scoped_default(5, |map| {
+                map[1] += 1;
+                panic!("boom");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(map[1], 6);
+        assert_eq!(map[2], 1);
+    }
+
+    #[test]
+    fn macro_fn() {
+        let macro_map: DefaultHashMap<i32, Vec<i32>> = defaulthashmap! { @fn || vec![0] };
+        assert_eq!(macro_map[1], vec![0]);
+        assert!(macro_map.is_empty());
+
+        let mut macro_map: DefaultHashMap<_, _> = defaulthashmap! {
+            @fn || vec![0],
+            1 => vec![],
+            2 => vec![2],
+        };
+        macro_map[3].push(3);
+        assert_eq!(macro_map[1], Vec::<i32>::new());
+        assert_eq!(macro_map[2], vec![2]);
+        assert_eq!(macro_map[3], vec![0, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn stats() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map[1] += 1;
+        map.incr(2);
+        map.incr(2);
+        assert_eq!(map[1] + map[3] + map[4], 1);
+        assert_eq!(map.miss_count(), 2);
+        assert_eq!(map.materialized_keys(), 2);
+        let clone = map.clone();
+        map.reset_stats();
+        assert_eq!(map.miss_count(), 0);
+        assert_eq!(map.materialized_keys(), 0);
+        assert_eq!(clone.miss_count(), 2);
+    }
+
+    #[test]
+    fn missing_hook() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let keys = Rc::new(RefCell::new(Vec::new()));
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map[1] = 1;
+        let log = keys.clone();
+        map.set_missing_hook(move |key| log.borrow_mut().push(*key));
+        assert_eq!(map[1] + map[2] + *map.get(3), 1);
+        map[4] += 1;
+        map[4] += 1;
+        map.incr(5);
+        let clone = map.clone();
+        assert_eq!(clone[6], 0);
+        map.clear_missing_hook();
+        assert_eq!(map[7], 0);
+        assert_eq!(*keys.borrow(), vec![2, 4, 5, 6]);
+    }
+
+    #[test]
+    fn try_get_mut() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map[1] = 1;
+        let fail = || Err::<i32, &str>("failed");
+        assert_eq!(map.try_get_mut(1, fail), Ok(&mut 1));
+        assert_eq!(map.try_get_mut(2, fail), Err("failed"));
+        assert!(!map.contains_key(&2));
+        *map.try_get_mut(2, || Ok::<_, ()>(5)).unwrap() += 1;
+        assert_eq!(map[2], 6);
+    }
+
+    #[test]
+    #[cfg(feature = "async")]
+    fn get_or_insert_async() {
+        use crate::test_util::block_on;
+
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::new();
+        map[1] = 1;
+        let mut fetches = 0;
+        block_on(async {
+            assert_eq!(
+                *map.get_or_insert_async(1, |_| async { unreachable!() })
+                    .await,
+                1
+            );
+            let value = map
+                .get_or_insert_async(2, |key| {
+                    fetches += 1;
+                    let key = *key;
+                    async move { key * 10 }
+                })
+                .await;
+            *value += 1;
+        });
+        assert_eq!(fetches, 1);
+        assert_eq!(map[2], 21);
+    }
+
+    #[test]
+    fn with_default_borrowed() {
+        let fallback = String::from("none");
+        let mut map: DefaultHashMap<i32, &str> = DefaultHashMap::with_default(fallback.as_str());
+        *map.get_mut(1) = "one";
+        map.get_mut(2);
+        assert_eq!(map[1], "one");
+        assert_eq!(map[2], "none");
+        assert_eq!(map.clone()[3], "none");
+    }
+
+    #[test]
+    fn clone_shares_default_fn() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct CountClones(Rc<Cell<u32>>);
+
+        impl Clone for CountClones {
+            fn clone(&self) -> Self {
+                self.0.set(self.0.get() + 1);
+                CountClones(Rc::clone(&self.0))
+            }
+        }
+
+        let clones = Rc::new(Cell::new(0));
+        let counter = CountClones(Rc::clone(&clones));
+        let map: DefaultHashMap<i32, u32> = DefaultHashMap::with_fn(move || counter.0.get() + 7);
+        let copy = map.clone();
+        assert_eq!(copy[1], 7);
+        assert_eq!(clones.get(), 0);
+    }
+
+    #[test]
+    fn with_fn_lazy() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let mut map: DefaultHashMap<i32, u32> = DefaultHashMap::with_fn(move || {
+            counter.set(counter.get() + 1);
+            5
+        });
+        map.insert(1, 1);
+        assert_eq!(calls.get(), 0);
+        assert_eq!(map[2], 5);
+        assert_eq!(map[3], 5);
+        assert_eq!(calls.get(), 1);
+        *map.get_mut(4) += 1;
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn atomic_counters() {
+        use std::sync::atomic::AtomicU64;
+
+        let mut map: DefaultHashMap<i32, AtomicU64> = DefaultHashMap::new();
+        assert_eq!(map.fetch_add(&1, 5), None);
+        assert_eq!(map.fetch_add_or_insert(1, 5), 0);
+        assert_eq!(map.fetch_add(&1, 5), Some(5));
+        assert_eq!(map.load(&1), 10);
+        assert_eq!(map.load(&2), 0);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn from_btreemap() {
+        let mut other: crate::DefaultBTreeMap<i32, Vec<i32>> =
+            crate::DefaultBTreeMap::with_fn(|| vec![0]);
+        other[1].push(1);
+        let mut map = DefaultHashMap::from(other);
+        assert_eq!(map[1], [0, 1]);
+        assert_eq!(map[2], [0]);
+        map[3].push(3);
+        assert_eq!(map[3], [0, 3]);
+    }
+
+    #[test]
+    fn display_empty() {
+        let map: DefaultHashMap<i32, &str> = DefaultHashMap::with_default("none");
+        assert_eq!(map.to_string(), "{} (default: none)");
+        assert_eq!(format!("{:#}", map), "(default: none)");
+    }
+
+    #[test]
+    fn sorted_debug() {
+        let mut map: DefaultHashMap<&str, Vec<i32>> =
+            DefaultHashMap::with_named_fn("empty", Vec::new);
+        map["b"].push(2);
+        map["a"].push(1);
+        assert_eq!(
+            format!("{:?}", map.sorted_debug()),
+            r#"DefaultHashMap { map: {"a": [1], "b": [2]}, default: [], default_fn: "empty" }"#
+        );
+        assert_eq!(
+            format!("{:#?}", map.sorted_debug()).lines().nth(2),
+            Some(r#"        "a": ["#)
+        );
+    }
+
+    #[test]
+    fn from_keys() {
+        let map: DefaultHashMap<i32, i32> = DefaultHashMap::from_keys(1..4, 5);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[3], 5);
+        assert_eq!(map[4], 5);
+
+        let mut map: DefaultHashMap<i32, Vec<i32>> =
+            DefaultHashMap::from_keys_with_fn([1, 2, 1], || vec![0]);
+        map[1].push(1);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[1], [0, 1]);
+        assert_eq!(map[2], [0]);
+    }
+
+    #[test]
+    fn update_replaces() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(-1);
+        map.insert(1, 10);
+        map.insert(2, 20);
+        map.update_from(vec![(2, 21), (3, 30)]);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[2], 21);
+
+        let mut other: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(7);
+        other.insert(1, 11);
+        map.update_from_map(&other);
+        assert_eq!(map[1], 11);
+        assert_eq!(map[3], 30);
+        assert_eq!(map[4], -1);
+        assert_eq!(other.len(), 1);
+    }
+
+    #[test]
+    fn get_or_fallback() {
+        let mut map: DefaultHashMap<String, Vec<i32>> = DefaultHashMap::new();
+        map[String::from("a")].push(1);
+        let fallback = vec![0];
+        assert_eq!(map.get_or("a", &fallback), &[1]);
+        assert_eq!(map.get_or("b", &fallback), &fallback);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn get_or_insert_with() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        *map.get_or_insert_with(1, || 10) += 1;
+        assert_eq!(*map.get_or_insert_with(1, || unreachable!()), 11);
+        *map.get_mut(2) += 1;
+        assert_eq!(map[2], 2);
+        assert_eq!(map[3], 1);
+    }
+
+    #[test]
+    fn modify_chains() {
+        let mut map: DefaultHashMap<i32, Vec<i32>> = DefaultHashMap::with_default(vec![0]);
+        map.modify(1, |v| v.push(1)).modify(2, |v| v.clear());
+        map.modify(1, |v| v.push(2));
+        assert_eq!(map[1], [0, 1, 2]);
+        assert!(map[2].is_empty());
+        assert_eq!(map[3], [0]);
+    }
+
+    #[test]
+    fn update_or_remove() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        map.update_or_remove(1, |v| Some(v + 1));
+        map.update_or_remove(2, |_| None);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[1], 2);
+
+        map.update_or_remove(1, |v| if v > 1 { Some(v - 1) } else { None });
+        assert_eq!(map[1], 1);
+        map.update_or_remove(1, |v| if v > 1 { Some(v - 1) } else { None });
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn update_or_remove_panic() {
+        let mut map: DefaultHashMap<i32, i32> = DefaultHashMap::with_default(1);
+        map[1] = 5;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.update_or_remove(1, |_| panic!("boom"));
+        }));
+        assert!(result.is_err());
+        assert!(map.contains_key(&1));
+        assert_eq!(map[1], 1);
+    }
+
+    #[test]
+    fn partition() {
+        let mut map: DefaultHashMap<i32, Vec<i32>> = DefaultHashMap::with_fn(|| vec![0]);
+        for i in 0..6 {
+            map[i].push(i);
+        }
+        let (even, odd) = map.partition(|k, _| k % 2 == 0);
+        assert_eq!(even.len(), 3);
+        assert_eq!(odd.len(), 3);
+        assert_eq!(even[4], [0, 4]);
+        assert_eq!(even[5], [0]);
+        assert_eq!(odd[5], [0, 5]);
+        assert_eq!(odd[4], [0]);
+    }
+
+    #[test]
+    fn value_stats() {
+        let mut map: DefaultHashMap<i32, u64> = DefaultHashMap::with_default(1);
+        assert_eq!(map.value_stats(), None);
+        map.insert(1, 3);
+        map.insert(2, 5);
+        let stats = map.value_stats().unwrap();
+        assert_eq!((stats.count, stats.mean, stats.variance), (2, 4.0, 1.0));
+        let stats = map.value_stats_over([1, 2, 3]).unwrap();
+        assert_eq!(
+            (stats.count, stats.min, stats.max, stats.mean),
+            (3, 1.0, 5.0, 3.0)
+        );
+        assert_eq!(map.value_stats_over(Vec::<i32>::new()), None);
+    }
+
+    #[test]
+    fn normalize() {
+        let mut map: DefaultHashMap<i32, i64> = DefaultHashMap::with_default(-2);
+        map.insert(1, 1);
+        map.insert(2, -5);
+        map.insert(3, 8);
+        let mut normalized = map.normalized();
+        assert_eq!(normalized[1], 0.25);
+        assert_eq!(normalized[2], -1.25);
+        assert_eq!(normalized[4], -2.0);
+        normalized.insert(4, 1.0);
+        normalized.normalize();
+        assert_eq!(normalized[1], 0.125);
+        assert_eq!(normalized[5], -2.0);
+
+        let empty: DefaultHashMap<i32, f64> = DefaultHashMap::new();
+        assert!(empty.normalized().is_empty());
+
+        let mut zeros: DefaultHashMap<i32, f64> = DefaultHashMap::new();
+        zeros.insert(1, 0.0);
+        zeros.insert(2, 0.0);
+        zeros.normalize();
+        assert!(zeros[1].is_nan() && zeros[2].is_nan());
+        assert_eq!(zeros[3], 0.0);
+    }
+
+    #[test]
+    fn sorted_by_value() {
+        let map: DefaultHashMap<i32, i32> = (0..5).map(|i| (i, (i * 3) % 5)).collect();
+        let ascending: Vec<_> = map.iter_sorted_by_value().map(|(_, v)| *v).collect();
+        assert_eq!(ascending, [0, 1, 2, 3, 4]);
+        let descending: Vec<_> = map.iter_sorted_by_value_desc().map(|(k, _)| *k).collect();
+        assert_eq!(descending, [3, 1, 4, 2, 0]);
+        let keys: Vec<_> = map.keys_sorted_by_value().rev().copied().collect();
+        assert_eq!(keys, descending);
+    }
+
     #[cfg(feature = "with-serde")]
     mod serde_tests {
         use super::*;
@@ -563,6 +3468,48 @@ mod tests {
             assert_eq!(h2["answer"], 42);
         }
 
+        #[test]
+        #[cfg(debug_assertions)]
+        #[should_panic(expected = "disagrees with the values created for missing keys")]
+        fn assert_invariants_deserialized_default() {
+            let map: DefaultHashMap<&str, i32> =
+                serde_json::from_str(r#"{"map": {}, "default": 15}"#).unwrap();
+            map.assert_invariants();
+        }
+
+        #[test]
+        fn deserialize_plain_map() {
+            let h: DefaultHashMap<String, i32> =
+                serde_json::from_str(r#"{"foo": 3, "bar": 5}"#).unwrap();
+            assert_eq!(h.len(), 2);
+            assert_eq!(h[String::from("foo")], 3);
+            assert_eq!(h[String::from("baz")], 0);
+
+            let h: DefaultHashMap<&str, i32> = serde_json::from_str("{}").unwrap();
+            assert!(h.is_empty());
+
+            let h: DefaultHashMap<&str, i32> =
+                serde_json::from_str(r#"{"map": {"a": 1}, "default": 2}"#).unwrap();
+            assert_eq!(h["a"] + h["b"], 3);
+            let err =
+                serde_json::from_str::<DefaultHashMap<&str, i32>>(r#"{"map": {}}"#).unwrap_err();
+            assert!(err.to_string().starts_with("missing field `default`"));
+
+            let h: DefaultHashMap<u32, u32> = serde_json::from_str(r#"{"1": 5, "2": 6}"#).unwrap();
+            assert_eq!((h[1], h[2], h[3]), (5, 6, 0));
+
+            let h: DefaultHashMap<String, u32> =
+                serde_json::from_str(r#"{"default": 3, "x": 1, "map": 2}"#).unwrap();
+            assert_eq!(h.len(), 3);
+            assert_eq!(h[String::from("default")], 3);
+            assert_eq!(h[String::from("map")], 2);
+            assert_eq!(h[String::from("y")], 0);
+
+            let h: DefaultHashMap<&str, i32> =
+                serde_json::from_str(r#"{"default": 2, "map": {"a": 1}}"#).unwrap();
+            assert_eq!(h["a"] + h["b"], 3);
+        }
+
         #[test]
         fn std_hashmap() {
             let h1: DefaultHashMap<i32, i32> = defaulthashmap!(1=> 10, 2=> 20);
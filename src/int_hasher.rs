@@ -0,0 +1,152 @@
+use std::fmt;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::marker::PhantomData;
+
+use crate::DefaultHashMap;
+
+/// A `DefaultHashMap` for integer keys that hashes them with a single multiplication. This is
+/// only available with the `int-hasher` feature.
+///
+/// Hashing integer keys with SipHash is a significant part of the cost of a counting loop. For
+/// keys that are already well distributed, such as ids, this map only multiplies the integer by
+/// a constant. The multiplication spreads small keys over all bits of the hash, because the
+/// `HashMap` uses the top bits to tell apart keys in the same group. It's not resistant against
+/// keys that are chosen to collide, so don't use it for keys that come from untrusted input.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut counts: DefaultIntMap<u64, u32> = DefaultIntMap::default();
+/// for id in [3, 7, 3, 3] {
+///     counts[id] += 1;
+/// }
+/// assert_eq!(counts[3], 3);
+/// assert_eq!(counts[8], 0);
+/// ```
+///
+/// Since the hasher is a type parameter of `DefaultHashMap`, hashers from other crates, such as
+/// `nohash_hasher::BuildNoHashHasher`, can be used in the same way.
+pub type DefaultIntMap<K, V> = DefaultHashMap<K, V, BuildIntHasher<K>>;
+
+/// The `BuildHasher` used by [`DefaultIntMap`].
+pub type BuildIntHasher<T> = BuildHasherDefault<IntHasher<T>>;
+
+/// Marker trait for the key types that [`IntHasher`] can hash, i.e. types that hash themselves
+/// by writing a single integer.
+pub trait IntKey {}
+
+macro_rules! impl_int_key {
+    ($($t:ty),*) => {
+        $(impl IntKey for $t {})*
+    };
+}
+
+impl_int_key!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, char);
+
+/// A `Hasher` that multiplies the integer that is written to it by a constant. Keys that are
+/// hashed as bytes instead of as a single integer are mixed in byte by byte, which is slower
+/// but still correct.
+pub struct IntHasher<T> {
+    hash: u64,
+    _key: PhantomData<fn(T)>,
+}
+
+impl<T> Default for IntHasher<T> {
+    fn default() -> Self {
+        IntHasher {
+            hash: 0,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for IntHasher<T> {
+    fn clone(&self) -> Self {
+        IntHasher {
+            hash: self.hash,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for IntHasher<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntHasher")
+            .field("hash", &self.hash)
+            .finish()
+    }
+}
+
+/// An odd constant with the bits well mixed, 2^64 divided by the golden ratio.
+const MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl<T> IntHasher<T> {
+    /// Mixes `n` into the hash. For a key that writes a single integer this is just a
+    /// multiplication, because the hash starts out as zero.
+    #[inline]
+    fn add(&mut self, n: u64) {
+        self.hash = (self.hash.rotate_left(26) ^ n).wrapping_mul(MULTIPLIER);
+    }
+}
+
+impl<T: IntKey> Hasher for IntHasher<T> {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.add(u64::from(byte));
+        }
+    }
+
+    #[inline]
+    fn write_u8(&mut self, n: u8) {
+        self.add(u64::from(n));
+    }
+
+    #[inline]
+    fn write_u16(&mut self, n: u16) {
+        self.add(u64::from(n));
+    }
+
+    #[inline]
+    fn write_u32(&mut self, n: u32) {
+        self.add(u64::from(n));
+    }
+
+    #[inline]
+    fn write_u64(&mut self, n: u64) {
+        self.add(n);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasher, Hash, Hasher};
+
+    use super::{BuildIntHasher, IntHasher};
+
+    #[test]
+    fn spreads_small_keys() {
+        let build = BuildIntHasher::<u32>::default();
+        let tags: std::collections::HashSet<u64> =
+            (0..16u32).map(|key| build.hash_one(key) >> 57).collect();
+        assert!(tags.len() > 8);
+        assert_ne!(build.hash_one(1u32), build.hash_one(2u32));
+    }
+
+    #[test]
+    fn hashes_bytes() {
+        let mut a = IntHasher::<u8>::default();
+        "ab".hash(&mut a);
+        let mut b = IntHasher::<u8>::default();
+        "ba".hash(&mut b);
+        assert_ne!(a.finish(), b.finish());
+    }
+}
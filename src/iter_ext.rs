@@ -0,0 +1,49 @@
+use std::hash::Hash;
+
+use crate::DefaultHashMap;
+
+/// An extension trait for iterators to collect them directly into a `DefaultHashMap`.
+///
+/// ```
+/// # use defaultmap::*;
+/// let counts = "hello world".chars().filter(|c| c.is_alphabetic()).counts();
+/// assert_eq!(counts['l'], 3);
+/// assert_eq!(counts['x'], 0);
+///
+/// let by_len = ["a", "bb", "cc"].into_iter().group_by_default(|word| word.len());
+/// assert_eq!(by_len[2], vec!["bb", "cc"]);
+///
+/// let map = [("a", 1), ("b", 2)].into_iter().into_default_hashmap();
+/// assert_eq!(map["b"], 2);
+/// assert_eq!(map["c"], 0);
+/// ```
+pub trait DefaultMapIterExt: Iterator + Sized {
+    /// Counts how often every item occurs, see [`DefaultHashMap::count_occurrences`].
+    fn counts(self) -> DefaultHashMap<Self::Item, usize>
+    where
+        Self::Item: Eq + Hash,
+    {
+        DefaultHashMap::count_occurrences(self)
+    }
+
+    /// Collects the key-value pairs into a `DefaultHashMap` with `V::default()` as the default.
+    fn into_default_hashmap<K, V>(self) -> DefaultHashMap<K, V>
+    where
+        Self: Iterator<Item = (K, V)>,
+        K: Eq + Hash,
+        V: Default,
+    {
+        self.collect()
+    }
+
+    /// Groups the items by the key that `f` returns for them, see [`DefaultHashMap::group_by`].
+    fn group_by_default<K, F>(self, f: F) -> DefaultHashMap<K, Vec<Self::Item>>
+    where
+        K: Eq + Hash,
+        F: FnMut(&Self::Item) -> K,
+    {
+        DefaultHashMap::group_by(self, f)
+    }
+}
+
+impl<I: Iterator> DefaultMapIterExt for I {}
@@ -67,12 +67,62 @@
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 #![cfg_attr(any(not(docsrs), ci), deny(rustdoc::all))]
 
+mod bounded;
+mod bucketed;
+mod case_insensitive;
+mod chain;
+mod composite_key;
 mod default_fn;
+mod default_map;
+mod diff;
+mod error;
+mod frozen;
+mod grid;
+mod iter_ext;
+mod materializing;
+mod missing;
+mod multimap;
+mod nested;
+mod one;
+mod redacted;
+mod sharded;
+mod stats;
+mod sync_map;
+mod try_default;
+mod value_stats;
 
-pub use default_fn::DefaultFn;
+pub use bounded::BoundedDefaultMap;
+pub use bucketed::BucketedMaps;
+pub use case_insensitive::CaseInsensitive;
+pub use chain::ChainDefaultMap;
+pub use composite_key::{CompositeKey, RangeByFirst};
+pub use default_fn::{DefaultFn, TryDefaultFn};
+pub use default_map::DefaultMap;
+pub use diff::MapDiff;
+pub use error::OccupiedError;
+pub use frozen::FrozenDefaultMap;
+pub use grid::DefaultGrid2D;
+pub use iter_ext::DefaultMapIterExt;
+pub use materializing::MaterializingDefaultMap;
+pub use multimap::{DefaultMultiMap, DefaultSetMultiMap};
+pub use nested::{NestedDefaultMap, NestedPaths};
+pub use one::One;
+pub use redacted::Redacted;
+pub use sharded::ShardedDefaultMap;
+pub use sync_map::DefaultSyncMap;
+pub use try_default::TryDefaultMap;
+pub use value_stats::{AsF64, ValueStats};
 
-mod btreemap;
-mod hashmap;
+pub mod btreemap;
+pub mod hashmap;
+#[cfg(feature = "int-hasher")]
+mod int_hasher;
+#[cfg(feature = "with-serde")]
+pub mod serde_helpers;
+#[cfg(all(test, feature = "async"))]
+mod test_util;
 
 pub use btreemap::DefaultBTreeMap;
 pub use hashmap::DefaultHashMap;
+#[cfg(feature = "int-hasher")]
+pub use int_hasher::{BuildIntHasher, DefaultIntMap, IntHasher, IntKey};
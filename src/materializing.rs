@@ -0,0 +1,194 @@
+use std::borrow::Borrow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::{Index, IndexMut};
+use std::rc::Rc;
+
+use crate::DefaultFn;
+
+/// A map where reading a missing key also inserts the default for it, like `__getitem__` of
+/// Python's `defaultdict` does.
+///
+/// This is the opt-in alternative to `DefaultHashMap`, whose `get` and `Index` never change the
+/// map. It's mostly useful when porting Python code that relies on reads creating keys. The map
+/// uses interior mutability, so it can't be shared between threads.
+///
+/// ```
+/// # use defaultmap::*;
+/// let seen: MaterializingDefaultMap<&str, Vec<u32>> = MaterializingDefaultMap::new();
+/// assert!(seen["a"].is_empty());
+/// assert!(seen.contains_key("a"));
+/// assert_eq!(seen.len(), 1);
+/// ```
+pub struct MaterializingDefaultMap<K: Eq + Hash, V> {
+    // The values are reference counted, only so they stay at the same address when the map
+    // grows. They are never actually shared.
+    map: RefCell<HashMap<K, Rc<V>>>,
+    default_fn: Box<dyn DefaultFn<V>>,
+}
+
+impl<K: Eq + Hash, V: Default> MaterializingDefaultMap<K, V> {
+    /// Creates an empty `MaterializingDefaultMap` with the default of `V` as the default for
+    /// missing keys.
+    pub fn new() -> MaterializingDefaultMap<K, V> {
+        MaterializingDefaultMap {
+            map: RefCell::new(HashMap::new()),
+            default_fn: Box::new(|| V::default()),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Default> Default for MaterializingDefaultMap<K, V> {
+    /// The `default()` method is equivalent to `MaterializingDefaultMap::new()`.
+    fn default() -> MaterializingDefaultMap<K, V> {
+        MaterializingDefaultMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone + 'static> MaterializingDefaultMap<K, V> {
+    /// Creates an empty `MaterializingDefaultMap` with `default` as the default for missing keys.
+    pub fn with_default(default: V) -> MaterializingDefaultMap<K, V> {
+        MaterializingDefaultMap::with_fn(move || default.clone())
+    }
+}
+
+impl<K: Eq + Hash, V> MaterializingDefaultMap<K, V> {
+    /// Creates an empty `MaterializingDefaultMap` with `default_fn` as the default value
+    /// generation function for missing keys.
+    pub fn with_fn(default_fn: impl DefaultFn<V> + 'static) -> MaterializingDefaultMap<K, V> {
+        MaterializingDefaultMap {
+            map: RefCell::new(HashMap::new()),
+            default_fn: Box::new(default_fn),
+        }
+    }
+
+    /// Returns a reference to the value stored for the provided key. If there is no value stored
+    /// for the key the default value is inserted for it first, even though the map is only
+    /// borrowed immutably.
+    pub fn get(&self, key: K) -> &V {
+        let mut map = self.map.borrow_mut();
+        let value: *const V = &**map
+            .entry(key)
+            .or_insert_with(|| Rc::new(self.default_fn.call()));
+        drop(map);
+        // SAFETY: The value is stored in its own allocation, which doesn't move when the map
+        // grows. Values are only dropped or mutated through `&mut self`, which can't happen while
+        // the returned reference borrows `self`.
+        unsafe { &*value }
+    }
+
+    /// Returns a mutable reference to the value stored for the provided key. If there is no value
+    /// stored for the key the default value is first inserted for this key.
+    pub fn get_mut(&mut self, key: K) -> &mut V {
+        let value = self
+            .map
+            .get_mut()
+            .entry(key)
+            .or_insert_with(|| Rc::new(self.default_fn.call()));
+        Rc::get_mut(value).expect("values are never shared")
+    }
+
+    /// Returns `true` if a value is stored for the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.borrow().contains_key(key)
+    }
+
+    /// Removes the key from the map and returns its value, if it had one.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.get_mut().remove(key).map(unwrap_value)
+    }
+
+    /// Returns the number of values stored in the map.
+    pub fn len(&self) -> usize {
+        self.map.borrow().len()
+    }
+
+    /// Returns `true` if no values are stored in the map.
+    pub fn is_empty(&self) -> bool {
+        self.map.borrow().is_empty()
+    }
+
+    /// Converts the map into a `HashMap` with all values that were stored, including the ones
+    /// that were inserted by reads.
+    pub fn into_map(self) -> HashMap<K, V> {
+        self.map
+            .into_inner()
+            .into_iter()
+            .map(|(key, value)| (key, unwrap_value(value)))
+            .collect()
+    }
+}
+
+fn unwrap_value<V>(value: Rc<V>) -> V {
+    Rc::try_unwrap(value).ok().expect("values are never shared")
+}
+
+/// Implements the `Index` trait so you can do `map[key]`. This inserts the default when the key
+/// is missing, just like `get` does.
+impl<K: Eq + Hash, V> Index<K> for MaterializingDefaultMap<K, V> {
+    type Output = V;
+
+    fn index(&self, index: K) -> &V {
+        self.get(index)
+    }
+}
+
+/// Implements the `IndexMut` trait so you can do `map[key] = val`.
+impl<K: Eq + Hash, V> IndexMut<K> for MaterializingDefaultMap<K, V> {
+    fn index_mut(&mut self, index: K) -> &mut V {
+        self.get_mut(index)
+    }
+}
+
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug> fmt::Debug for MaterializingDefaultMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaterializingDefaultMap")
+            .field("map", &self.map.borrow())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MaterializingDefaultMap;
+
+    #[test]
+    fn reads_insert() {
+        let map: MaterializingDefaultMap<i32, Vec<i32>> = MaterializingDefaultMap::new();
+        let first = map.get(1);
+        // Grow the map a lot, while holding on to the first reference.
+        let others: Vec<_> = (2..100).map(|i| map.get(i)).collect();
+        assert!(first.is_empty());
+        assert_eq!(others.len(), 98);
+        assert_eq!(map.len(), 99);
+
+        let mut map = map;
+        map[1].push(1);
+        assert_eq!(map[1], vec![1]);
+        assert_eq!(map.remove(&1), Some(vec![1]));
+        assert!(!map.contains_key(&1));
+        let std_map = map.into_map();
+        assert_eq!(std_map.len(), 98);
+        assert_eq!(std_map[&2], Vec::<i32>::new());
+    }
+
+    #[test]
+    fn with_default() {
+        let map: MaterializingDefaultMap<&str, i32> = MaterializingDefaultMap::with_default(3);
+        assert_eq!(map["a"], 3);
+        assert_eq!(
+            format!("{:?}", map),
+            r#"MaterializingDefaultMap { map: {"a": 3} }"#
+        );
+    }
+}
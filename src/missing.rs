@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use crate::stats::Stats;
+
+/// A function that is called with the key whenever the default is used for a missing key.
+pub(crate) type MissingHook<K> = Arc<dyn Fn(&K)>;
+
+/// Keeps track of the defaults that a map uses for missing keys, by updating its statistics and
+/// calling the hook that was set with `set_missing_hook`.
+pub(crate) struct Missing<K> {
+    pub(crate) stats: Stats,
+    hook: Option<MissingHook<K>>,
+}
+
+impl<K> Missing<K> {
+    pub(crate) fn set_hook(&mut self, hook: Option<MissingHook<K>>) {
+        self.hook = hook;
+    }
+
+    /// Records that the default was returned for a missing key. The hook is only called when the
+    /// key itself is known, and not just a borrowed form of it.
+    #[inline]
+    pub(crate) fn record_miss(&self, key: Option<&K>) {
+        self.stats.record_miss();
+        if let (Some(hook), Some(key)) = (&self.hook, key) {
+            hook(key);
+        }
+    }
+
+    /// Records that the default was inserted for a missing key.
+    #[inline]
+    pub(crate) fn record_materialized(&self, key: &K) {
+        self.stats.record_materialized();
+        if let Some(hook) = &self.hook {
+            hook(key);
+        }
+    }
+}
+
+impl<K> Default for Missing<K> {
+    fn default() -> Self {
+        Missing {
+            stats: Stats::default(),
+            hook: None,
+        }
+    }
+}
+
+impl<K> Clone for Missing<K> {
+    fn clone(&self) -> Self {
+        Missing {
+            stats: self.stats.clone(),
+            hook: self.hook.clone(),
+        }
+    }
+}
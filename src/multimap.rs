@@ -0,0 +1,345 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::hash_set::{Intersection, Union};
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::iter::FromIterator;
+
+use crate::DefaultHashMap;
+
+/// A map from keys to lists of values, built on a `DefaultHashMap<K, Vec<V>>`.
+///
+/// Keys without values are not stored, so removing the last value of a key removes the key too.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut synonyms: DefaultMultiMap<&str, &str> = DefaultMultiMap::new();
+/// for (l, r) in [("nice", "sweet"), ("nice", "good"), ("sweet", "candy")] {
+///     synonyms.insert(l, r);
+///     synonyms.insert(r, l);
+/// }
+///
+/// assert_eq!(synonyms.get("nice"), ["sweet", "good"]);
+/// assert!(synonyms.contains("sweet", &"candy"));
+/// assert!(synonyms.get("evil").is_empty());
+/// assert_eq!(synonyms.iter_pairs().count(), 6);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DefaultMultiMap<K: Eq + Hash, V> {
+    map: DefaultHashMap<K, Vec<V>>,
+}
+
+impl<K: Eq + Hash, V> DefaultMultiMap<K, V> {
+    /// Creates an empty `DefaultMultiMap`.
+    pub fn new() -> DefaultMultiMap<K, V> {
+        DefaultMultiMap {
+            map: DefaultHashMap::new(),
+        }
+    }
+
+    /// Adds a value to the list of values of the key.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.map[key].push(value);
+    }
+
+    /// Returns the values of the key, which is empty if the key has none.
+    pub fn get<Q>(&self, key: &Q) -> &[V]
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.get::<Q, _>(key)
+    }
+
+    /// Returns `true` if `value` is one of the values of the key.
+    pub fn contains<Q>(&self, key: &Q, value: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: PartialEq,
+    {
+        self.get(key).contains(value)
+    }
+
+    /// Removes the first occurrence of `value` from the values of the key, and removes the key
+    /// when it has no values left. Returns `true` if the value was found.
+    pub fn remove_value<Q>(&mut self, key: &Q, value: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+        V: PartialEq,
+    {
+        let Some((key, mut values)) = self.map.remove_entry(key) else {
+            return false;
+        };
+        let position = values.iter().position(|v| v == value);
+        if let Some(position) = position {
+            values.remove(position);
+        }
+        if !values.is_empty() {
+            self.map.insert(key, values);
+        }
+        position.is_some()
+    }
+
+    /// Removes the key and returns all of its values.
+    pub fn remove<Q>(&mut self, key: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.remove(key).unwrap_or_default()
+    }
+
+    /// Returns an iterator over all key-value pairs, so keys with multiple values are returned
+    /// multiple times.
+    pub fn iter_pairs(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key, value)))
+    }
+
+    /// Returns the number of keys that have values.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if no key has any values.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns a reference to the underlying map.
+    pub fn as_map(&self) -> &DefaultHashMap<K, Vec<V>> {
+        &self.map
+    }
+
+    /// Converts the multimap into the underlying map.
+    pub fn into_map(self) -> DefaultHashMap<K, Vec<V>> {
+        self.map
+    }
+}
+
+impl<K: Eq + Hash, V> Default for DefaultMultiMap<K, V> {
+    /// The `default()` method is equivalent to `DefaultMultiMap::new()`.
+    fn default() -> DefaultMultiMap<K, V> {
+        DefaultMultiMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V> FromIterator<(K, V)> for DefaultMultiMap<K, V> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut map = DefaultMultiMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+/// A map from keys to sets of values, built on a `DefaultHashMap<K, HashSet<V>>`.
+///
+/// Unlike [`DefaultMultiMap`] a value is stored at most once per key, which is useful for
+/// building reverse indexes. Keys without values are not stored.
+///
+/// ```
+/// # use defaultmap::*;
+/// let documents = [(1, "the cat sat"), (2, "the dog sat"), (3, "the cat ran")];
+/// let mut index: DefaultSetMultiMap<&str, u32> = DefaultSetMultiMap::new();
+/// for (id, text) in documents {
+///     for word in text.split(' ') {
+///         index.insert(word, id);
+///     }
+/// }
+///
+/// let mut cats_that_sat: Vec<_> = index.intersection("cat", "sat").collect();
+/// cats_that_sat.sort();
+/// assert_eq!(cats_that_sat, vec![&1]);
+/// assert_eq!(index.union("dog", "ran").count(), 2);
+/// assert!(index.get("bird").is_empty());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DefaultSetMultiMap<K: Eq + Hash, V: Eq + Hash> {
+    map: DefaultHashMap<K, HashSet<V>>,
+}
+
+impl<K: Eq + Hash, V: Eq + Hash> DefaultSetMultiMap<K, V> {
+    /// Creates an empty `DefaultSetMultiMap`.
+    pub fn new() -> DefaultSetMultiMap<K, V> {
+        DefaultSetMultiMap {
+            map: DefaultHashMap::new(),
+        }
+    }
+
+    /// Adds a value to the set of values of the key. Returns `false` if the key already had this
+    /// value.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        self.map[key].insert(value)
+    }
+
+    /// Returns the values of the key, which is empty if the key has none.
+    pub fn get<Q>(&self, key: &Q) -> &HashSet<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.get::<Q, _>(key)
+    }
+
+    /// Returns `true` if `value` is one of the values of the key.
+    pub fn contains<Q>(&self, key: &Q, value: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(key).contains(value)
+    }
+
+    /// Removes `value` from the values of the key, and removes the key when it has no values
+    /// left. Returns `true` if the value was found.
+    pub fn remove_value<Q>(&mut self, key: &Q, value: &V) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        let Some((key, mut values)) = self.map.remove_entry(key) else {
+            return false;
+        };
+        let removed = values.remove(value);
+        if !values.is_empty() {
+            self.map.insert(key, values);
+        }
+        removed
+    }
+
+    /// Removes the key and returns all of its values.
+    pub fn remove<Q>(&mut self, key: &Q) -> HashSet<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.remove(key).unwrap_or_default()
+    }
+
+    /// Returns an iterator over the values that both keys have.
+    pub fn intersection<Q>(&self, a: &Q, b: &Q) -> Intersection<'_, V, RandomState>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(a).intersection(self.get(b))
+    }
+
+    /// Returns an iterator over the values that at least one of the keys has, without duplicates.
+    pub fn union<Q>(&self, a: &Q, b: &Q) -> Union<'_, V, RandomState>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.get(a).union(self.get(b))
+    }
+
+    /// Returns an iterator over all key-value pairs, so keys with multiple values are returned
+    /// multiple times.
+    pub fn iter_pairs(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key, value)))
+    }
+
+    /// Returns the number of keys that have values.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if no key has any values.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns a reference to the underlying map.
+    pub fn as_map(&self) -> &DefaultHashMap<K, HashSet<V>> {
+        &self.map
+    }
+
+    /// Converts the multimap into the underlying map.
+    pub fn into_map(self) -> DefaultHashMap<K, HashSet<V>> {
+        self.map
+    }
+}
+
+impl<K: Eq + Hash, V: Eq + Hash> Default for DefaultSetMultiMap<K, V> {
+    /// The `default()` method is equivalent to `DefaultSetMultiMap::new()`.
+    fn default() -> DefaultSetMultiMap<K, V> {
+        DefaultSetMultiMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Eq + Hash> FromIterator<(K, V)> for DefaultSetMultiMap<K, V> {
+    fn from_iter<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut map = DefaultSetMultiMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DefaultMultiMap, DefaultSetMultiMap};
+
+    #[test]
+    fn multimap() {
+        let mut map: DefaultMultiMap<i32, i32> =
+            [(1, 1), (1, 2), (2, 2), (1, 1)].into_iter().collect();
+        assert_eq!(map.get(&1), [1, 2, 1]);
+        assert_eq!(map.len(), 2);
+        assert!(map.contains(&2, &2));
+        assert!(!map.contains(&2, &1));
+        assert!(!map.contains(&3, &1));
+
+        assert!(map.remove_value(&1, &1));
+        assert_eq!(map.get(&1), [2, 1]);
+        assert!(!map.remove_value(&2, &3));
+        assert!(map.remove_value(&2, &2));
+        assert!(!map.as_map().contains_key(&2));
+        assert!(!map.remove_value(&2, &2));
+
+        let mut pairs: Vec<_> = map.iter_pairs().collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(&1, &1), (&1, &2)]);
+        assert_eq!(map.remove(&1), vec![2, 1]);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn set_multimap() {
+        let mut map: DefaultSetMultiMap<i32, i32> = DefaultSetMultiMap::new();
+        assert!(map.insert(1, 1));
+        assert!(map.insert(1, 2));
+        assert!(!map.insert(1, 1));
+        assert!(map.insert(2, 2));
+        assert!(map.insert(2, 3));
+        assert_eq!(map.get(&1).len(), 2);
+        assert!(map.contains(&2, &3));
+        assert!(!map.contains(&3, &3));
+        assert_eq!(map.intersection(&1, &2).collect::<Vec<_>>(), vec![&2]);
+        assert_eq!(map.intersection(&1, &3).count(), 0);
+        assert_eq!(map.union(&1, &2).count(), 3);
+        assert_eq!(map.iter_pairs().count(), 4);
+
+        assert!(map.remove_value(&2, &2));
+        assert!(map.remove_value(&2, &3));
+        assert!(!map.remove_value(&2, &3));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.remove(&1).len(), 2);
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,288 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+use crate::default_fn::LazyDefault;
+use crate::DefaultFn;
+
+/// A map of arbitrary depth where values are stored at paths of keys, and missing levels are
+/// created automatically.
+///
+/// This saves building a `DefaultHashMap<_, DefaultHashMap<_, _>>` by hand, where the default
+/// function for the inner maps has to be written out at every level. Values can be stored at any
+/// depth, also at paths that are a prefix of other paths.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut population: NestedDefaultMap<&str, u32> = NestedDefaultMap::new();
+/// *population.at(["us", "ca", "sf"]) += 800;
+/// *population.at(["us", "ca", "la"]) += 3800;
+/// *population.at(["nl", "nh", "amsterdam"]) += 900;
+///
+/// assert_eq!(*population.get(["us", "ca", "sf"]), 800);
+/// assert_eq!(*population.get(["us", "ny", "nyc"]), 0);
+/// let us_total: u32 = population
+///     .iter_paths()
+///     .filter(|(path, _)| path[0] == &"us")
+///     .map(|(_, value)| value)
+///     .sum();
+/// assert_eq!(us_total, 4600);
+/// ```
+#[derive(Clone)]
+pub struct NestedDefaultMap<K: Eq + Hash, V> {
+    root: Node<K, V>,
+    default: LazyDefault<V>,
+}
+
+#[derive(Clone)]
+struct Node<K, V> {
+    value: Option<V>,
+    children: HashMap<K, Node<K, V>>,
+}
+
+impl<K, V> Node<K, V> {
+    fn new() -> Node<K, V> {
+        Node {
+            value: None,
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V: Default> NestedDefaultMap<K, V> {
+    /// Creates an empty `NestedDefaultMap` with the default of `V` as the default for missing
+    /// paths.
+    pub fn new() -> NestedDefaultMap<K, V> {
+        NestedDefaultMap::with_lazy_default(LazyDefault::std_default())
+    }
+}
+
+impl<K: Eq + Hash, V: Default> Default for NestedDefaultMap<K, V> {
+    /// The `default()` method is equivalent to `NestedDefaultMap::new()`.
+    fn default() -> NestedDefaultMap<K, V> {
+        NestedDefaultMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> NestedDefaultMap<K, V> {
+    /// Creates an empty `NestedDefaultMap` with `default` as the default for missing paths.
+    pub fn with_default(default: V) -> NestedDefaultMap<K, V> {
+        NestedDefaultMap::with_lazy_default(LazyDefault::value(default))
+    }
+}
+
+impl<K: Eq + Hash, V> NestedDefaultMap<K, V> {
+    /// Creates an empty `NestedDefaultMap` with `default_fn` as the default value generation
+    /// function for missing paths.
+    pub fn with_fn(default_fn: impl DefaultFn<V> + 'static) -> NestedDefaultMap<K, V> {
+        NestedDefaultMap::with_lazy_default(LazyDefault::with_fn(default_fn))
+    }
+
+    fn with_lazy_default(default: LazyDefault<V>) -> NestedDefaultMap<K, V> {
+        NestedDefaultMap {
+            root: Node::new(),
+            default,
+        }
+    }
+
+    /// Returns a mutable reference to the value stored at the path. Missing levels are created,
+    /// and if there is no value stored at the path yet the default is inserted first.
+    pub fn at<I>(&mut self, path: I) -> &mut V
+    where
+        I: IntoIterator<Item = K>,
+    {
+        let mut node = &mut self.root;
+        for key in path {
+            node = node.children.entry(key).or_insert_with(Node::new);
+        }
+        node.value.get_or_insert_with(|| self.default.create())
+    }
+
+    /// Returns a reference to the value stored at the path, or to the default if there is none.
+    /// Unlike `at` this doesn't create anything.
+    pub fn get<'q, Q, I>(&self, path: I) -> &V
+    where
+        I: IntoIterator<Item = &'q Q>,
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + 'q,
+    {
+        let mut node = &self.root;
+        for key in path {
+            match node.children.get(key) {
+                Some(child) => node = child,
+                None => return self.default.get(),
+            }
+        }
+        node.value.as_ref().unwrap_or_else(|| self.default.get())
+    }
+
+    /// Returns an iterator over all stored values together with their paths, in arbitrary order.
+    pub fn iter_paths(&self) -> NestedPaths<'_, K, V> {
+        NestedPaths {
+            stack: vec![(Vec::new(), &self.root)],
+        }
+    }
+
+    /// Returns `true` if no values are stored in the map.
+    pub fn is_empty(&self) -> bool {
+        self.iter_paths().next().is_none()
+    }
+
+    /// Removes all values and levels from the map.
+    pub fn clear(&mut self) {
+        self.root = Node::new();
+    }
+}
+
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug> fmt::Debug for NestedDefaultMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NestedDefaultMap")
+            .field("paths", &DebugPaths(self))
+            .field("default", self.default.get())
+            .finish()
+    }
+}
+
+struct DebugPaths<'a, K: Eq + Hash, V>(&'a NestedDefaultMap<K, V>);
+
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug> fmt::Debug for DebugPaths<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter_paths()).finish()
+    }
+}
+
+/// An iterator over the values of a [`NestedDefaultMap`] together with their paths. It's
+/// returned by [`NestedDefaultMap::iter_paths`].
+pub struct NestedPaths<'a, K, V> {
+    stack: Vec<(Vec<&'a K>, &'a Node<K, V>)>,
+}
+
+impl<'a, K, V> Iterator for NestedPaths<'a, K, V> {
+    type Item = (Vec<&'a K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, node)) = self.stack.pop() {
+            for (key, child) in &node.children {
+                let mut child_path = path.clone();
+                child_path.push(key);
+                self.stack.push((child_path, child));
+            }
+            if let Some(value) = &node.value {
+                return Some((path, value));
+            }
+        }
+        None
+    }
+}
+
+/// The `nested!` macro mutably indexes maps that contain other default maps as values, such as
+/// `DefaultHashMap<K1, DefaultHashMap<K2, V>>`. Every index is turned into a `get_mut` call, so
+/// missing levels are created with their default. It can be used in the following ways:
+///
+/// ```
+/// # #[macro_use] extern crate defaultmap;
+/// # use defaultmap::*;
+/// let mut cities: DefaultHashMap<&str, DefaultHashMap<&str, DefaultBTreeMap<&str, u32>>> =
+///     DefaultHashMap::new();
+///
+/// // Updating a value with an assignment operator
+/// nested!(cities["us"]["ca"]["sf"] += 1);
+/// nested!(cities["us"]["ca"]["sf"] *= 10);
+/// nested!(cities["us"]["ca"]["la"] = 3);
+///
+/// // Getting a mutable reference to a value
+/// let la = nested!(cities["us"]["ca"]["la"]);
+/// *la -= 1;
+///
+/// assert_eq!(cities["us"]["ca"]["sf"], 10);
+/// assert_eq!(cities["us"]["ca"]["la"], 2);
+/// assert_eq!(cities["nl"]["nh"]["amsterdam"], 0);
+/// ```
+#[macro_export]
+macro_rules! nested {
+    ($map:ident $([$key:expr])+ = $value:expr) => { *$crate::nested!($map $([$key])+) = $value };
+    ($map:ident $([$key:expr])+ += $value:expr) => { *$crate::nested!($map $([$key])+) += $value };
+    ($map:ident $([$key:expr])+ -= $value:expr) => { *$crate::nested!($map $([$key])+) -= $value };
+    ($map:ident $([$key:expr])+ *= $value:expr) => { *$crate::nested!($map $([$key])+) *= $value };
+    ($map:ident $([$key:expr])+ /= $value:expr) => { *$crate::nested!($map $([$key])+) /= $value };
+    ($map:ident $([$key:expr])+) => { $map$(.get_mut($key))+ };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NestedDefaultMap;
+
+    use crate::DefaultHashMap;
+
+    #[test]
+    fn at_and_get() {
+        let mut map: NestedDefaultMap<i32, Vec<i32>> = NestedDefaultMap::with_default(vec![0]);
+        assert!(map.is_empty());
+        map.at([1, 2]).push(12);
+        map.at([1]).push(1);
+        map.at([]).push(-1);
+        assert_eq!(map.get(&[1, 2]), &vec![0, 12]);
+        assert_eq!(map.get(&[1]), &vec![0, 1]);
+        assert_eq!(map.get(&[]), &vec![0, -1]);
+        assert_eq!(map.get(&[1, 2, 3]), &vec![0]);
+        assert_eq!(map.get(&[2]), &vec![0]);
+
+        let mut paths: Vec<_> = map.iter_paths().collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                (vec![], &vec![0, -1]),
+                (vec![&1], &vec![0, 1]),
+                (vec![&1, &2], &vec![0, 12]),
+            ]
+        );
+
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn intermediate_levels_have_no_value() {
+        let mut map: NestedDefaultMap<String, u32> = NestedDefaultMap::new();
+        *map.at(["a".to_string(), "b".to_string()]) += 1;
+        assert_eq!(map.iter_paths().count(), 1);
+        assert_eq!(*map.get(["a"]), 0);
+        assert_eq!(*map.get(["a", "b"]), 1);
+        assert_eq!(
+            format!("{:?}", map),
+            r#"NestedDefaultMap { paths: {["a", "b"]: 1}, default: 0 }"#
+        );
+    }
+
+    #[test]
+    fn lazy_default() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let mut map: NestedDefaultMap<i32, i32> = NestedDefaultMap::with_fn(move || {
+            counter.set(counter.get() + 1);
+            7
+        });
+        assert_eq!(calls.get(), 0);
+        *map.at([1]) += 1;
+        assert_eq!(calls.get(), 1);
+        assert_eq!((*map.get(&[1]), *map.get(&[2]), *map.get(&[3])), (8, 7, 7));
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn nested_macro() {
+        let mut map: DefaultHashMap<i32, DefaultHashMap<i32, Vec<i32>>> = DefaultHashMap::new();
+        nested!(map[1][2]).push(1);
+        nested!(map[1][2]).push(2);
+        nested!(map[1][3] = vec![3]);
+        assert_eq!(map[1][2], vec![1, 2]);
+        assert_eq!(map[1][3], vec![3]);
+        assert_eq!(map[1].len(), 2);
+        assert_eq!(map.len(), 1);
+    }
+}
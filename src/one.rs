@@ -0,0 +1,20 @@
+/// Numbers that have a one, which the `incr` and `decr` methods of the maps add or subtract.
+/// Unlike a `From<u8>` bound this also covers `i8`.
+pub trait One {
+    /// Returns the number one.
+    fn one() -> Self;
+}
+
+macro_rules! impl_one {
+    ($one:expr => $($t:ty),*) => {
+        $(impl One for $t {
+            #[inline]
+            fn one() -> Self {
+                $one
+            }
+        })*
+    };
+}
+
+impl_one!(1 => u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+impl_one!(1.0 => f32, f64);
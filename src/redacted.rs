@@ -0,0 +1,65 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::sync::OnceLock;
+
+/// A wrapper that formats a map with its keys masked, so maps keyed by sensitive data such as
+/// user identifiers can be logged. It's returned by the `redacted` method of the maps.
+///
+/// Every key is replaced by a hash of it. The same key always results in the same mask while the
+/// program is running, so entries can still be correlated in the logs. The hashes are randomly
+/// seeded on every run, which makes it impractical to recover keys by hashing guesses of them.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut logins: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+/// logins["alice@example.com"] += 1;
+///
+/// let output = format!("{:?}", logins.redacted());
+/// assert!(output.starts_with("DefaultHashMap { map: {<redacted "));
+/// assert!(!output.contains("alice"));
+/// ```
+pub struct Redacted<'a, M> {
+    pub(crate) map: &'a M,
+}
+
+impl<M> Clone for Redacted<'_, M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M> Copy for Redacted<'_, M> {}
+
+/// Formats the entries of a map, with every key replaced by its mask.
+pub(crate) struct MaskedEntries<I>(pub(crate) I);
+
+impl<'a, K: Hash + 'a, V: fmt::Debug + 'a, I> fmt::Debug for MaskedEntries<I>
+where
+    I: Iterator<Item = (&'a K, &'a V)> + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.0
+                    .clone()
+                    .map(|(key, value)| (MaskedKey::new(key), value)),
+            )
+            .finish()
+    }
+}
+
+struct MaskedKey(u64);
+
+impl MaskedKey {
+    fn new<K: Hash>(key: &K) -> MaskedKey {
+        static STATE: OnceLock<RandomState> = OnceLock::new();
+        MaskedKey(STATE.get_or_init(RandomState::new).hash_one(key))
+    }
+}
+
+impl fmt::Debug for MaskedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted {:016x}>", self.0)
+    }
+}
@@ -0,0 +1,649 @@
+//! Helpers for `#[serde(with = ...)]` and `#[serde(deserialize_with = ...)]` attributes, which
+//! (de)serialize a `DefaultHashMap` or `DefaultBTreeMap` field as a plain map of its entries.
+//!
+//! By default the maps are serialized together with their default. That's not what's wanted
+//! when the map is part of a configuration file, where the entries should be written as a plain
+//! map and the default is known by the program.
+//!
+//! ```
+//! # use defaultmap::*;
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     #[serde(with = "defaultmap::serde_helpers::as_map")]
+//!     retries: DefaultHashMap<String, u32>,
+//!     #[serde(
+//!         serialize_with = "defaultmap::serde_helpers::as_map::serialize",
+//!         deserialize_with = "defaultmap::serde_helpers::with_default::<30, _, _>"
+//!     )]
+//!     timeouts: DefaultBTreeMap<String, u64>,
+//! }
+//!
+//! let config: Config =
+//!     serde_json::from_str(r#"{"retries": {"upload": 5}, "timeouts": {"upload": 60}}"#).unwrap();
+//! assert_eq!(config.retries[String::from("upload")], 5);
+//! assert_eq!(config.retries[String::from("download")], 0);
+//! assert_eq!(config.timeouts[String::from("download")], 30);
+//! assert_eq!(
+//!     serde_json::to_string(&config).unwrap(),
+//!     r#"{"retries":{"upload":5},"timeouts":{"upload":60}}"#
+//! );
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+use serde::de::value::{BorrowedStrDeserializer, MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{
+    DeserializeSeed, Error, IgnoredAny, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::default_fn::LazyDefault;
+use crate::{DefaultBTreeMap, DefaultFn, DefaultHashMap};
+
+/// The maps that can be used with the helpers in this module.
+pub trait PlainMap: Sized {
+    /// The type of the keys of the map.
+    type Key;
+
+    /// The type of the values of the map.
+    type Value;
+
+    /// The map from the standard library that stores the entries.
+    type Inner;
+
+    /// Returns the map that stores the entries.
+    fn as_inner(&self) -> &Self::Inner;
+
+    /// Creates a map from its entries, with the default of `V` as the default for missing keys.
+    fn from_inner(inner: Self::Inner) -> Self
+    where
+        Self::Value: Default;
+
+    /// Creates a map from its entries, with `default` as the default for missing keys.
+    fn from_inner_with_default(inner: Self::Inner, default: Self::Value) -> Self
+    where
+        Self::Value: Clone;
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> PlainMap for DefaultHashMap<K, V, S> {
+    type Key = K;
+    type Value = V;
+    type Inner = HashMap<K, V, S>;
+
+    fn as_inner(&self) -> &HashMap<K, V, S> {
+        self
+    }
+
+    fn from_inner(inner: HashMap<K, V, S>) -> Self
+    where
+        V: Default,
+    {
+        DefaultHashMap::from(inner)
+    }
+
+    fn from_inner_with_default(inner: HashMap<K, V, S>, default: V) -> Self
+    where
+        V: Clone,
+    {
+        DefaultHashMap::from_map_with_default(inner, default)
+    }
+}
+
+impl<K: Eq + Ord, V> PlainMap for DefaultBTreeMap<K, V> {
+    type Key = K;
+    type Value = V;
+    type Inner = BTreeMap<K, V>;
+
+    fn as_inner(&self) -> &BTreeMap<K, V> {
+        self
+    }
+
+    fn from_inner(inner: BTreeMap<K, V>) -> Self
+    where
+        V: Default,
+    {
+        DefaultBTreeMap::from(inner)
+    }
+
+    fn from_inner_with_default(inner: BTreeMap<K, V>, default: V) -> Self
+    where
+        V: Clone,
+    {
+        DefaultBTreeMap::from_map_with_default(inner, default)
+    }
+}
+
+/// (De)serializes a map as a plain map of its entries. When deserializing, the default of `V`
+/// is used as the default for missing keys.
+pub mod as_map {
+    use super::*;
+
+    /// Serializes the entries of the map, without its default.
+    pub fn serialize<M, S>(map: &M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        M: PlainMap,
+        M::Inner: Serialize,
+        S: Serializer,
+    {
+        map.as_inner().serialize(serializer)
+    }
+
+    /// Deserializes a plain map of entries, with the default of `V` as the default for missing
+    /// keys.
+    pub fn deserialize<'de, M, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: PlainMap,
+        M::Inner: Deserialize<'de>,
+        M::Value: Default,
+        D: Deserializer<'de>,
+    {
+        M::Inner::deserialize(deserializer).map(M::from_inner)
+    }
+}
+
+/// (De)serializes a map as a sequence of `(key, value)` pairs, for keys that formats like JSON
+/// can't use as map keys. When deserializing, the default of `V` is used as the default for
+/// missing keys.
+///
+/// ```
+/// # use defaultmap::*;
+/// # use serde::{Deserialize, Serialize};
+/// #[derive(Serialize, Deserialize)]
+/// struct Grid {
+///     #[serde(with = "defaultmap::serde_helpers::as_entries")]
+///     cells: DefaultBTreeMap<(u32, u32), char>,
+/// }
+///
+/// let mut grid = Grid { cells: DefaultBTreeMap::with_default('.') };
+/// grid.cells[(0, 1)] = 'x';
+/// grid.cells[(2, 0)] = '@';
+/// let json = serde_json::to_string(&grid).unwrap();
+/// assert_eq!(json, r#"{"cells":[[[0,1],"x"],[[2,0],"@"]]}"#);
+///
+/// let grid: Grid = serde_json::from_str(&json).unwrap();
+/// assert_eq!(grid.cells[(2, 0)], '@');
+/// assert_eq!(grid.cells[(1, 1)], char::default());
+/// ```
+pub mod as_entries {
+    use super::*;
+
+    /// Serializes the entries of the map as a sequence of pairs, without its default.
+    pub fn serialize<M, S>(map: &M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        M: PlainMap,
+        M::Key: Serialize,
+        M::Value: Serialize,
+        for<'a> &'a M::Inner: IntoIterator<Item = (&'a M::Key, &'a M::Value)>,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.as_inner())
+    }
+
+    /// Deserializes a sequence of `(key, value)` pairs, with the default of `V` as the default
+    /// for missing keys. When a key occurs more than once, the last value is kept.
+    pub fn deserialize<'de, M, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: PlainMap,
+        M::Key: Deserialize<'de>,
+        M::Value: Deserialize<'de> + Default,
+        M::Inner: FromIterator<(M::Key, M::Value)>,
+        D: Deserializer<'de>,
+    {
+        let entries = Vec::<(M::Key, M::Value)>::deserialize(deserializer)?;
+        Ok(M::from_inner(entries.into_iter().collect()))
+    }
+}
+
+/// Deserializes a plain map of entries, with `DEFAULT` as the default for missing keys. This is
+/// meant for maps of numbers, to be used as `deserialize_with = "with_default::<5, _, _>"`.
+/// Deserializing fails if `DEFAULT` doesn't fit in the value type.
+pub fn with_default<'de, const DEFAULT: i64, M, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: PlainMap,
+    M::Inner: Deserialize<'de>,
+    M::Value: TryFrom<i64> + Clone,
+    D: Deserializer<'de>,
+{
+    let default = M::Value::try_from(DEFAULT)
+        .map_err(|_| D::Error::custom(format_args!("{} doesn't fit in the value type", DEFAULT)))?;
+    let inner = M::Inner::deserialize(deserializer)?;
+    Ok(M::from_inner_with_default(inner, default))
+}
+
+/// The `map` field of a serialized map. The serialized default is ignored.
+#[derive(Deserialize)]
+#[serde(bound = "M: Deserialize<'de>")]
+struct MapField<M> {
+    map: M,
+}
+
+/// A `DeserializeSeed` that deserializes a `DefaultHashMap` in its normal format, but that uses
+/// the given default value generation function instead of the serialized default. This makes it
+/// possible to deserialize maps whose default can't be serialized, like a closure.
+///
+/// ```
+/// # use defaultmap::*;
+/// use defaultmap::serde_helpers::DefaultHashMapSeed;
+/// use serde::de::DeserializeSeed;
+///
+/// let json = r#"{"map": {"a": [1]}, "default": []}"#;
+/// let seed = DefaultHashMapSeed::with_fn(|| vec![0]);
+/// let map: DefaultHashMap<String, Vec<i32>> =
+///     seed.deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+/// assert_eq!(map[String::from("a")], [1]);
+/// assert_eq!(map[String::from("b")], [0]);
+/// ```
+pub struct DefaultHashMapSeed<K, V, F, S = RandomState> {
+    default_fn: F,
+    _map: PhantomData<HashMap<K, V, S>>,
+}
+
+impl<K, V, F: DefaultFn<V> + 'static> DefaultHashMapSeed<K, V, F> {
+    /// Creates a seed that uses `default_fn` as the default value generation function of the
+    /// deserialized map.
+    pub fn with_fn(default_fn: F) -> Self {
+        DefaultHashMapSeed {
+            default_fn,
+            _map: PhantomData,
+        }
+    }
+}
+
+impl<'de, K, V, F, S> DeserializeSeed<'de> for DefaultHashMapSeed<K, V, F, S>
+where
+    K: Eq + Hash + Deserialize<'de>,
+    V: Deserialize<'de>,
+    F: DefaultFn<V> + 'static,
+    S: BuildHasher + Default,
+{
+    type Value = DefaultHashMap<K, V, S>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let field = MapField::<HashMap<K, V, S>>::deserialize(deserializer)?;
+        Ok(DefaultHashMap::from_map_with_fn(field.map, self.default_fn))
+    }
+}
+
+/// A `DeserializeSeed` that deserializes a `DefaultBTreeMap` in its normal format, but that uses
+/// the given default value generation function instead of the serialized default, see
+/// [`DefaultHashMapSeed`].
+pub struct DefaultBTreeMapSeed<K, V, F> {
+    default_fn: F,
+    _map: PhantomData<BTreeMap<K, V>>,
+}
+
+impl<K, V, F: DefaultFn<V> + 'static> DefaultBTreeMapSeed<K, V, F> {
+    /// Creates a seed that uses `default_fn` as the default value generation function of the
+    /// deserialized map.
+    pub fn with_fn(default_fn: F) -> Self {
+        DefaultBTreeMapSeed {
+            default_fn,
+            _map: PhantomData,
+        }
+    }
+}
+
+impl<'de, K, V, F> DeserializeSeed<'de> for DefaultBTreeMapSeed<K, V, F>
+where
+    K: Eq + Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+    F: DefaultFn<V> + 'static,
+{
+    type Value = DefaultBTreeMap<K, V>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let field = MapField::<BTreeMap<K, V>>::deserialize(deserializer)?;
+        Ok(DefaultBTreeMap::from_map_with_fn(
+            field.map,
+            self.default_fn,
+        ))
+    }
+}
+
+/// Deserializes the entries and the default of a map. This accepts the format the maps are
+/// serialized in, a struct with a `map` and a `default` field, as well as a plain map of entries.
+/// A plain map uses the default of `V` as the default for missing keys.
+///
+/// The input is only read as the struct when it has a `map` key whose value is a map, so plain
+/// maps can contain `"map"` and `"default"` keys too.
+pub(crate) fn deserialize_entries<'de, K, V, M, D>(
+    deserializer: D,
+    name: &'static str,
+) -> Result<(M, LazyDefault<V>), D::Error>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de> + Default,
+    M: Deserialize<'de> + Default + Extend<(K, V)>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_struct(name, &["map", "default"], EntriesVisitor(PhantomData))
+}
+
+struct EntriesVisitor<K, V, M>(PhantomData<(K, V, M)>);
+
+impl<'de, K, V, M> Visitor<'de> for EntriesVisitor<K, V, M>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de> + Default,
+    M: Deserialize<'de> + Default + Extend<(K, V)>,
+{
+    type Value = (M, LazyDefault<V>);
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of entries or a struct with `map` and `default` fields")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let map = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let default = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        Ok((map, default))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        // Until the value of `map` turns out to be a map, a `default` key can also be an entry.
+        let mut default = None;
+        let map = loop {
+            let first = match access.next_key_seed(FirstKey(PhantomData))? {
+                None => return plain_entries(access, default, None),
+                Some(Key::Default) if default.is_some() => {
+                    return Err(A::Error::duplicate_field("default"))
+                }
+                Some(Key::Default) => {
+                    default = Some(access.next_value()?);
+                    continue;
+                }
+                Some(Key::Map) => match access.next_value_seed(MapValueSeed(PhantomData))? {
+                    MapValue::Map(map) => break map,
+                    MapValue::Value(value) => (entry_key("map")?, value),
+                },
+                Some(Key::Entry(key)) => (key, access.next_value()?),
+            };
+            return plain_entries(access, default, Some(first));
+        };
+        while let Some(field) = access.next_key::<Field>()? {
+            match field {
+                Field::Map => return Err(A::Error::duplicate_field("map")),
+                Field::Default if default.is_some() => {
+                    return Err(A::Error::duplicate_field("default"))
+                }
+                Field::Default => default = Some(access.next_value()?),
+                Field::Other => {
+                    access.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let default = default.ok_or_else(|| A::Error::missing_field("default"))?;
+        Ok((map, LazyDefault::deserialized(default)))
+    }
+}
+
+/// Reads the remaining entries of a plain map, after the `default` entry and the first other
+/// entry that were already read.
+fn plain_entries<'de, K, V, M, A>(
+    mut access: A,
+    default: Option<V>,
+    first: Option<(K, V)>,
+) -> Result<(M, LazyDefault<V>), A::Error>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de> + Default,
+    M: Default + Extend<(K, V)>,
+    A: MapAccess<'de>,
+{
+    let mut entries = M::default();
+    if let Some(value) = default {
+        let key =
+            entry_key::<K, A::Error>("default").map_err(|_| A::Error::missing_field("map"))?;
+        entries.extend(Some((key, value)));
+    }
+    entries.extend(first);
+    while let Some(entry) = access.next_entry()? {
+        entries.extend(Some(entry));
+    }
+    Ok((entries, LazyDefault::std_default()))
+}
+
+/// Deserializes the key of an entry whose key was read as a field name.
+fn entry_key<'de, K: Deserialize<'de>, E: Error>(name: &'static str) -> Result<K, E> {
+    K::deserialize(BorrowedStrDeserializer::new(name))
+}
+
+/// Parses a string key as a number or a boolean if `K` can't be deserialized from the string
+/// itself, like formats such as JSON do for the keys of an object.
+fn parse_key<'de, K: Deserialize<'de>, E: Error>(v: &str, parsed: Result<K, E>) -> Result<K, E> {
+    parsed.or_else(|err| {
+        let parsed = if let Ok(v) = v.parse::<u64>() {
+            K::deserialize(v.into_deserializer())
+        } else if let Ok(v) = v.parse::<i64>() {
+            K::deserialize(v.into_deserializer())
+        } else if let Ok(v) = v.parse::<f64>() {
+            K::deserialize(v.into_deserializer())
+        } else if let Ok(v) = v.parse::<bool>() {
+            K::deserialize(v.into_deserializer())
+        } else {
+            return Err(err);
+        };
+        parsed.map_err(|_: E| err)
+    })
+}
+
+/// A key of the struct format, or the first key of a plain map.
+enum Key<K> {
+    Map,
+    Default,
+    Entry(K),
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Map,
+    Default,
+    #[serde(other)]
+    Other,
+}
+
+struct FirstKey<K>(PhantomData<K>);
+
+impl<'de, K: Deserialize<'de>> DeserializeSeed<'de> for FirstKey<K> {
+    type Value = Key<K>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Key<K>, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, K: Deserialize<'de>> Visitor<'de> for FirstKey<K> {
+    type Value = Key<K>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a field name or a key")
+    }
+
+    fn visit_borrowed_str<E: Error>(self, v: &'de str) -> Result<Key<K>, E> {
+        match v {
+            "map" => Ok(Key::Map),
+            "default" => Ok(Key::Default),
+            _ => parse_key(v, K::deserialize(BorrowedStrDeserializer::new(v))).map(Key::Entry),
+        }
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Key<K>, E> {
+        match v {
+            "map" => Ok(Key::Map),
+            "default" => Ok(Key::Default),
+            _ => parse_key(v, K::deserialize(v.into_deserializer())).map(Key::Entry),
+        }
+    }
+
+    fn visit_string<E: Error>(self, v: String) -> Result<Key<K>, E> {
+        match v.as_str() {
+            "map" => Ok(Key::Map),
+            "default" => Ok(Key::Default),
+            _ => parse_key(&v, K::deserialize(v.as_str().into_deserializer())).map(Key::Entry),
+        }
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Key<K>, E> {
+        K::deserialize(v.into_deserializer()).map(Key::Entry)
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Key<K>, E> {
+        K::deserialize(v.into_deserializer()).map(Key::Entry)
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Key<K>, E> {
+        K::deserialize(v.into_deserializer()).map(Key::Entry)
+    }
+
+    fn visit_char<E: Error>(self, v: char) -> Result<Key<K>, E> {
+        K::deserialize(v.into_deserializer()).map(Key::Entry)
+    }
+}
+
+/// The value of a `map` key, which is the entries of the struct format if it's a map, and the
+/// value of an entry of a plain map otherwise.
+enum MapValue<M, V> {
+    Map(M),
+    Value(V),
+}
+
+struct MapValueSeed<M, V>(PhantomData<(M, V)>);
+
+impl<'de, M: Deserialize<'de>, V: Deserialize<'de>> DeserializeSeed<'de> for MapValueSeed<M, V> {
+    type Value = MapValue<M, V>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, M: Deserialize<'de>, V: Deserialize<'de>> Visitor<'de> for MapValueSeed<M, V> {
+    type Value = MapValue<M, V>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of entries or a value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, access: A) -> Result<Self::Value, A::Error> {
+        M::deserialize(MapAccessDeserializer::new(access)).map(MapValue::Map)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        V::deserialize(SeqAccessDeserializer::new(seq)).map(MapValue::Value)
+    }
+
+    fn visit_bool<E: Error>(self, v: bool) -> Result<Self::Value, E> {
+        V::deserialize(v.into_deserializer()).map(MapValue::Value)
+    }
+
+    fn visit_i64<E: Error>(self, v: i64) -> Result<Self::Value, E> {
+        V::deserialize(v.into_deserializer()).map(MapValue::Value)
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        V::deserialize(v.into_deserializer()).map(MapValue::Value)
+    }
+
+    fn visit_f64<E: Error>(self, v: f64) -> Result<Self::Value, E> {
+        V::deserialize(v.into_deserializer()).map(MapValue::Value)
+    }
+
+    fn visit_char<E: Error>(self, v: char) -> Result<Self::Value, E> {
+        V::deserialize(v.into_deserializer()).map(MapValue::Value)
+    }
+
+    fn visit_borrowed_str<E: Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        V::deserialize(BorrowedStrDeserializer::new(v)).map(MapValue::Value)
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        V::deserialize(v.into_deserializer()).map(MapValue::Value)
+    }
+
+    fn visit_string<E: Error>(self, v: String) -> Result<Self::Value, E> {
+        V::deserialize(v.into_deserializer()).map(MapValue::Value)
+    }
+
+    fn visit_unit<E: Error>(self) -> Result<Self::Value, E> {
+        V::deserialize(().into_deserializer()).map(MapValue::Value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::DefaultBTreeMapSeed;
+    use crate::{DefaultBTreeMap, DefaultHashMap};
+
+    #[derive(Deserialize)]
+    struct Limits {
+        #[serde(deserialize_with = "super::with_default::<-1, _, _>")]
+        limits: DefaultHashMap<String, i8>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Unsigned {
+        #[serde(deserialize_with = "super::with_default::<-1, _, _>")]
+        _limits: DefaultHashMap<String, u8>,
+    }
+
+    #[test]
+    fn with_default() {
+        let limits: Limits = serde_json::from_str(r#"{"limits": {"a": 3}}"#).unwrap();
+        assert_eq!(limits.limits[String::from("a")], 3);
+        assert_eq!(limits.limits[String::from("b")], -1);
+
+        let err = serde_json::from_str::<Unsigned>(r#"{"_limits": {}}"#).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("-1 doesn't fit in the value type"));
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Pairs {
+        #[serde(with = "super::as_entries")]
+        pairs: DefaultHashMap<(u32, u32), u64>,
+    }
+
+    #[test]
+    fn as_entries() {
+        let mut pairs = Pairs {
+            pairs: DefaultHashMap::new(),
+        };
+        pairs.pairs[(1, 2)] = 3;
+        let json = serde_json::to_string(&pairs).unwrap();
+        assert_eq!(json, r#"{"pairs":[[[1,2],3]]}"#);
+
+        let pairs: Pairs =
+            serde_json::from_str(r#"{"pairs": [[[1, 2], 3], [[1, 2], 4]]}"#).unwrap();
+        assert_eq!(pairs.pairs.len(), 1);
+        assert_eq!(pairs.pairs[(1, 2)], 4);
+        assert_eq!(pairs.pairs[(2, 1)], 0);
+    }
+
+    #[test]
+    fn btreemap_seed() {
+        use serde::de::DeserializeSeed;
+
+        let mut original: DefaultBTreeMap<i32, String> = DefaultBTreeMap::new();
+        original.insert(1, "one".to_string());
+        let json = serde_json::to_string(&original).unwrap();
+        let seed = DefaultBTreeMapSeed::with_fn(|| "unknown".to_string());
+        let map: DefaultBTreeMap<i32, String> = seed
+            .deserialize(&mut serde_json::Deserializer::from_str(&json))
+            .unwrap();
+        assert_eq!(map[1], "one");
+        assert_eq!(map[2], "unknown");
+    }
+}
@@ -0,0 +1,226 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::ops::AddAssign;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::DefaultHashMap;
+
+/// A map that returns a default for missing keys and that can be updated from multiple threads
+/// at the same time.
+///
+/// The keys are partitioned over a number of shards that each have their own lock, so threads
+/// that update different keys rarely have to wait for each other. New values are created by
+/// cloning the default, because a default value generation function can't be shared between
+/// threads. A panic while a shard is locked doesn't poison the map, the values are used as the
+/// panicking update left them.
+///
+/// ```
+/// # use defaultmap::*;
+/// let counts: ShardedDefaultMap<&str, u64> = ShardedDefaultMap::new();
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| {
+///             for word in ["a", "b", "a"] {
+///                 counts.update(word, |count| *count += 1);
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(counts.get_cloned("a"), 8);
+/// assert_eq!(counts.get_cloned("c"), 0);
+/// ```
+pub struct ShardedDefaultMap<K, V> {
+    shards: Box<[Mutex<HashMap<K, V>>]>,
+    default: V,
+    hash_builder: RandomState,
+}
+
+impl<K: Eq + Hash, V: Default + Clone> ShardedDefaultMap<K, V> {
+    /// Creates an empty map with 16 shards, with the default of `V` as the default for missing
+    /// keys.
+    pub fn new() -> ShardedDefaultMap<K, V> {
+        ShardedDefaultMap::with_default(16, V::default())
+    }
+}
+
+impl<K: Eq + Hash, V: Default + Clone> Default for ShardedDefaultMap<K, V> {
+    /// The `default()` method is equivalent to `ShardedDefaultMap::new()`.
+    fn default() -> ShardedDefaultMap<K, V> {
+        ShardedDefaultMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> ShardedDefaultMap<K, V> {
+    /// Creates an empty map with `shards` shards, with `default` as the default for missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_default(shards: usize, default: V) -> ShardedDefaultMap<K, V> {
+        assert!(shards > 0, "ShardedDefaultMap needs at least one shard");
+        ShardedDefaultMap {
+            shards: (0..shards).map(|_| Mutex::default()).collect(),
+            default,
+            hash_builder: RandomState::new(),
+        }
+    }
+
+    /// Returns a clone of the value stored for the key, or of the default if there is none.
+    pub fn get_cloned<Q>(&self, key: &Q) -> V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        match self.shard(key).get(key) {
+            Some(value) => value.clone(),
+            None => self.default.clone(),
+        }
+    }
+
+    /// Calls `f` with a mutable reference to the value of the key, inserting the default first if
+    /// there is no value stored yet. The shard of the key stays locked while `f` runs.
+    pub fn update<R>(&self, key: K, f: impl FnOnce(&mut V) -> R) -> R {
+        let mut shard = self.shard(&key);
+        f(shard.entry(key).or_insert_with(|| self.default.clone()))
+    }
+
+    /// Removes the key from the map and returns its value, if it was stored.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.shard(key).remove(key)
+    }
+
+    /// Returns a `DefaultHashMap` with clones of all entries and the same default. The shards are
+    /// locked one after the other, so updates that happen while the snapshot is taken may only be
+    /// partly included.
+    pub fn snapshot(&self) -> DefaultHashMap<K, V>
+    where
+        K: Clone,
+    {
+        let mut snapshot = DefaultHashMap::with_default(self.default.clone());
+        for shard in self.shards.iter() {
+            let shard = lock(shard);
+            for (key, value) in shard.iter() {
+                snapshot.insert(key.clone(), value.clone());
+            }
+        }
+        snapshot
+    }
+
+    /// Moves all entries into `target`, adding them to the values that are already there. Keys
+    /// that are missing from `target` start out at its default. This empties the shards, so it can
+    /// be used to periodically flush the counts of an ingest pipeline into a single map.
+    pub fn merge_into<S: BuildHasher>(&self, target: &mut DefaultHashMap<K, V, S>)
+    where
+        V: AddAssign,
+    {
+        for shard in self.shards.iter() {
+            let entries = std::mem::take(&mut *lock(shard));
+            for (key, value) in entries {
+                *target.get_mut(key) += value;
+            }
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> ShardedDefaultMap<K, V> {
+    /// Returns the number of shards of the map.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| lock(shard).is_empty())
+    }
+
+    /// Returns a reference to the default value of the map.
+    pub fn default_value(&self) -> &V {
+        &self.default
+    }
+
+    fn shard<Q>(&self, key: &Q) -> MutexGuard<'_, HashMap<K, V>>
+    where
+        Q: ?Sized + Hash,
+    {
+        let index = self.hash_builder.hash_one(key) % self.shards.len() as u64;
+        lock(&self.shards[index as usize])
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<K, V: fmt::Debug> fmt::Debug for ShardedDefaultMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedDefaultMap")
+            .field("shards", &self.shards.len())
+            .field("default", &self.default)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedDefaultMap;
+    use crate::DefaultHashMap;
+
+    #[test]
+    fn concurrent_updates() {
+        let map: ShardedDefaultMap<u32, u32> = ShardedDefaultMap::with_default(4, 1);
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let map = &map;
+                s.spawn(move || {
+                    for key in 0..100 {
+                        map.update(key, |value| *value += t);
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), 100);
+        assert_eq!(map.get_cloned(&7), 7);
+        assert_eq!(map.get_cloned(&100), 1);
+
+        let snapshot = map.snapshot();
+        assert_eq!(snapshot[7], 7);
+        assert_eq!(snapshot[100], 1);
+        assert_eq!(map.remove(&7), Some(7));
+        assert_eq!(map.len(), 99);
+    }
+
+    #[test]
+    fn merge_into_drains() {
+        let map: ShardedDefaultMap<&str, u32> = ShardedDefaultMap::new();
+        map.update("a", |v| *v += 2);
+        map.update("b", |v| *v += 3);
+        let mut total: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+        total["a"] = 10;
+        map.merge_into(&mut total);
+        assert!(map.is_empty());
+        assert_eq!(total["a"], 12);
+        assert_eq!(total["b"], 3);
+        assert_eq!(
+            format!("{:?}", map),
+            "ShardedDefaultMap { shards: 16, default: 0 }"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one shard")]
+    fn zero_shards() {
+        let _: ShardedDefaultMap<i32, i32> = ShardedDefaultMap::with_default(0, 0);
+    }
+}
@@ -0,0 +1,55 @@
+#[cfg(feature = "stats")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counters for how often the default of a map was used. They are only tracked when the `stats`
+/// feature is enabled, otherwise this is an empty type and recording does nothing.
+#[derive(Default)]
+pub(crate) struct Stats {
+    #[cfg(feature = "stats")]
+    misses: AtomicUsize,
+    #[cfg(feature = "stats")]
+    materialized: AtomicUsize,
+}
+
+impl Stats {
+    /// Records that the default was returned for a missing key.
+    #[inline]
+    pub(crate) fn record_miss(&self) {
+        #[cfg(feature = "stats")]
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that the default was inserted for a missing key.
+    #[inline]
+    pub(crate) fn record_materialized(&self) {
+        #[cfg(feature = "stats")]
+        self.materialized.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn materialized(&self) -> usize {
+        self.materialized.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "stats")]
+    pub(crate) fn reset(&self) {
+        self.misses.store(0, Ordering::Relaxed);
+        self.materialized.store(0, Ordering::Relaxed);
+    }
+}
+
+impl Clone for Stats {
+    fn clone(&self) -> Self {
+        Stats {
+            #[cfg(feature = "stats")]
+            misses: AtomicUsize::new(self.misses()),
+            #[cfg(feature = "stats")]
+            materialized: AtomicUsize::new(self.materialized()),
+        }
+    }
+}
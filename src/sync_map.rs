@@ -0,0 +1,169 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::{PoisonError, RwLock, RwLockWriteGuard};
+
+use crate::{DefaultHashMap, FrozenDefaultMap};
+
+/// A map that returns a default for missing keys behind a `RwLock`, for the common case of a map
+/// of counters that is shared between threads.
+///
+/// Reading happens through a [`FrozenDefaultMap`], so lookups inside `read` return the default
+/// for missing keys like they do on the other maps. New values are created by cloning the
+/// default, because a default value generation function can't be shared between threads. A panic
+/// while the lock is held doesn't poison the map, the values are used as the panicking update
+/// left them.
+///
+/// ```
+/// # use defaultmap::*;
+/// let counts: DefaultSyncMap<&str, u64> = DefaultSyncMap::new();
+/// std::thread::scope(|s| {
+///     for _ in 0..4 {
+///         s.spawn(|| counts.update("requests", |count| *count += 1));
+///     }
+/// });
+/// assert_eq!(counts.get_cloned("requests"), 4);
+/// assert_eq!(counts.read(|counts| counts["requests"] + counts["errors"]), 4);
+/// ```
+pub struct DefaultSyncMap<K: Eq + Hash, V> {
+    lock: RwLock<FrozenDefaultMap<K, V>>,
+}
+
+impl<K: Eq + Hash, V: Default> DefaultSyncMap<K, V> {
+    /// Creates an empty map with the default of `V` as the default for missing keys.
+    pub fn new() -> DefaultSyncMap<K, V> {
+        DefaultSyncMap::with_default(V::default())
+    }
+}
+
+impl<K: Eq + Hash, V: Default> Default for DefaultSyncMap<K, V> {
+    /// The `default()` method is equivalent to `DefaultSyncMap::new()`.
+    fn default() -> DefaultSyncMap<K, V> {
+        DefaultSyncMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V> From<DefaultHashMap<K, V>> for DefaultSyncMap<K, V> {
+    /// Creates a `DefaultSyncMap` with the entries and the default of the map. Its default value
+    /// generation function isn't kept, new values are clones of the default.
+    fn from(map: DefaultHashMap<K, V>) -> DefaultSyncMap<K, V> {
+        DefaultSyncMap {
+            lock: RwLock::new(map.freeze()),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> DefaultSyncMap<K, V> {
+    /// Creates an empty map with `default` as the default for missing keys.
+    pub fn with_default(default: V) -> DefaultSyncMap<K, V> {
+        DefaultSyncMap {
+            lock: RwLock::new(FrozenDefaultMap {
+                map: HashMap::new(),
+                default,
+            }),
+        }
+    }
+
+    /// Calls `f` with a read-only view of the map, while holding the read lock.
+    pub fn read<R>(&self, f: impl FnOnce(&FrozenDefaultMap<K, V>) -> R) -> R {
+        f(&self.lock.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Removes the key from the map and returns its value, if it was stored.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.write().map.remove(key)
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.read(|map| map.len())
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.read(|map| map.is_empty())
+    }
+
+    /// Returns the read-only map that is behind the lock.
+    pub fn into_inner(self) -> FrozenDefaultMap<K, V> {
+        self.lock
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, FrozenDefaultMap<K, V>> {
+        self.lock.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> DefaultSyncMap<K, V> {
+    /// Returns a clone of the value stored for the key, or of the default if there is none.
+    pub fn get_cloned<Q>(&self, key: &Q) -> V
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.read(|map| map.get(key).clone())
+    }
+
+    /// Calls `f` with a mutable reference to the value of the key, inserting the default first if
+    /// there is no value stored yet. The write lock is held while `f` runs.
+    pub fn update<R>(&self, key: K, f: impl FnOnce(&mut V) -> R) -> R {
+        let mut map = self.write();
+        let FrozenDefaultMap { map, default } = &mut *map;
+        f(map.entry(key).or_insert_with(|| default.clone()))
+    }
+}
+
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug> fmt::Debug for DefaultSyncMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.read(|map| {
+            f.debug_struct("DefaultSyncMap")
+                .field("map", &map.map)
+                .field("default", &map.default)
+                .finish()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DefaultSyncMap;
+    use crate::DefaultHashMap;
+
+    #[test]
+    fn shared_updates() {
+        let map: DefaultSyncMap<i32, i32> = DefaultSyncMap::with_default(10);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for key in 0..10 {
+                        map.update(key, |value| *value += 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.get_cloned(&3), 14);
+        assert_eq!(map.get_cloned(&10), 10);
+        assert_eq!(map.remove(&3), Some(14));
+        assert_eq!(map.into_inner()[3], 10);
+    }
+
+    #[test]
+    fn from_default_map() {
+        let mut counts: DefaultHashMap<&str, u32> = DefaultHashMap::with_default(1);
+        counts["a"] += 1;
+        let map = DefaultSyncMap::from(counts);
+        assert_eq!(map.read(|m| m["a"] + m["b"]), 3);
+        assert_eq!(
+            format!("{:?}", map),
+            r#"DefaultSyncMap { map: {"a": 2}, default: 1 }"#
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::borrow::Borrow;
+use std::cell::OnceCell;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use crate::TryDefaultFn;
+
+/// A map that creates the values of missing keys with a function that can fail, like one that
+/// loads them from a file.
+///
+/// There is no default that can always be returned, so there's no `Index` implementation.
+/// Instead `try_get` and `try_get_mut` return the error of the function when a missing key is
+/// looked up and the default can't be created. Nothing is stored when that happens, so a later
+/// lookup tries again.
+///
+/// ```
+/// # use defaultmap::*;
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let available = Rc::new(Cell::new(false));
+/// let resource = available.clone();
+/// let mut templates: TryDefaultMap<&str, String, &str> = TryDefaultMap::with_try_fn(move || {
+///     match resource.get() {
+///         true => Ok("Hello {name}!".to_string()),
+///         false => Err("the template file is missing"),
+///     }
+/// });
+///
+/// assert_eq!(templates.try_get("greeting"), Err("the template file is missing"));
+/// available.set(true);
+/// assert_eq!(templates.try_get("greeting").unwrap(), "Hello {name}!");
+/// templates.try_get_mut("farewell").unwrap().replace_range(..5, "Bye");
+/// assert_eq!(templates.try_get("farewell").unwrap(), "Bye {name}!");
+/// assert_eq!(templates.len(), 1);
+/// ```
+pub struct TryDefaultMap<K: Eq + Hash, V, E> {
+    map: HashMap<K, V>,
+    default_fn: Arc<dyn TryDefaultFn<V, E>>,
+    // The default that `try_get` returns, created by the first lookup of a missing key that
+    // succeeds.
+    default: OnceCell<V>,
+}
+
+impl<K: Eq + Hash, V, E> TryDefaultMap<K, V, E> {
+    /// Creates an empty map with `default_fn` as the fallible default value generation function
+    /// for missing keys.
+    pub fn with_try_fn(default_fn: impl TryDefaultFn<V, E> + 'static) -> TryDefaultMap<K, V, E> {
+        TryDefaultMap {
+            map: HashMap::new(),
+            default_fn: Arc::new(default_fn),
+            default: OnceCell::new(),
+        }
+    }
+
+    /// Returns a reference to the value stored for the key, or to the default if there is none.
+    /// The default is only created once, by the first call for a missing key that succeeds.
+    pub fn try_get<Q>(&self, key: &Q) -> Result<&V, E>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        if let Some(value) = self.map.get(key) {
+            return Ok(value);
+        }
+        if let Some(default) = self.default.get() {
+            return Ok(default);
+        }
+        let default = self.default_fn.try_call()?;
+        Ok(self.default.get_or_init(|| default))
+    }
+
+    /// Returns a mutable reference to the value stored for the key. If there is no value stored,
+    /// one is created by the default value generation function and inserted first. When that
+    /// fails its error is returned and nothing is inserted.
+    pub fn try_get_mut(&mut self, key: K) -> Result<&mut V, E> {
+        match self.map.entry(key) {
+            Entry::Occupied(occupied) => Ok(occupied.into_mut()),
+            Entry::Vacant(vacant) => Ok(vacant.insert(self.default_fn.try_call()?)),
+        }
+    }
+
+    /// Inserts a value for the key and returns the previous value, if there was one.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.map.insert(key, value)
+    }
+
+    /// Removes the key and returns its value, if it had one.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.remove(key)
+    }
+
+    /// Returns `true` if a value is stored for the key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Returns the number of stored entries.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if no entries are stored.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator over the stored entries, in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.map.iter()
+    }
+
+    /// Returns the map that stores the entries.
+    pub fn into_map(self) -> HashMap<K, V> {
+        self.map
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone, E> Clone for TryDefaultMap<K, V, E> {
+    /// The clone shares the default value generation function of the original map.
+    fn clone(&self) -> Self {
+        TryDefaultMap {
+            map: self.map.clone(),
+            default_fn: self.default_fn.clone(),
+            default: self.default.clone(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + fmt::Debug, V: fmt::Debug, E> fmt::Debug for TryDefaultMap<K, V, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TryDefaultMap")
+            .field("map", &self.map)
+            .field("default", &self.default.get())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::TryDefaultMap;
+
+    #[test]
+    fn failing_defaults() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let mut map: TryDefaultMap<i32, i32, String> = TryDefaultMap::with_try_fn(move || {
+            counter.set(counter.get() + 1);
+            match counter.get() {
+                1 => Err("not yet".to_string()),
+                n => Ok(n * 10),
+            }
+        });
+        map.insert(1, 1);
+        assert_eq!(map.try_get(&1), Ok(&1));
+        assert_eq!(map.try_get(&2), Err("not yet".to_string()));
+        assert_eq!(map.try_get(&2), Ok(&20));
+        assert_eq!(map.try_get(&3), Ok(&20));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(map.len(), 1);
+
+        *map.try_get_mut(2).unwrap() += 1;
+        assert_eq!(map.try_get(&2), Ok(&31));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.remove(&2), Some(31));
+        assert!(!map.contains_key(&2));
+        assert_eq!(
+            format!("{:?}", map),
+            "TryDefaultMap { map: {1: 1}, default: Some(20) }"
+        );
+    }
+
+    #[test]
+    fn try_get_mut_error() {
+        let mut map: TryDefaultMap<&str, u32, &str> = TryDefaultMap::with_try_fn(|| Err("gone"));
+        assert_eq!(map.try_get_mut("a"), Err("gone"));
+        assert!(map.is_empty());
+        map.insert("a", 1);
+        assert_eq!(map.try_get_mut("a"), Ok(&mut 1));
+    }
+}
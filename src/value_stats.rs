@@ -0,0 +1,99 @@
+/// Numbers that can be converted to an `f64` for computing statistics, see [`ValueStats`]. The
+/// conversion is lossy for large 64 bit integers.
+pub trait AsF64 {
+    /// Returns the number as an `f64`.
+    fn as_f64(&self) -> f64;
+}
+
+macro_rules! impl_as_f64 {
+    ($($t:ty),*) => {
+        $(impl AsF64 for $t {
+            #[inline]
+            fn as_f64(&self) -> f64 {
+                *self as f64
+            }
+        })*
+    };
+}
+
+impl_as_f64!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// Statistics over the values of a map, as returned by the `value_stats` method of the maps.
+///
+/// ```
+/// # use defaultmap::*;
+/// let mut latencies: DefaultHashMap<&str, u32> = DefaultHashMap::new();
+/// latencies["a"] = 10;
+/// latencies["b"] = 30;
+///
+/// let stats = latencies.value_stats().unwrap();
+/// assert_eq!((stats.count, stats.min, stats.max), (2, 10.0, 30.0));
+/// assert_eq!((stats.mean, stats.variance), (20.0, 100.0));
+///
+/// let stats = latencies.value_stats_over(["a", "b", "c", "d"]).unwrap();
+/// assert_eq!((stats.count, stats.min, stats.mean), (4, 0.0, 10.0));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValueStats {
+    /// The number of values.
+    pub count: usize,
+    /// The smallest value.
+    pub min: f64,
+    /// The largest value.
+    pub max: f64,
+    /// The mean of the values.
+    pub mean: f64,
+    /// The population variance of the values, i.e. the mean of the squared differences from the
+    /// mean.
+    pub variance: f64,
+}
+
+impl ValueStats {
+    /// Computes the statistics in a single pass, or returns `None` if there are no values.
+    pub(crate) fn from_values<'a, V: AsF64 + 'a>(
+        values: impl IntoIterator<Item = &'a V>,
+    ) -> Option<ValueStats> {
+        let mut values = values.into_iter().map(AsF64::as_f64);
+        let first = values.next()?;
+        let mut stats = ValueStats {
+            count: 1,
+            min: first,
+            max: first,
+            mean: first,
+            variance: 0.0,
+        };
+        // Welford's algorithm, `variance` holds the sum of squared differences until the end.
+        for value in values {
+            stats.count += 1;
+            stats.min = stats.min.min(value);
+            stats.max = stats.max.max(value);
+            let delta = value - stats.mean;
+            stats.mean += delta / stats.count as f64;
+            stats.variance += delta * (value - stats.mean);
+        }
+        stats.variance /= stats.count as f64;
+        Some(stats)
+    }
+
+    /// Returns the standard deviation of the values, the square root of the variance.
+    pub fn std_dev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValueStats;
+
+    #[test]
+    fn from_values() {
+        assert_eq!(ValueStats::from_values(&[] as &[u8]), None);
+        let stats = ValueStats::from_values(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]).unwrap();
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.min, 2.0);
+        assert_eq!(stats.max, 9.0);
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.variance, 4.0);
+        assert_eq!(stats.std_dev(), 2.0);
+    }
+}